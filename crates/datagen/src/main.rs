@@ -10,8 +10,8 @@ use thread::DatagenThread;
 use monty::{
     chess::ChessState,
     mcts::MctsParams,
-    networks::{self, PolicyNetwork, ValueNetwork},
-    read_into_struct_unchecked, uci, MappedWeights,
+    networks::{self, NetworkFile, PolicyNetwork, ValueNetwork},
+    uci,
 };
 
 use std::{
@@ -29,14 +29,14 @@ fn main() {
     let mut args = std::env::args();
     args.next();
 
-    let policy_mapped: MappedWeights<networks::PolicyNetwork> =
-        unsafe { read_into_struct_unchecked(networks::PolicyFileDefaultName) };
+    let policy_file: NetworkFile<PolicyNetwork> =
+        NetworkFile::open_or_exit(networks::PolicyFileDefaultName);
 
-    let value_mapped: MappedWeights<networks::ValueNetwork> =
-        unsafe { read_into_struct_unchecked(networks::ValueFileDefaultName) };
+    let value_file: NetworkFile<ValueNetwork> =
+        NetworkFile::open_or_exit(networks::ValueFileDefaultName);
 
-    let policy = &policy_mapped.data;
-    let value = &value_mapped.data;
+    let policy = policy_file.get();
+    let value = value_file.get();
 
     let params = MctsParams::default();
 
@@ -184,7 +184,14 @@ pub fn run_datagen(
             let this_book = book.clone();
             let this_dest = dest_mutex.clone();
             s.spawn(move || {
-                let mut thread = DatagenThread::new(params.clone(), stop, this_book, this_dest);
+                let mut thread = DatagenThread::new(
+                    params.clone(),
+                    stop,
+                    this_book,
+                    this_dest,
+                    opts.gumbel,
+                    opts.nodes,
+                );
                 thread.run(opts.policy_data, policy, value);
             });
         }
@@ -203,6 +210,8 @@ pub struct RunOptions {
     policy_data: bool,
     nodes: usize,
     out_path: String,
+    /// Number of root moves sampled by a Gumbel root search, if enabled.
+    gumbel: Option<usize>,
 }
 
 pub fn parse_args(args: Args) -> Option<RunOptions> {
@@ -223,6 +232,7 @@ pub fn parse_args(args: Args) -> Option<RunOptions> {
             "-n" | "--nodes" => mode = 3,
             "-o" | "--output" => mode = 4,
             "-g" | "--games" => mode = 5,
+            "--gumbel" => mode = 6,
             _ => match mode {
                 1 => {
                     opts.threads = arg.parse().expect("can't parse");
@@ -244,6 +254,10 @@ pub fn parse_args(args: Args) -> Option<RunOptions> {
                     opts.games = arg.parse().expect("can't parse");
                     mode = 0;
                 }
+                6 => {
+                    opts.gumbel = Some(arg.parse().expect("can't parse"));
+                    mode = 0;
+                }
                 _ => println!("unrecognised argument {arg}"),
             },
         }
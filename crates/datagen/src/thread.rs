@@ -5,7 +5,7 @@ use crate::{
 
 use monty::{
     chess::{ChessState, GameState},
-    mcts::{Limits, MctsParams, Searcher},
+    mcts::{GumbelRoot, Limits, MctsParams, Searcher},
     networks::{PolicyNetwork, ValueNetwork},
     tree::Tree,
 };
@@ -22,14 +22,21 @@ pub struct DatagenThread<'a> {
     dest: Arc<Mutex<Destination>>,
     stop: &'a AtomicBool,
     book: Option<OpeningBookReader>,
+    gumbel: Option<usize>,
+    nodes: usize,
 }
 
+/// Node budget of a Gumbel root search if none is given.
+const GUMBEL_DEFAULT_NODES: usize = 800;
+
 impl<'a> DatagenThread<'a> {
     pub fn new(
         params: MctsParams,
         stop: &'a AtomicBool,
         book: Option<OpeningBook>,
         dest: Arc<Mutex<Destination>>,
+        gumbel: Option<usize>,
+        nodes: usize,
     ) -> Self {
         let book = book.map(|book| book.reader().expect("failed to open opening book reader"));
 
@@ -39,6 +46,8 @@ impl<'a> DatagenThread<'a> {
             dest,
             stop,
             book,
+            gumbel,
+            nodes,
         }
     }
 
@@ -69,12 +78,29 @@ impl<'a> DatagenThread<'a> {
             return;
         }
 
-        let limits = Limits {
-            max_depth: 64,
-            max_nodes: 100000,
-            max_time: None,
-            opt_time: None,
-            kld_min_gain: Some(0.000005),
+        let limits = if self.gumbel.is_some() {
+            // sequential halving relies on the whole budget being used
+            Limits {
+                max_depth: 64,
+                max_nodes: if self.nodes > 0 {
+                    self.nodes
+                } else {
+                    GUMBEL_DEFAULT_NODES
+                },
+                max_time: None,
+                opt_time: None,
+                mate: None,
+                kld_min_gain: None,
+            }
+        } else {
+            Limits {
+                max_depth: 64,
+                max_nodes: 100000,
+                max_time: None,
+                opt_time: None,
+                mate: None,
+                kld_min_gain: Some(0.000005),
+            }
         };
 
         let mut result = 0.5;
@@ -105,10 +131,28 @@ impl<'a> DatagenThread<'a> {
 
             let abort = AtomicBool::new(false);
             tree.set_root_position(&position);
-            let searcher = Searcher::new(&tree, &self.params, policy, value, &abort);
 
-            let (best_move, score, iters) =
-                searcher.search(1, limits, false, 1, false, &mut 0, true, temp);
+            let gumbel = self.gumbel.map(|considered| {
+                GumbelRoot::new(limits.max_nodes, considered, || {
+                    ((self.rng.rand_int() >> 8) as f32 + 0.5) / (1 << 24) as f32
+                })
+            });
+
+            let mut searcher = Searcher::new(&tree, &self.params, policy, value, &abort);
+            if let Some(gumbel) = &gumbel {
+                searcher = searcher.with_selection(gumbel);
+            }
+
+            let (mut best_move, score, iters) = if gumbel.is_some() {
+                // the Gumbel noise replaces dirichlet noise and temperature
+                searcher.search(1, limits, false, 1, false, &mut 0, false, 0.0)
+            } else {
+                searcher.search(1, limits, false, 1, false, &mut 0, true, temp)
+            };
+
+            if let Some(gumbel) = &gumbel {
+                best_move = gumbel.best_move(&tree);
+            }
 
             searches += 1;
             total_iters += iters;
@@ -128,13 +172,21 @@ impl<'a> DatagenThread<'a> {
             } else {
                 let mut dist = Vec::new();
 
-                let actions = tree[tree.root_node()].actions();
-
-                for action in 0..tree[tree.root_node()].num_actions() {
-                    let node = &tree[actions + action];
-                    let mov = node.parent_move();
-                    let visits = node.visits().min(u32::MAX as u64) as u32;
-                    dist.push((mov, visits));
+                if let Some(gumbel) = &gumbel {
+                    // the improved policy as pseudo-visits, as only
+                    // the relative counts are kept when serialised
+                    for (mov, pi) in gumbel.improved_policy(&tree) {
+                        dist.push((mov, (pi * f32::from(u16::MAX)).round() as u32));
+                    }
+                } else {
+                    let actions = tree[tree.root_node()].actions();
+
+                    for action in 0..tree[tree.root_node()].num_actions() {
+                        let node = &tree[actions + action];
+                        let mov = node.parent_move();
+                        let visits = node.visits().min(u32::MAX as u64) as u32;
+                        dist.push((mov, visits));
+                    }
                 }
 
                 assert_eq!(root_count, dist.len());
@@ -0,0 +1,48 @@
+//! Feeds arbitrary bytes to the binpack readers, which must return
+//! errors for malformed data rather than panicking.
+//!
+//! Run with `cargo fuzz run readers` from `crates/montyformat`.
+
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+use montyformat::{FastDeserialise, MontyFormat, MontyValueFormat};
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = Cursor::new(data);
+    while let Ok(game) = MontyFormat::deserialise_from(&mut reader) {
+        // anything read successfully must also write successfully,
+        // and read back as a game that is written the same way
+        let mut written = Vec::new();
+        game.serialise_into_buffer(&mut written).unwrap();
+
+        let reread = MontyFormat::deserialise_from(&mut Cursor::new(&written)).unwrap();
+        let mut rewritten = Vec::new();
+        reread.serialise_into_buffer(&mut rewritten).unwrap();
+
+        assert_eq!(written, rewritten);
+    }
+
+    let mut reader = Cursor::new(data);
+    while let Ok(game) = MontyValueFormat::deserialise_from(&mut reader, Vec::new()) {
+        let mut written = Vec::new();
+        game.serialise_into(&mut written).unwrap();
+
+        let reread =
+            MontyValueFormat::deserialise_from(&mut Cursor::new(&written), Vec::new()).unwrap();
+        let mut rewritten = Vec::new();
+        reread.serialise_into(&mut rewritten).unwrap();
+
+        assert_eq!(written, rewritten);
+    }
+
+    let mut buffer = Vec::new();
+
+    let mut reader = Cursor::new(data);
+    while MontyFormat::deserialise_fast_into_buffer(&mut reader, &mut buffer).is_ok() {}
+
+    let mut reader = Cursor::new(data);
+    while MontyValueFormat::deserialise_fast_into_buffer(&mut reader, &mut buffer).is_ok() {}
+});
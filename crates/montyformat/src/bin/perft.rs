@@ -1,19 +1,126 @@
-use std::time::Instant;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    process,
+    time::Instant,
+};
 
-use montyformat::chess::{perft, Castling, Position};
+use montyformat::chess::{
+    perft::{check_epd, perft_divide, EpdEntry, PerftTable},
+    Castling, Position,
+};
 
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+/// Usage:
+///   perft [depth] [--threads n] [--hash mb] [--fen <fen>]
+///   perft --epd <file> [--depth max] [--threads n] [--hash mb]
+///
+/// Exits with a non-zero status if any position in an EPD suite mismatches
+/// or is not a valid position.
 fn main() {
+    let mut depth = None;
+    let mut threads = 1;
+    let mut hash_mb = 0;
+    let mut fen = KIWIPETE.to_string();
+    let mut epd = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| usage(&format!("{arg} needs a value")))
+        };
+
+        match arg.as_str() {
+            "--threads" => {
+                threads = value()
+                    .parse()
+                    .unwrap_or_else(|_| usage("bad thread count"))
+            }
+            "--hash" => hash_mb = value().parse().unwrap_or_else(|_| usage("bad hash size")),
+            "--depth" => depth = Some(value().parse().unwrap_or_else(|_| usage("bad depth"))),
+            "--fen" => fen = value(),
+            "--epd" => epd = Some(value()),
+            _ => depth = Some(arg.parse().unwrap_or_else(|_| usage("bad depth"))),
+        }
+    }
+
+    let table = (hash_mb > 0).then(|| PerftTable::new(hash_mb));
+
+    if let Some(path) = epd {
+        run_suite(&path, depth.unwrap_or(u8::MAX), threads, table.as_ref());
+        return;
+    }
+
+    let depth = depth.unwrap_or(6).max(1);
     let mut castling = Castling::default();
-    let pos = Position::parse_fen(
-        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
-        &mut castling,
-    );
+    let pos = Position::parse_fen(&fen, &mut castling);
 
     let now = Instant::now();
-    let count = perft::<true>(&pos, &castling, 6);
+    let divide = perft_divide(&pos, &castling, depth, threads, table.as_ref());
     let time = now.elapsed().as_secs_f64();
+
+    for &(mov, count) in &divide {
+        println!("{}: {count}", mov.to_uci(&castling));
+    }
+
+    let count = divide.iter().map(|&(_, count)| count).sum::<u64>();
     println!(
         "nodes {count} time {time:.3} nps {:.0}",
         count as f64 / time
     );
 }
+
+fn run_suite(path: &str, max_depth: u8, threads: usize, table: Option<&PerftTable>) {
+    let file = File::open(path).unwrap_or_else(|err| usage(&format!("{path}: {err}")));
+
+    let now = Instant::now();
+    let mut positions = 0;
+    let mut failed = 0;
+
+    for line in BufReader::new(file).lines() {
+        let line = line.unwrap();
+        let Some(entry) = EpdEntry::parse(&line) else {
+            continue;
+        };
+
+        positions += 1;
+
+        let mismatches = match check_epd(&entry, max_depth, threads, table) {
+            Ok(mismatches) => mismatches,
+            Err(err) => {
+                println!("bad entry {}: {err}", entry.fen);
+                failed += 1;
+                continue;
+            }
+        };
+
+        if !mismatches.is_empty() {
+            failed += 1;
+        }
+
+        for mismatch in mismatches {
+            println!(
+                "mismatch {} depth {} expected {} found {}",
+                entry.fen, mismatch.depth, mismatch.expected, mismatch.found
+            );
+        }
+    }
+
+    println!(
+        "positions {positions} failed {failed} time {:.3}",
+        now.elapsed().as_secs_f64()
+    );
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn usage(err: &str) -> ! {
+    eprintln!("{err}");
+    eprintln!("usage: perft [depth] [--threads n] [--hash mb] [--fen <fen>]");
+    eprintln!("       perft --epd <file> [--depth max] [--threads n] [--hash mb]");
+    process::exit(2);
+}
@@ -2,6 +2,7 @@ pub mod attacks;
 pub mod consts;
 pub mod frc;
 pub mod moves;
+pub mod perft;
 pub mod position;
 
 pub const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
@@ -10,6 +11,7 @@ pub use attacks::Attacks;
 pub use consts::{Flag, Piece, Right, Side};
 pub use frc::Castling;
 pub use moves::Move;
+pub use perft::perft;
 pub use position::Position;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
@@ -57,28 +59,3 @@ impl std::fmt::Display for GameState {
         }
     }
 }
-
-pub fn perft<const REPORT: bool>(pos: &Position, castling: &Castling, depth: u8) -> u64 {
-    if depth == 1 {
-        let mut count = 0;
-        pos.map_legal_moves(castling, |_| count += 1);
-        return count;
-    }
-
-    let mut count = 0;
-
-    pos.map_legal_moves(castling, |mov| {
-        let mut new = *pos;
-        new.make(mov, castling);
-
-        let sub_count = perft::<false>(&new, castling, depth - 1);
-
-        if REPORT {
-            println!("{}: {sub_count}", mov.to_uci(castling));
-        }
-
-        count += sub_count;
-    });
-
-    count
-}
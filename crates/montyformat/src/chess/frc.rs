@@ -61,8 +61,25 @@ impl Castling {
         ret
     }
 
+    /// Choose whether castling moves are written as the king capturing its
+    /// own rook (Chess960) or as the king moving two squares.
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    /// Parse the castling field of a FEN, accepting standard (`KQkq`),
+    /// Shredder-FEN (`HAha`) and X-FEN rights, where `K`/`Q` refer to
+    /// the outermost rook on that side of the king.
     pub fn parse(&mut self, pos: &Position, rights_str: &str) -> u8 {
-        let mut kings = [4, 4];
+        let mut kings = [Side::WHITE, Side::BLACK].map(|side| {
+            let king = pos.piece(side) & pos.piece(Piece::KING);
+
+            if king == 0 {
+                4
+            } else {
+                king.trailing_zeros() as usize & 7
+            }
+        });
 
         self.chess960 = false;
         self.rook_files[0][0] = 0;
@@ -72,16 +89,26 @@ impl Castling {
 
         let rights = rights_str.chars().fold(0, |cr, ch| {
             cr | match ch as u8 {
-                b'Q' => Right::WQS,
-                b'K' => Right::WKS,
-                b'q' => Right::BQS,
-                b'k' => Right::BKS,
+                b'Q' => self.parse_outermost(pos, Side::WHITE, &kings, false),
+                b'K' => self.parse_outermost(pos, Side::WHITE, &kings, true),
+                b'q' => self.parse_outermost(pos, Side::BLACK, &kings, false),
+                b'k' => self.parse_outermost(pos, Side::BLACK, &kings, true),
                 b'A'..=b'H' => self.parse_castle(pos, Side::WHITE, &mut kings, ch),
                 b'a'..=b'h' => self.parse_castle(pos, Side::BLACK, &mut kings, ch),
                 _ => 0,
             }
         });
 
+        // X-FEN rights in a non-standard starting position
+        for (side, rights_mask) in [
+            (Side::WHITE, Right::WQS | Right::WKS),
+            (Side::BLACK, Right::BQS | Right::BKS),
+        ] {
+            if rights & rights_mask > 0 && (kings[side] != 4 || self.rook_files[side] != [0, 7]) {
+                self.chess960 = true;
+            }
+        }
+
         for sq in self.castle_mask.iter_mut() {
             *sq = 15;
         }
@@ -96,6 +123,26 @@ impl Castling {
         rights
     }
 
+    fn parse_outermost(&mut self, pos: &Position, side: usize, kings: &[usize; 2], ks: bool) -> u8 {
+        let rooks = pos.piece(side) & pos.piece(Piece::ROOK);
+        let back_rank = (rooks >> (56 * side)) as u8;
+        let king = kings[side] as u8;
+
+        let rook = if ks {
+            (king + 1..8)
+                .rev()
+                .find(|&file| back_rank & (1 << file) > 0)
+        } else {
+            (0..king).find(|&file| back_rank & (1 << file) > 0)
+        };
+
+        if let Some(rook) = rook {
+            self.rook_files[side][usize::from(ks)] = rook;
+        }
+
+        [[Right::WQS, Right::WKS], [Right::BQS, Right::BKS]][side][usize::from(ks)]
+    }
+
     fn parse_castle(
         &mut self,
         pos: &Position,
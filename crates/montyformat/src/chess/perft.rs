@@ -0,0 +1,284 @@
+use std::{
+    io,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    thread,
+};
+
+use super::{Castling, Move, Position};
+use crate::format::validate_startpos;
+
+pub fn perft<const REPORT: bool>(pos: &Position, castling: &Castling, depth: u8) -> u64 {
+    if depth == 1 {
+        let mut count = 0;
+        pos.map_legal_moves(castling, |_| count += 1);
+        return count;
+    }
+
+    let mut count = 0;
+
+    pos.map_legal_moves(castling, |mov| {
+        let mut new = *pos;
+        new.make(mov, castling);
+
+        let sub_count = perft::<false>(&new, castling, depth - 1);
+
+        if REPORT {
+            println!("{}: {sub_count}", mov.to_uci(castling));
+        }
+
+        count += sub_count;
+    });
+
+    count
+}
+
+#[derive(Default)]
+struct PerftEntry {
+    /// The key xor the count, so that torn writes from
+    /// other threads are detected when probing.
+    check: AtomicU64,
+    count: AtomicU64,
+}
+
+/// Node counts of previously visited subtrees, keyed on `Position::hash`,
+/// the castling rook files and depth, shared between threads without locking.
+pub struct PerftTable {
+    entries: Vec<PerftEntry>,
+}
+
+impl PerftTable {
+    #[must_use]
+    pub fn new(mb: usize) -> Self {
+        let len = (mb * 1024 * 1024 / std::mem::size_of::<PerftEntry>()).max(1);
+        let mut entries = Vec::with_capacity(len);
+        entries.resize_with(len, PerftEntry::default);
+
+        Self { entries }
+    }
+
+    /// The rook files are part of the key, as in Chess960 the same
+    /// position and rights can castle with different rooks.
+    fn key(hash: u64, castling: &Castling, depth: u8) -> u64 {
+        let files = u32::from_le_bytes(castling.rook_files().concat().try_into().unwrap());
+
+        hash ^ u64::from(depth).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ u64::from(files).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+    }
+
+    fn entry(&self, key: u64) -> &PerftEntry {
+        let idx = (u128::from(key) * self.entries.len() as u128) >> 64;
+        &self.entries[idx as usize]
+    }
+
+    fn probe(&self, hash: u64, castling: &Castling, depth: u8) -> Option<u64> {
+        let key = Self::key(hash, castling, depth);
+        let entry = self.entry(key);
+
+        let count = entry.count.load(Ordering::Relaxed);
+        let check = entry.check.load(Ordering::Relaxed);
+
+        (count != 0 && check ^ count == key).then_some(count)
+    }
+
+    fn store(&self, hash: u64, castling: &Castling, depth: u8, count: u64) {
+        let key = Self::key(hash, castling, depth);
+        let entry = self.entry(key);
+
+        entry.count.store(count, Ordering::Relaxed);
+        entry.check.store(key ^ count, Ordering::Relaxed);
+    }
+}
+
+fn perft_hashed(pos: &Position, castling: &Castling, depth: u8, table: Option<&PerftTable>) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    if depth == 1 {
+        let mut count = 0;
+        pos.map_legal_moves(castling, |_| count += 1);
+        return count;
+    }
+
+    if let Some(count) = table.and_then(|table| table.probe(pos.hash(), castling, depth)) {
+        return count;
+    }
+
+    let mut count = 0;
+
+    pos.map_legal_moves(castling, |mov| {
+        let mut new = *pos;
+        new.make(mov, castling);
+        count += perft_hashed(&new, castling, depth - 1, table);
+    });
+
+    if let Some(table) = table {
+        table.store(pos.hash(), castling, depth, count);
+    }
+
+    count
+}
+
+/// The node count below each root move, in move generation order, with
+/// the root moves split between `threads` threads. `depth` includes the
+/// root move, so must be at least 1.
+pub fn perft_divide(
+    pos: &Position,
+    castling: &Castling,
+    depth: u8,
+    threads: usize,
+    table: Option<&PerftTable>,
+) -> Vec<(Move, u64)> {
+    let mut moves = Vec::new();
+    pos.map_legal_moves(castling, |mov| moves.push(mov));
+
+    let counts = (0..moves.len())
+        .map(|_| AtomicU64::new(0))
+        .collect::<Vec<_>>();
+    let next = AtomicUsize::new(0);
+
+    thread::scope(|s| {
+        for _ in 0..threads.clamp(1, moves.len().max(1)) {
+            s.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&mov) = moves.get(idx) else {
+                    break;
+                };
+
+                let mut new = *pos;
+                new.make(mov, castling);
+
+                let count = perft_hashed(&new, castling, depth.saturating_sub(1), table);
+                counts[idx].store(count, Ordering::Relaxed);
+            });
+        }
+    });
+
+    moves
+        .into_iter()
+        .zip(counts)
+        .map(|(mov, count)| (mov, count.into_inner()))
+        .collect()
+}
+
+/// A line of a perft suite in EPD format, `<fen> ;D1 <count> ;D2 <count> ...`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EpdEntry {
+    pub fen: String,
+    /// Expected node counts, as `(depth, count)`.
+    pub counts: Vec<(u8, u64)>,
+}
+
+impl EpdEntry {
+    /// Parses a line of a perft suite, returning `None` for blank
+    /// lines, comments starting with `#`, and malformed lines.
+    #[must_use]
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let mut fields = line.split(';');
+        let fen = fields.next()?.trim().to_string();
+
+        let counts = fields
+            .map(|field| {
+                let mut parts = field.split_whitespace();
+                let depth = parts.next()?.strip_prefix('D')?.parse().ok()?;
+                let count = parts.next()?.parse().ok()?;
+                Some((depth, count))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self { fen, counts })
+    }
+}
+
+/// A depth at which an `EpdEntry` disagreed with perft.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PerftMismatch {
+    pub depth: u8,
+    pub expected: u64,
+    pub found: u64,
+}
+
+/// Parse `fen`, checking that it is well formed and that moves can be
+/// generated from it, so that a bad suite entry is an error rather than
+/// a panic.
+fn parse_position(fen: &str) -> io::Result<(Position, Castling)> {
+    let invalid = |msg: &str| Err(io::Error::new(io::ErrorKind::InvalidData, msg.to_string()));
+
+    let fields = fen.split_whitespace().collect::<Vec<_>>();
+    if !(4..=6).contains(&fields.len()) {
+        return invalid("FEN must have 4 to 6 fields!");
+    }
+
+    let ranks = fields[0].split('/').collect::<Vec<_>>();
+    let rank_len = |rank: &str| {
+        rank.chars()
+            .map(|ch| ch.to_digit(10).unwrap_or(1))
+            .sum::<u32>()
+    };
+
+    if ranks.len() != 8
+        || ranks.iter().any(|&rank| rank_len(rank) != 8)
+        || !fields[0]
+            .chars()
+            .all(|ch| "PNBRQKpnbrqk12345678/".contains(ch))
+    {
+        return invalid("Invalid board in FEN!");
+    }
+
+    if !["w", "b"].contains(&fields[1]) {
+        return invalid("Invalid side to move in FEN!");
+    }
+
+    let enp = fields[3].as_bytes();
+    if fields[3] != "-"
+        && !(enp.len() == 2 && (b'a'..=b'h').contains(&enp[0]) && (b'1'..=b'8').contains(&enp[1]))
+    {
+        return invalid("Invalid en passant square in FEN!");
+    }
+
+    let mut castling = Castling::default();
+    let pos = Position::parse_fen(fen, &mut castling);
+    validate_startpos(&pos, &castling.rook_files())?;
+
+    Ok((pos, castling))
+}
+
+/// Check the counts of `entry` up to `max_depth`, returning those
+/// that do not match, or an error if its FEN is not a valid position.
+pub fn check_epd(
+    entry: &EpdEntry,
+    max_depth: u8,
+    threads: usize,
+    table: Option<&PerftTable>,
+) -> io::Result<Vec<PerftMismatch>> {
+    let (pos, castling) = parse_position(&entry.fen)?;
+
+    Ok(entry
+        .counts
+        .iter()
+        .filter(|&&(depth, _)| depth <= max_depth)
+        .filter_map(|&(depth, expected)| {
+            // only the position itself is counted at depth 0
+            let found = if depth == 0 {
+                1
+            } else {
+                perft_divide(&pos, &castling, depth, threads, table)
+                    .iter()
+                    .map(|&(_, count)| count)
+                    .sum()
+            };
+
+            (found != expected).then_some(PerftMismatch {
+                depth,
+                expected,
+                found,
+            })
+        })
+        .collect())
+}
@@ -1,9 +1,9 @@
 use std::io::{Error, ErrorKind, Write};
 
 use crate::{
-    chess::{Castling, Move, Position},
+    chess::{Castling, Move, Piece, Position, Right, Side},
     interleave::{interleave, FastDeserialise},
-    read_into_primitive,
+    read_into_primitive, read_primitive_into_vec,
 };
 
 const GAME_HEADER_SIZE: usize = 43;
@@ -156,6 +156,7 @@ impl MontyFormat {
             }
         }
 
+        validate_startpos(&startpos, &rook_files)?;
         let castling = Castling::from_raw(&startpos, rook_files);
 
         let result = read_into_primitive!(reader, u8) as f32 / 2.0;
@@ -175,21 +176,32 @@ impl MontyFormat {
 
             let num_moves = read_into_primitive!(reader, u8);
 
+            let mut legal = Vec::new();
+            pos.map_legal_moves(&castling, |mov| legal.push((mov, 0)));
+
+            if !legal.iter().any(|&(mov, _)| mov == best_move) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Illegal move {best_move:?} in {}", pos.as_fen()),
+                ));
+            }
+
             let visit_distribution = if num_moves == 0 {
                 None
             } else {
-                let mut dist = Vec::with_capacity(usize::from(num_moves));
-
-                pos.map_legal_moves(&castling, |mov| dist.push((mov, 0)));
+                let mut dist = legal;
                 dist.sort_by_key(|(mov, _)| u16::from(*mov));
 
-                assert_eq!(
-                    dist.len(),
-                    usize::from(num_moves),
-                    "{}\n{:?}",
-                    pos.as_fen(),
-                    castling.rook_files(),
-                );
+                if dist.len() != usize::from(num_moves) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "{num_moves} visit counts for {} legal moves in {}",
+                            dist.len(),
+                            pos.as_fen()
+                        ),
+                    ));
+                }
 
                 for entry in &mut dist {
                     entry.1 = u32::from(read_into_primitive!(reader, u8));
@@ -233,16 +245,17 @@ impl FastDeserialise for MontyFormat {
         buffer.extend_from_slice(&header);
 
         loop {
-            let mut move_header = [0u8; 5];
-            reader.read_exact(&mut move_header)?;
-            buffer.extend_from_slice(&move_header);
-
-            let best_move = Move::from(u16::from_le_bytes([move_header[0], move_header[1]]));
+            // the game is terminated by a null move alone
+            let best_move = Move::from(read_primitive_into_vec!(reader, buffer, u16));
             if best_move == Move::NULL {
                 break;
             }
 
-            let move_count = usize::from(move_header[4]);
+            let mut move_header = [0u8; 3];
+            reader.read_exact(&mut move_header)?;
+            buffer.extend_from_slice(&move_header);
+
+            let move_count = usize::from(move_header[2]);
             if move_count > 0 {
                 let start_len = buffer.len();
                 buffer.resize(start_len + move_count, 0);
@@ -254,6 +267,74 @@ impl FastDeserialise for MontyFormat {
     }
 }
 
+/// Checks that a start position read from a binpack is one that games can
+/// be played out from, so that corrupt data is an error rather than a panic.
+pub(crate) fn validate_startpos(pos: &Position, rook_files: &[[u8; 2]; 2]) -> std::io::Result<()> {
+    let invalid = |msg: &str| Err(Error::new(ErrorKind::InvalidData, msg.to_string()));
+
+    let bbs = pos.bbs();
+    let occ = bbs[Side::WHITE] | bbs[Side::BLACK];
+    let pieces = bbs[Piece::PAWN..].iter().fold(0, |acc, bb| acc | bb);
+
+    if bbs[Side::WHITE] & bbs[Side::BLACK] != 0 || pieces != occ {
+        return invalid("Inconsistent piece bitboards!");
+    }
+
+    for side in [Side::WHITE, Side::BLACK] {
+        if (bbs[side] & bbs[Piece::KING]).count_ones() != 1 {
+            return invalid("Each side must have exactly one king!");
+        }
+    }
+
+    let them = pos.stm() ^ 1;
+    if pos.is_square_attacked(pos.king_sq(them), them, occ) {
+        return invalid("Side not to move is in check!");
+    }
+
+    if rook_files.iter().flatten().any(|&file| file >= 8) || pos.rights() > 15 {
+        return invalid("Invalid castling rights!");
+    }
+
+    let castles = [
+        (Right::WQS, Side::WHITE, 0),
+        (Right::WKS, Side::WHITE, 1),
+        (Right::BQS, Side::BLACK, 0),
+        (Right::BKS, Side::BLACK, 1),
+    ];
+
+    for (right, side, ks) in castles {
+        let back_rank = 56 * side;
+        let rook = 1 << (back_rank + usize::from(rook_files[side][ks]));
+        let king_on_back_rank = pos.king_sq(side) / 8 == back_rank / 8;
+
+        if pos.rights() & right > 0
+            && (!king_on_back_rank || bbs[side] & bbs[Piece::ROOK] & rook == 0)
+        {
+            return invalid("Castling rights without a king and rook to castle!");
+        }
+    }
+
+    let enp_sq = usize::from(pos.enp_sq());
+    if enp_sq > 0 {
+        // the square passed over by a pawn of the side not to move
+        let (rank, pawn_sq) = if pos.stm() == Side::WHITE {
+            (5, enp_sq.wrapping_sub(8))
+        } else {
+            (2, enp_sq + 8)
+        };
+
+        if enp_sq >= 64
+            || enp_sq / 8 != rank
+            || occ & (1 << enp_sq) != 0
+            || bbs[them] & bbs[Piece::PAWN] & (1 << pawn_sq) == 0
+        {
+            return invalid("Invalid en passant square!");
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(Clone, Copy)]
 pub struct CompressedChessBoard {
     pub bbs: [u64; 4],
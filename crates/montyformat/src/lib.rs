@@ -3,7 +3,7 @@ mod format;
 mod interleave;
 mod value;
 
-pub use format::{MontyFormat, SearchData};
+pub use format::{CompressedChessBoard, MontyFormat, SearchData};
 pub use interleave::FastDeserialise;
 pub use value::{MontyValueFormat, SearchResult};
 
@@ -1,6 +1,6 @@
 use crate::{
     chess::{Castling, Move, Position},
-    format::CompressedChessBoard,
+    format::{validate_startpos, CompressedChessBoard},
     interleave::{interleave, FastDeserialise},
     read_into_primitive, read_primitive_into_vec,
 };
@@ -92,6 +92,7 @@ impl MontyValueFormat {
             }
         }
 
+        validate_startpos(&startpos, &rook_files)?;
         let castling = Castling::from_raw(&startpos, rook_files);
 
         let result = read_into_primitive!(reader, u8) as f32 / 2.0;
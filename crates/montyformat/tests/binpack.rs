@@ -0,0 +1,237 @@
+use std::io::Cursor;
+
+mod common;
+
+use common::{legal_moves, parse, Rand, FENS};
+use montyformat::{
+    chess::{GameState, Position},
+    FastDeserialise, MontyFormat, MontyValueFormat, SearchData,
+};
+
+/// A random legal game from one of `FENS`, with random scores and
+/// visit distributions (on roughly half of the positions).
+fn random_game(rng: &mut Rand) -> MontyFormat {
+    let (startpos, castling) = parse(FENS[rng.below(FENS.len())]);
+
+    let mut game = MontyFormat::new(startpos, castling);
+    game.result = rng.below(3) as f32 / 2.0;
+
+    let mut pos = startpos;
+    let mut stack = Vec::new();
+    let plies = rng.below(120);
+
+    for _ in 0..plies {
+        if pos.game_state(&castling, &stack) != GameState::Ongoing {
+            break;
+        }
+
+        let moves = legal_moves(&pos, &castling);
+        let best_move = moves[rng.below(moves.len())];
+
+        let visits = (rng.below(2) == 0).then(|| {
+            moves
+                .iter()
+                .map(|&mov| (mov, rng.below(10_000) as u32))
+                .collect()
+        });
+
+        game.push(SearchData::new(best_move, rng.unit(), visits));
+
+        stack.push(pos.hash());
+        pos.make(best_move, &castling);
+    }
+
+    game
+}
+
+fn serialise(game: &MontyFormat) -> Vec<u8> {
+    let mut buf = Vec::new();
+    game.serialise_into_buffer(&mut buf).unwrap();
+    buf
+}
+
+fn value_game(game: &MontyFormat) -> MontyValueFormat {
+    let mut value = MontyValueFormat {
+        startpos: game.startpos,
+        castling: game.castling,
+        result: game.result,
+        moves: Vec::new(),
+    };
+
+    let mut stm = game.startpos.stm();
+    for data in &game.moves {
+        value.push(stm, data.best_move, data.score.clamp(0.001, 0.999));
+        stm = 1 - stm;
+    }
+
+    value
+}
+
+fn same_startpos(a: &Position, b: &Position) -> bool {
+    a.bbs() == b.bbs()
+        && a.stm() == b.stm()
+        && a.enp_sq() == b.enp_sq()
+        && a.rights() == b.rights()
+        && a.halfm() == b.halfm()
+        && a.fullm() == b.fullm()
+}
+
+#[test]
+fn policy_format_round_trips() {
+    let mut rng = Rand(0x9E37_79B9_7F4A_7C15);
+
+    for _ in 0..64 {
+        let game = random_game(&mut rng);
+        let bytes = serialise(&game);
+        let read = MontyFormat::deserialise_from(&mut Cursor::new(&bytes)).unwrap();
+
+        assert!(same_startpos(&read.startpos, &game.startpos));
+        assert_eq!(read.castling.rook_files(), game.castling.rook_files());
+        assert_eq!(read.result, game.result);
+        assert_eq!(read.moves.len(), game.moves.len());
+
+        for (read, data) in read.moves.iter().zip(&game.moves) {
+            assert_eq!(read.best_move, data.best_move);
+            assert!((read.score - data.score).abs() <= 1.0 / f32::from(u16::MAX));
+
+            match (&read.visit_distribution, &data.visit_distribution) {
+                (None, None) => {}
+                (Some(read), Some(dist)) => {
+                    // visits are quantised relative to the most visited move
+                    let max = dist.iter().map(|&(_, visits)| visits).max().unwrap();
+
+                    assert_eq!(read.len(), dist.len());
+                    for (&(read_mov, read_visits), &(mov, visits)) in read.iter().zip(dist) {
+                        let expected = (visits as f32 * 255.0 / max as f32).round() as u32;
+                        assert_eq!(read_mov, mov);
+                        assert_eq!(read_visits, expected);
+                    }
+                }
+                _ => panic!("visit distribution lost"),
+            }
+        }
+
+        // quantisation is idempotent, so a second round trip is exact
+        assert_eq!(serialise(&read), bytes);
+    }
+}
+
+#[test]
+fn value_format_round_trips() {
+    let mut rng = Rand(0xD1B5_4A32_D192_ED03);
+
+    for _ in 0..64 {
+        let game = value_game(&random_game(&mut rng));
+
+        let mut bytes = Vec::new();
+        game.serialise_into(&mut bytes).unwrap();
+        let read =
+            MontyValueFormat::deserialise_from(&mut Cursor::new(&bytes), Vec::new()).unwrap();
+
+        assert!(same_startpos(&read.startpos, &game.startpos));
+        assert_eq!(read.castling.rook_files(), game.castling.rook_files());
+        assert_eq!(read.result, game.result);
+        assert_eq!(read.moves.len(), game.moves.len());
+
+        for (read, result) in read.moves.iter().zip(&game.moves) {
+            assert_eq!(read.best_move, result.best_move);
+            assert_eq!(read.score, result.score);
+        }
+    }
+}
+
+#[test]
+fn fast_deserialise_consumes_whole_games() {
+    let mut rng = Rand(0x2545_F491_4F6C_DD1D);
+    let games = (0..32).map(|_| random_game(&mut rng)).collect::<Vec<_>>();
+
+    let policy = games.iter().map(serialise).collect::<Vec<_>>();
+    let value = games
+        .iter()
+        .map(|game| {
+            let mut bytes = Vec::new();
+            value_game(game).serialise_into(&mut bytes).unwrap();
+            bytes
+        })
+        .collect::<Vec<_>>();
+
+    fn check<T: FastDeserialise>(games: &[Vec<u8>]) {
+        let concat = games.concat();
+        let mut reader = Cursor::new(&concat);
+        let mut buffer = Vec::new();
+
+        for game in games {
+            T::deserialise_fast_into_buffer(&mut reader, &mut buffer).unwrap();
+            assert_eq!(&buffer, game);
+        }
+
+        assert_eq!(reader.position() as usize, concat.len());
+        assert!(T::deserialise_fast_into_buffer(&mut reader, &mut buffer).is_err());
+    }
+
+    check::<MontyFormat>(&policy);
+    check::<MontyValueFormat>(&value);
+}
+
+/// Feed `bytes` to every reader, which may fail but must not panic, as
+/// in the fuzz target. Anything read must write and read back the same.
+fn read_all(bytes: &[u8]) {
+    let mut reader = Cursor::new(bytes);
+    while let Ok(game) = MontyFormat::deserialise_from(&mut reader) {
+        let written = serialise(&game);
+        let reread = MontyFormat::deserialise_from(&mut Cursor::new(&written)).unwrap();
+        assert_eq!(serialise(&reread), written);
+    }
+
+    let mut reader = Cursor::new(bytes);
+    while let Ok(game) = MontyValueFormat::deserialise_from(&mut reader, Vec::new()) {
+        let mut written = Vec::new();
+        game.serialise_into(&mut written).unwrap();
+
+        let reread =
+            MontyValueFormat::deserialise_from(&mut Cursor::new(&written), Vec::new()).unwrap();
+        let mut rewritten = Vec::new();
+        reread.serialise_into(&mut rewritten).unwrap();
+
+        assert_eq!(rewritten, written);
+    }
+
+    let mut buffer = Vec::new();
+
+    let mut reader = Cursor::new(bytes);
+    while MontyFormat::deserialise_fast_into_buffer(&mut reader, &mut buffer).is_ok() {}
+
+    let mut reader = Cursor::new(bytes);
+    while MontyValueFormat::deserialise_fast_into_buffer(&mut reader, &mut buffer).is_ok() {}
+}
+
+#[test]
+fn readers_reject_random_bytes() {
+    let mut rng = Rand(0x8CB9_2BA7_2F3D_8DD7);
+
+    for _ in 0..2048 {
+        let len = rng.below(256);
+        let bytes = (0..len).map(|_| rng.next() as u8).collect::<Vec<_>>();
+        read_all(&bytes);
+    }
+}
+
+#[test]
+fn readers_reject_corrupted_games() {
+    let mut rng = Rand(0x94D0_49BB_1331_11EB);
+
+    for _ in 0..512 {
+        let game = random_game(&mut rng);
+        let mut bytes = serialise(&game);
+        value_game(&game).serialise_into(&mut bytes).unwrap();
+
+        for _ in 0..=rng.below(4) {
+            let idx = rng.below(bytes.len());
+            bytes[idx] ^= 1 << rng.below(8);
+        }
+
+        let truncate = rng.below(bytes.len() + 1);
+        read_all(&bytes);
+        read_all(&bytes[..truncate]);
+    }
+}
@@ -0,0 +1,48 @@
+//! Helpers shared by the integration tests, each of which uses only some.
+#![allow(dead_code)]
+
+use montyformat::chess::{Castling, Move, Position, STARTPOS};
+
+/// Positions covering castling, en passant, promotions and Chess960.
+pub const FENS: [&str; 7] = [
+    STARTPOS,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 b - - 3 10",
+    "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+];
+
+/// A xorshift generator, so that random tests are reproducible.
+pub struct Rand(pub u64);
+
+impl Rand {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn unit(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+pub fn parse(fen: &str) -> (Position, Castling) {
+    let mut castling = Castling::default();
+    let pos = Position::parse_fen(fen, &mut castling);
+    (pos, castling)
+}
+
+pub fn legal_moves(pos: &Position, castling: &Castling) -> Vec<Move> {
+    let mut moves = Vec::new();
+    pos.map_legal_moves(castling, |mov| moves.push(mov));
+    moves
+}
@@ -0,0 +1,88 @@
+use montyformat::chess::{perft, Castling, Position, STARTPOS};
+
+/// The standard perft positions, with the node counts at depths 1 to 4.
+const STANDARD: [(&str, [u64; 4]); 6] = [
+    (STARTPOS, [20, 400, 8902, 197281]),
+    (
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        [48, 2039, 97862, 4085603],
+    ),
+    (
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        [14, 191, 2812, 43238],
+    ),
+    (
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        [6, 264, 9467, 422333],
+    ),
+    (
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        [44, 1486, 62379, 2103487],
+    ),
+    (
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        [46, 2079, 89890, 3894594],
+    ),
+];
+
+/// Positions from the Chess960 perft suite, in Shredder-FEN,
+/// with the node counts at depths 1 to 4.
+const CHESS960: [(&str, [u64; 4]); 4] = [
+    (
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        [21, 528, 12189, 326672],
+    ),
+    (
+        "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+        [21, 807, 18002, 667366],
+    ),
+    (
+        "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+        [20, 479, 10471, 273318],
+    ),
+    (
+        "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+        [22, 593, 13440, 382958],
+    ),
+];
+
+fn check_suite(suite: &[(&str, [u64; 4])]) {
+    for &(fen, counts) in suite {
+        let mut castling = Castling::default();
+        let pos = Position::parse_fen(fen, &mut castling);
+
+        for (depth, &count) in counts.iter().enumerate() {
+            let depth = depth as u8 + 1;
+            assert_eq!(
+                perft::<false>(&pos, &castling, depth),
+                count,
+                "{fen} depth {depth}"
+            );
+        }
+    }
+}
+
+#[test]
+fn standard_perft() {
+    check_suite(&STANDARD);
+}
+
+#[test]
+fn chess960_perft() {
+    check_suite(&CHESS960);
+}
+
+#[test]
+fn chess960_positions_are_detected() {
+    for (fen, _) in CHESS960 {
+        let mut castling = Castling::default();
+        let _ = Position::parse_fen(fen, &mut castling);
+        assert!(castling.is_chess960(), "{fen}");
+    }
+
+    for (fen, _) in STANDARD {
+        let mut castling = Castling::default();
+        let _ = Position::parse_fen(fen, &mut castling);
+        assert!(!castling.is_chess960(), "{fen}");
+    }
+}
@@ -0,0 +1,178 @@
+mod common;
+
+use common::{legal_moves, parse, Rand, FENS};
+use montyformat::{
+    chess::{Castling, GameState, Position, STARTPOS},
+    CompressedChessBoard,
+};
+
+/// `as_fen` always writes `-` for the en passant square, so add it back.
+fn full_fen(pos: &Position) -> String {
+    let fen = pos.as_fen();
+
+    if pos.enp_sq() == 0 {
+        return fen;
+    }
+
+    let mut fields = fen.split_whitespace().map(String::from).collect::<Vec<_>>();
+    let file = char::from(b'a' + pos.enp_sq() % 8);
+    fields[3] = format!("{file}{}", pos.enp_sq() / 8 + 1);
+    fields.join(" ")
+}
+
+/// Plays random legal moves from each of `FENS`, calling `f` on every
+/// position reached along with the hashes of the positions before it.
+fn random_games(mut f: impl FnMut(&Position, &Castling, &[u64])) {
+    let mut rng = Rand(0x2545_F491_4F6C_DD1D);
+
+    for fen in FENS {
+        for _ in 0..8 {
+            let (mut pos, castling) = parse(fen);
+            let mut stack = Vec::new();
+
+            for _ in 0..200 {
+                f(&pos, &castling, &stack);
+
+                if pos.game_state(&castling, &stack) != GameState::Ongoing {
+                    break;
+                }
+
+                let moves = legal_moves(&pos, &castling);
+                let mov = moves[rng.below(moves.len())];
+
+                stack.push(pos.hash());
+                pos.make(mov, &castling);
+            }
+        }
+    }
+}
+
+#[test]
+fn fen_round_trips() {
+    for fen in FENS {
+        let (pos, _) = parse(fen);
+        let (reparsed, _) = parse(&pos.as_fen());
+
+        assert!(pos == reparsed, "{fen} became {}", reparsed.as_fen());
+        assert_eq!(reparsed.as_fen(), pos.as_fen());
+    }
+}
+
+#[test]
+fn fen_round_trips_during_games() {
+    random_games(|pos, _, _| {
+        let fen = full_fen(pos);
+        let (reparsed, _) = parse(&fen);
+
+        assert!(*pos == reparsed, "{fen} became {}", full_fen(&reparsed));
+    });
+}
+
+#[test]
+fn incremental_hash_matches_recomputed() {
+    random_games(|pos, _, _| {
+        let fen = full_fen(pos);
+        let (recomputed, _) = parse(&fen);
+
+        assert_eq!(pos.hash(), recomputed.hash(), "{fen}");
+    });
+}
+
+#[test]
+fn distinct_positions_have_distinct_hashes() {
+    let (pos, castling) = parse(STARTPOS);
+    let mut hashes = legal_moves(&pos, &castling)
+        .into_iter()
+        .map(|mov| {
+            let mut child = pos;
+            child.make(mov, &castling);
+            child.hash()
+        })
+        .collect::<Vec<_>>();
+
+    hashes.push(pos.hash());
+    hashes.sort_unstable();
+    hashes.dedup();
+
+    assert_eq!(hashes.len(), 21);
+}
+
+#[test]
+fn detects_checkmate() {
+    // fool's mate
+    let (pos, castling) = parse("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+    assert!(pos.in_check());
+    assert_eq!(pos.game_state(&castling, &[]), GameState::Lost(0));
+
+    // mate takes priority over the fifty move rule
+    let (pos, castling) = parse("7k/6Q1/6K1/8/8/8/8/8 b - - 100 80");
+    assert_eq!(pos.game_state(&castling, &[]), GameState::Lost(0));
+}
+
+#[test]
+fn detects_draws() {
+    let draws = [
+        // stalemate
+        "7k/5Q2/6K1/8/8/8/8/8 b - - 0 1",
+        // insufficient material
+        "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+        "8/8/4k3/8/8/3KN3/8/8 w - - 0 1",
+        "8/8/4k3/8/8/3KB3/8/8 w - - 0 1",
+        // bishops all on the same colour
+        "8/8/3bk3/8/8/3KB3/8/8 w - - 0 1",
+        // fifty move rule
+        "8/8/4k3/8/8/3K4/8/7R w - - 100 120",
+    ];
+
+    for fen in draws {
+        let (pos, castling) = parse(fen);
+        assert_eq!(pos.game_state(&castling, &[]), GameState::Draw, "{fen}");
+    }
+
+    let ongoing = [
+        STARTPOS,
+        // bishops on opposite colours can still mate
+        "8/8/2b1k3/8/8/3KB3/8/8 w - - 0 1",
+        "8/8/4k3/8/8/3K4/8/7R w - - 99 120",
+    ];
+
+    for fen in ongoing {
+        let (pos, castling) = parse(fen);
+        assert_eq!(pos.game_state(&castling, &[]), GameState::Ongoing, "{fen}");
+    }
+}
+
+#[test]
+fn detects_repetition() {
+    let (mut pos, castling) = parse(STARTPOS);
+    let mut stack = Vec::new();
+
+    // knights out and back again
+    for uci in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+        assert_eq!(pos.game_state(&castling, &stack), GameState::Ongoing);
+
+        let mov = legal_moves(&pos, &castling)
+            .into_iter()
+            .find(|mov| mov.to_uci(&castling) == uci)
+            .unwrap();
+
+        stack.push(pos.hash());
+        pos.make(mov, &castling);
+    }
+
+    assert_eq!(pos.game_state(&castling, &stack), GameState::Draw);
+}
+
+#[test]
+fn compressed_board_round_trips() {
+    random_games(|pos, _, _| {
+        let restored = Position::from(CompressedChessBoard::from(*pos));
+
+        assert_eq!(restored.bbs(), pos.bbs());
+        assert_eq!(restored.stm(), pos.stm());
+        assert_eq!(restored.enp_sq(), pos.enp_sq());
+        assert_eq!(restored.rights(), pos.rights());
+        assert_eq!(restored.halfm(), pos.halfm());
+        assert_eq!(restored.fullm(), pos.fullm());
+    });
+}
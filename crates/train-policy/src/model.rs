@@ -78,7 +78,7 @@ pub fn eval(graph: &mut Graph<CudaDevice>, node: GraphNodeId, fen: &str) {
 }
 
 pub fn save_quantised(graph: &Graph<CudaDevice>, path: &str) -> std::io::Result<()> {
-    use std::io::Write;
+    use monty::networks::{file::write_network, PolicyNetwork};
 
     let mut file = std::fs::File::create(path).unwrap();
 
@@ -94,5 +94,5 @@ pub fn save_quantised(graph: &Graph<CudaDevice>, path: &str) -> std::io::Result<
         }
     }
 
-    file.write_all(&quant)
+    write_network::<PolicyNetwork>(&mut file, &quant)
 }
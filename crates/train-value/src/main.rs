@@ -18,8 +18,11 @@ use bullet_lib::{
     value::ValueTrainerBuilder,
 };
 
+use monty::networks::{file::write_network, ValueNetwork};
 use montyformat::chess::{Move, Position};
 
+use std::{fs, io, path::Path};
+
 fn main() {
     let experiment_name = "3072T".to_string();
 
@@ -128,6 +131,8 @@ fn main() {
 
     trainer.run(&schedule, &settings, &data_loader);
 
+    write_networks(Path::new(settings.output_directory)).unwrap();
+
     for fen in [
         "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
         "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
@@ -140,3 +145,35 @@ fn main() {
         println!("EVAL: {}", 400.0 * eval);
     }
 }
+
+/// Write the `quantised.bin` of each checkpoint in `dir` as a `monty.network`
+/// beside it, with the header monty checks when loading a network.
+fn write_networks(dir: &Path) -> io::Result<()> {
+    let size = size_of::<ValueNetwork>();
+
+    for entry in fs::read_dir(dir)? {
+        let checkpoint = entry?.path();
+
+        let Ok(mut weights) = fs::read(checkpoint.join("quantised.bin")) else {
+            continue;
+        };
+
+        // bullet pads saved networks to a multiple of 64 bytes
+        if weights.len() < size || weights.len() - size >= 64 {
+            println!(
+                "Skipping {}: expected {size} bytes of weights, found {}",
+                checkpoint.display(),
+                weights.len()
+            );
+            continue;
+        }
+
+        weights.truncate(size);
+
+        let mut file = fs::File::create(checkpoint.join("monty.network"))?;
+        write_network::<ValueNetwork>(&mut file, &weights)?;
+        println!("Wrote {}", checkpoint.join("monty.network").display());
+    }
+
+    Ok(())
+}
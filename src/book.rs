@@ -0,0 +1,186 @@
+mod random;
+
+use std::{
+    fs::File,
+    io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use memmap2::Mmap;
+use montyformat::chess::{Attacks, Flag, Piece, Position, Right, Side};
+use random::RANDOM64;
+
+use crate::chess::{ChessState, Move};
+
+const ENTRY_SIZE: usize = 16;
+
+/// An opening book in the Polyglot `.bin` format: 16 byte big-endian
+/// entries of `(key, move, weight, learn)`, sorted by key.
+pub struct PolyglotBook {
+    mmap: Mmap,
+}
+
+impl PolyglotBook {
+    pub fn open(path: &str) -> io::Result<Self> {
+        let file = File::open(path)?;
+
+        // SAFETY: book files are not expected to be modified whilst in use
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        if !mmap.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "book size is not a multiple of the entry size",
+            ));
+        }
+
+        Ok(Self { mmap })
+    }
+
+    /// The Polyglot key of a position. Unlike `Position::hash`, the en
+    /// passant file is only included if a pawn can capture en passant.
+    pub fn key(pos: &Position) -> u64 {
+        let mut key = 0;
+
+        for side in [Side::WHITE, Side::BLACK] {
+            for piece in Piece::PAWN..=Piece::KING {
+                // black pawn, white pawn, black knight, ...
+                let kind = 2 * (piece - Piece::PAWN) + (side ^ 1);
+
+                let mut bb = pos.piece(side) & pos.piece(piece);
+                while bb > 0 {
+                    key ^= RANDOM64[64 * kind + bb.trailing_zeros() as usize];
+                    bb &= bb - 1;
+                }
+            }
+        }
+
+        for (i, right) in [Right::WKS, Right::WQS, Right::BKS, Right::BQS]
+            .into_iter()
+            .enumerate()
+        {
+            if pos.rights() & right > 0 {
+                key ^= RANDOM64[768 + i];
+            }
+        }
+
+        let enp_sq = usize::from(pos.enp_sq());
+        let pawns = pos.piece(Piece::PAWN) & pos.piece(pos.stm());
+
+        if enp_sq > 0 && Attacks::pawn(enp_sq, pos.stm() ^ 1) & pawns > 0 {
+            key ^= RANDOM64[772 + (enp_sq & 7)];
+        }
+
+        if pos.stm() == Side::WHITE {
+            key ^= RANDOM64[780];
+        }
+
+        key
+    }
+
+    pub fn num_entries(&self) -> usize {
+        self.mmap.len() / ENTRY_SIZE
+    }
+
+    fn entry(&self, idx: usize) -> (u64, u16, u16) {
+        let bytes = &self.mmap[idx * ENTRY_SIZE..(idx + 1) * ENTRY_SIZE];
+
+        let key = u64::from_be_bytes(bytes[..8].try_into().unwrap());
+        let mov = u16::from_be_bytes([bytes[8], bytes[9]]);
+        let weight = u16::from_be_bytes([bytes[10], bytes[11]]);
+
+        (key, mov, weight)
+    }
+
+    /// All legal book moves in the position, with their weights.
+    pub fn moves(&self, pos: &ChessState) -> Vec<(Move, u16)> {
+        let key = Self::key(&pos.board());
+
+        // binary search for the first entry with this key
+        let (mut lo, mut hi) = (0, self.num_entries());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+
+            if self.entry(mid).0 < key {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        let mut moves = Vec::new();
+
+        for idx in lo..self.num_entries() {
+            let (entry_key, book_mov, weight) = self.entry(idx);
+
+            if entry_key != key {
+                break;
+            }
+
+            pos.map_legal_moves(|mov| {
+                if to_polyglot(pos, mov) == book_mov {
+                    moves.push((mov, weight));
+                }
+            });
+        }
+
+        moves
+    }
+
+    /// Pick a book move at random, in proportion to the weights.
+    pub fn probe(&self, pos: &ChessState) -> Option<Move> {
+        let moves = self.moves(pos);
+        let total = moves
+            .iter()
+            .map(|&(_, weight)| u64::from(weight))
+            .sum::<u64>();
+
+        if total == 0 {
+            return moves.first().map(|&(mov, _)| mov);
+        }
+
+        let mut pick = random() % total;
+
+        for (mov, weight) in moves {
+            if pick < u64::from(weight) {
+                return Some(mov);
+            }
+
+            pick -= u64::from(weight);
+        }
+
+        None
+    }
+}
+
+/// Polyglot encodes castling as the king capturing its own rook,
+/// and promotions from 1 (knight) to 4 (queen).
+fn to_polyglot(pos: &ChessState, mov: Move) -> u16 {
+    let to = if [Flag::KS, Flag::QS].contains(&mov.flag()) {
+        let side = pos.stm();
+        let ks = usize::from(mov.flag() == Flag::KS);
+        56 * side as u16 + pos.castling().rook_file(side, ks)
+    } else {
+        mov.to()
+    };
+
+    let promo = if mov.is_promo() {
+        mov.promo_pc() as u16 - 2
+    } else {
+        0
+    };
+
+    (promo << 12) | (mov.src() << 6) | to
+}
+
+fn random() -> u64 {
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64);
+
+    // splitmix64
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
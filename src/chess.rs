@@ -3,6 +3,10 @@ use crate::{
     networks::{Accumulator, PolicyNetwork, ValueNetwork, POLICY_L1},
 };
 
+use montyformat::chess::{
+    perft::{perft_divide, PerftTable},
+    Right,
+};
 pub use montyformat::chess::{Attacks, Castling, GameState, Move, Position};
 
 #[derive(Clone, Copy, Debug)]
@@ -151,6 +155,59 @@ impl ChessState {
         }
     }
 
+    /// The FEN of the current position, using Shredder-style castling
+    /// rights in Chess960 so that `from_fen` restores the same rook files.
+    pub fn to_fen(&self) -> String {
+        let fen = self.board.as_fen();
+        let mut fields = fen.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        let rights = self.board.rights();
+        if self.castling.is_chess960() && rights != 0 {
+            let mut castle = String::new();
+
+            for (side, right, ks) in [
+                (0, Right::WKS, 1),
+                (0, Right::WQS, 0),
+                (1, Right::BKS, 1),
+                (1, Right::BQS, 0),
+            ] {
+                if rights & right > 0 {
+                    let file = self.castling.rook_file(side, ks) as u8;
+                    castle.push(char::from([b'A', b'a'][side] + file));
+                }
+            }
+
+            fields[2] = castle;
+        }
+
+        let enp_sq = self.board.enp_sq();
+        if enp_sq > 0 {
+            fields[3] = format!(
+                "{}{}",
+                char::from(b'a' + (enp_sq & 7)),
+                char::from(b'1' + (enp_sq >> 3))
+            );
+        }
+
+        fields.join(" ")
+    }
+
+    /// Hashes of the positions since the last irreversible move,
+    /// used for repetition detection.
+    pub fn stack(&self) -> &[u64] {
+        &self.stack
+    }
+
+    pub fn set_stack(&mut self, stack: Vec<u64>) {
+        self.stack = stack;
+    }
+
+    /// Write castling moves as the king capturing its own rook, as
+    /// required when `UCI_Chess960` is set, rather than as a king move.
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.castling.set_chess960(chess960);
+    }
+
     pub fn map_legal_moves<F: FnMut(Move)>(&self, f: F) {
         self.board.map_legal_moves(&self.castling, f);
     }
@@ -163,6 +220,12 @@ impl ChessState {
         self.board.hash()
     }
 
+    /// Whether the position has already occurred since the last
+    /// irreversible move, so that its result depends on the path to it.
+    pub fn is_repetition(&self) -> bool {
+        self.stack.contains(&self.hash())
+    }
+
     pub fn make_move(&mut self, mov: Move) {
         self.stack.push(self.board.hash());
         self.board.make(mov, &self.castling);
@@ -176,11 +239,19 @@ impl ChessState {
         self.board.stm()
     }
 
-    pub fn map_moves_with_policies<F: FnMut(Move, f32)>(&self, policy: &PolicyNetwork, mut f: F) {
+    pub fn map_moves_with_policies<F: FnMut(Move, f32)>(&self, policy: &PolicyNetwork, f: F) {
         let hl = policy.hl(&self.board);
+        self.map_moves_with_policy_hl(policy, &hl, f);
+    }
 
+    pub fn map_moves_with_policy_hl<F: FnMut(Move, f32)>(
+        &self,
+        policy: &PolicyNetwork,
+        hl: &Accumulator<i16, { POLICY_L1 / 2 }>,
+        mut f: F,
+    ) {
         self.map_legal_moves(|mov| {
-            let policy = policy.get(&self.board, &mov, &hl);
+            let policy = policy.get(&self.board, &mov, hl);
             f(mov, policy);
         });
     }
@@ -203,7 +274,13 @@ impl ChessState {
         value: &ValueNetwork,
         params: &MctsParams,
     ) -> (EvalWdl, EvalWdl, i32) {
-        let (win, draw, loss) = value.eval(&self.board);
+        Self::material_wdl(value.eval(&self.board), params)
+    }
+
+    fn material_wdl(
+        (win, draw, loss): (f32, f32, f32),
+        params: &MctsParams,
+    ) -> (EvalWdl, EvalWdl, i32) {
         let raw = EvalWdl::new(win, draw, loss);
 
         #[cfg(not(feature = "datagen"))]
@@ -238,7 +315,18 @@ impl ChessState {
         params: &MctsParams,
         root_stm: usize,
     ) -> EvalBreakdown {
-        let (raw, material, cp) = self.evaluate_material_wdl(value, params);
+        self.wdl_with_contempt(value.eval(&self.board), params, root_stm)
+    }
+
+    /// `eval_with_contempt` with the network output already computed,
+    /// e.g. as part of a batch.
+    pub fn wdl_with_contempt(
+        &self,
+        wdl: (f32, f32, f32),
+        params: &MctsParams,
+        root_stm: usize,
+    ) -> EvalBreakdown {
+        let (raw, material, cp) = Self::material_wdl(wdl, params);
         let contempt = params.contempt() as f32;
         let perspective = if self.stm() == root_stm { 1.0 } else { -1.0 };
         let contempt_scaled = material.apply_contempt(contempt * perspective);
@@ -266,6 +354,16 @@ impl ChessState {
         perft::<true, true>(&self.board, depth as u8, &self.castling)
     }
 
+    /// Perft divide, with the root moves split between `threads` threads.
+    pub fn perft_divide(
+        &self,
+        depth: usize,
+        threads: usize,
+        table: Option<&PerftTable>,
+    ) -> Vec<(Move, u64)> {
+        perft_divide(&self.board, &self.castling, depth as u8, threads, table)
+    }
+
     pub fn display(&self, policy: &PolicyNetwork) {
         let mut moves = Vec::new();
         let mut max = f32::NEG_INFINITY;
@@ -354,7 +452,8 @@ fn perft<const ROOT: bool, const BULK: bool>(
             let mut tmp = *pos;
             tmp.make(mov, castling);
 
-            let num = if !BULK && leaf {
+            // bulk counting only applies below the root
+            let num = if leaf {
                 1
             } else {
                 perft::<false, BULK>(&tmp, depth - 1, castling)
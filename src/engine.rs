@@ -0,0 +1,220 @@
+use std::sync::{atomic::AtomicBool, Arc, Mutex};
+
+use crate::{
+    chess::{ChessState, Move},
+    mcts::{EvalBatch, Limits, MctsParams, SearchInfo, Searcher, Selection},
+    networks::{PolicyNetwork, ValueNetwork},
+    syzygy::Tablebases,
+    tree::Tree,
+};
+
+/// The outcome of a search.
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub best_move: Move,
+    pub ponder_move: Option<Move>,
+    /// The final report of the search, with the PV lines,
+    /// WDL and statistics for every root move.
+    pub info: SearchInfo,
+}
+
+/// Monty for use as a library: owns a search tree and the search
+/// parameters, and runs searches without writing to stdout.
+///
+/// The networks are borrowed, as they are usually memory mapped.
+pub struct Engine<'a> {
+    tree: Tree,
+    params: MctsParams,
+    policy: &'a PolicyNetwork,
+    value: &'a ValueNetwork,
+    hash_mb: usize,
+    threads: usize,
+    tablebases: Option<Tablebases>,
+    selection: Selection,
+    eval_batch: usize,
+}
+
+impl<'a> Engine<'a> {
+    pub fn new(policy: &'a PolicyNetwork, value: &'a ValueNetwork) -> Self {
+        let hash_mb = 64;
+        let threads = 1;
+
+        Self {
+            tree: Tree::new_mb(hash_mb, threads),
+            params: MctsParams::default(),
+            policy,
+            value,
+            hash_mb,
+            threads,
+            tablebases: None,
+            selection: Selection::default(),
+            eval_batch: 1,
+        }
+    }
+
+    pub fn set_hash(&mut self, mb: usize) {
+        self.hash_mb = mb.max(1);
+        let root = self.tree.root_position().clone();
+        self.tree.rebuild(self.hash_mb, self.threads, root);
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+        let root = self.tree.root_position().clone();
+        self.tree.rebuild(self.hash_mb, self.threads, root);
+    }
+
+    pub fn set_tablebases(&mut self, tablebases: Option<Tablebases>) {
+        self.tablebases = tablebases;
+    }
+
+    pub fn set_selection(&mut self, selection: Selection) {
+        self.selection = selection;
+    }
+
+    /// Evaluate leaves in batches of up to `size` between the search
+    /// threads, see `EvalBatch`. A size of 1 disables batching.
+    pub fn set_eval_batch(&mut self, size: usize) {
+        self.eval_batch = size.max(1);
+    }
+
+    pub fn params(&self) -> &MctsParams {
+        &self.params
+    }
+
+    pub fn params_mut(&mut self) -> &mut MctsParams {
+        &mut self.params
+    }
+
+    pub fn tree(&self) -> &Tree {
+        &self.tree
+    }
+
+    /// Forget everything searched so far.
+    pub fn new_game(&mut self) {
+        self.tree.clear(self.threads);
+    }
+
+    /// Prepare a search of `pos`. The tree from previous searches
+    /// is reused if `pos` is found within it.
+    pub fn search(&mut self, pos: &ChessState, limits: Limits) -> SearchSession<'_, 'a> {
+        SearchSession {
+            engine: self,
+            pos: pos.clone(),
+            limits,
+            multipv: 1,
+            search_moves: Vec::new(),
+            abort: Arc::new(AtomicBool::new(false)),
+            progress: None,
+        }
+    }
+}
+
+type Progress<'e> = Box<dyn FnMut(&SearchInfo) + Send + 'e>;
+
+/// A search waiting to be run, see `Engine::search`.
+pub struct SearchSession<'e, 'a> {
+    engine: &'e mut Engine<'a>,
+    pos: ChessState,
+    limits: Limits,
+    multipv: usize,
+    search_moves: Vec<Move>,
+    abort: Arc<AtomicBool>,
+    progress: Option<Progress<'e>>,
+}
+
+impl<'e> SearchSession<'e, '_> {
+    pub fn multipv(mut self, multipv: usize) -> Self {
+        self.multipv = multipv.max(1);
+        self
+    }
+
+    /// Only search the given root moves, as with `go searchmoves`.
+    pub fn search_moves(mut self, moves: &[Move]) -> Self {
+        self.search_moves = moves.to_vec();
+        self
+    }
+
+    /// Call `progress` with each report made during the search.
+    pub fn on_progress(mut self, progress: impl FnMut(&SearchInfo) + Send + 'e) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// A flag which stops the search once set, e.g. from another thread.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        self.abort.clone()
+    }
+
+    /// Run the search until a limit is reached or it is stopped.
+    pub fn run(self) -> SearchResult {
+        let engine = self.engine;
+        let mut search_moves = self.search_moves;
+
+        // only search the moves keeping the best result in the tablebases
+        if let Some(moves) = engine
+            .tablebases
+            .as_ref()
+            .and_then(|tb| tb.root_moves(&self.pos, &search_moves))
+        {
+            search_moves = moves;
+        }
+
+        engine.tree.set_root_position(&self.pos);
+        engine.tree.set_search_moves(&search_moves);
+
+        let callback = Mutex::new(self.progress);
+        let last_info = Mutex::new(None);
+
+        let progress = |info: &SearchInfo| {
+            if let Some(callback) = callback.lock().unwrap().as_mut() {
+                callback(info);
+            }
+
+            *last_info.lock().unwrap() = Some(info.clone());
+        };
+
+        let mut searcher = Searcher::new(
+            &engine.tree,
+            &engine.params,
+            engine.policy,
+            engine.value,
+            &self.abort,
+        )
+        .with_progress(&progress)
+        .with_selection(engine.selection.policy());
+
+        if let Some(tablebases) = &engine.tablebases {
+            searcher = searcher.with_tablebases(tablebases);
+        }
+
+        let batch = (engine.eval_batch > 1).then(|| EvalBatch::new(engine.eval_batch));
+        if let Some(batch) = &batch {
+            searcher = searcher.with_batching(batch);
+        }
+
+        let best_move = searcher
+            .search(
+                engine.threads,
+                self.limits,
+                false,
+                self.multipv,
+                false,
+                &mut 0,
+                #[cfg(feature = "datagen")]
+                false,
+                #[cfg(feature = "datagen")]
+                1.0,
+            )
+            .0;
+
+        let ponder_move = searcher.ponder_move();
+
+        SearchResult {
+            best_move,
+            ponder_move,
+            // a final report is always made when the search finishes
+            info: last_info.into_inner().unwrap().unwrap(),
+        }
+    }
+}
@@ -1,16 +1,13 @@
+pub mod book;
 pub mod chess;
+pub mod engine;
 pub mod mcts;
+pub mod memory;
 pub mod networks;
+pub mod syzygy;
 pub mod tree;
 pub mod uci;
 
-use memmap2::Mmap;
-
-pub struct MappedWeights<'a, T> {
-    pub mmap: Mmap,  // The memory-mapped file
-    pub data: &'a T, // A reference to the data in the mmap
-}
-
 #[macro_export]
 macro_rules! init {
     (|$sq:ident, $size:literal | $($rest:tt)+) => {{
@@ -62,29 +59,3 @@ pub unsafe fn boxed_and_zeroed<T>() -> Box<T> {
         Box::from_raw(ptr.cast())
     }
 }
-
-/// # Safety
-/// Only to be used internally.
-pub unsafe fn read_into_struct_unchecked<'a, T>(path: &str) -> MappedWeights<'a, T> {
-    let f = std::fs::File::open(path).unwrap();
-    let mmap = Mmap::map(&f).unwrap();
-
-    let size = std::mem::size_of::<T>();
-    let file_size = mmap.len();
-    assert_eq!(
-        file_size, size,
-        "File size does not match the size of the structure"
-    );
-
-    let ptr = mmap.as_ptr() as *const T;
-
-    // Check if the pointer is properly aligned
-    if !(ptr as usize).is_multiple_of(std::mem::align_of::<T>()) {
-        panic!("Memory is not properly aligned for the type");
-    }
-
-    MappedWeights {
-        mmap, // This ensures the memory is valid as long as MappedWeights exists
-        data: &*ptr,
-    }
-}
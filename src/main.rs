@@ -12,11 +12,10 @@ mod net {
     use monty::{
         chess::ChessState,
         mcts::MctsParams,
-        networks::{PolicyNetwork, ValueNetwork},
+        networks::{file::sha_prefix, NetworkFile, PolicyNetwork, ValueNetwork},
         uci,
     };
     use once_cell::sync::Lazy;
-    use sha2::{Digest, Sha256};
     use std::fs::{self, File};
     use std::io::{self, Cursor, Write};
     use std::path::{Path, PathBuf};
@@ -27,15 +26,6 @@ mod net {
     static COMPRESSED_VALUE: &[u8] = include_bytes!("../value.network.zst");
     static COMPRESSED_POLICY: &[u8] = include_bytes!("../policy.network.zst");
 
-    /// Compute the first 12 hexadecimal characters of the SHA-256 hash of the data.
-    fn compute_short_sha(data: &[u8]) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(data);
-        let result = hasher.finalize();
-        // Convert the hash to a hexadecimal string and take the first 12 characters
-        format!("{result:x}")[..12].to_string()
-    }
-
     /// Get the full path in the OS's temporary directory for the given data.
     /// The filename format is "nn-<hash_prefix>.network"
     fn get_network_path(data: &[u8]) -> PathBuf {
@@ -43,7 +33,7 @@ mod net {
         temp_dir.push("Monty");
         fs::create_dir_all(&temp_dir)
             .expect("Failed to create 'Monty' directory in the temp folder");
-        let hash_prefix = compute_short_sha(data);
+        let hash_prefix = sha_prefix(data);
         temp_dir.join(format!("nn-{hash_prefix}.network"))
     }
 
@@ -130,7 +120,7 @@ mod net {
         file_path: &Path,
     ) -> std::io::Result<()> {
         // Compute expected hash prefix
-        let expected_hash_prefix = compute_short_sha(compressed_data);
+        let expected_hash_prefix = sha_prefix(compressed_data);
 
         // Check if a file with the expected hash prefix already exists
         if file_path.exists() {
@@ -168,22 +158,13 @@ mod net {
         Ok(())
     }
 
-    /// Unsafe helper function to interpret the memory-mapped data as the target structure.
-    /// Ensure that the data layout matches exactly.
-    unsafe fn read_into_struct_unchecked<T>(mmap: &Mmap) -> &T {
-        assert_eq!(
-            mmap.len(),
-            std::mem::size_of::<T>(),
-            "Mapped file size does not match the target structure size."
-        );
-        &*(mmap.as_ptr() as *const T)
-    }
-
     // Initialize and memory-map both policy and value networks together
-    static NETWORKS: Lazy<(Mmap, Mmap)> = Lazy::new(|| {
+    type Networks = (NetworkFile<PolicyNetwork>, NetworkFile<ValueNetwork>);
+
+    static NETWORKS: Lazy<Networks> = Lazy::new(|| {
         // Compute hash prefixes based on compressed data
-        let policy_hash_prefix = compute_short_sha(COMPRESSED_POLICY);
-        let value_hash_prefix = compute_short_sha(COMPRESSED_VALUE);
+        let policy_hash_prefix = sha_prefix(COMPRESSED_POLICY);
+        let value_hash_prefix = sha_prefix(COMPRESSED_VALUE);
 
         // Current hash prefixes
         let current_hash_prefixes = [policy_hash_prefix.as_str(), value_hash_prefix.as_str()];
@@ -214,7 +195,10 @@ mod net {
         let value_mmap =
             unsafe { Mmap::map(&value_file).expect("Failed to memory-map value network file") };
 
-        (policy_mmap, value_mmap)
+        let policy = NetworkFile::from_mmap(policy_mmap).expect("Invalid policy network file");
+        let value = NetworkFile::from_mmap(value_mmap).expect("Invalid value network file");
+
+        (policy, value)
     });
 
     pub fn run() {
@@ -224,8 +208,8 @@ mod net {
         let arg2 = args.next();
 
         // Interpret the memory-mapped data as network structures
-        let policy: &PolicyNetwork = unsafe { read_into_struct_unchecked(&NETWORKS.0) };
-        let value: &ValueNetwork = unsafe { read_into_struct_unchecked(&NETWORKS.1) };
+        let policy = NETWORKS.0.get();
+        let value = NETWORKS.1.get();
 
         if let Some("bench") = arg1.as_deref() {
             let depth = arg2
@@ -245,8 +229,10 @@ mod net {
 #[cfg(not(feature = "embed"))]
 mod nonet {
     use monty::{
-        chess::ChessState, mcts::MctsParams, networks, read_into_struct_unchecked, uci,
-        MappedWeights,
+        chess::ChessState,
+        mcts::MctsParams,
+        networks::{self, NetworkFile},
+        uci,
     };
 
     pub fn run() {
@@ -255,14 +241,14 @@ mod nonet {
         let arg1 = args.next();
         let arg2 = args.next();
 
-        let policy_mapped: MappedWeights<networks::PolicyNetwork> =
-            unsafe { read_into_struct_unchecked(networks::PolicyFileDefaultName) };
+        let policy_file: NetworkFile<networks::PolicyNetwork> =
+            NetworkFile::open_or_exit(networks::PolicyFileDefaultName);
 
-        let value_mapped: MappedWeights<networks::ValueNetwork> =
-            unsafe { read_into_struct_unchecked(networks::ValueFileDefaultName) };
+        let value_file: NetworkFile<networks::ValueNetwork> =
+            NetworkFile::open_or_exit(networks::ValueFileDefaultName);
 
-        let policy = policy_mapped.data;
-        let value = value_mapped.data;
+        let policy = policy_file.get();
+        let value = value_file.get();
 
         if let Some("bench") = arg1.as_deref() {
             let depth = arg2
@@ -1,22 +1,32 @@
+mod batch;
+mod gumbel;
 mod helpers;
 mod iteration;
 mod params;
+mod pool;
+mod report;
 mod search_stats;
+mod selection;
 
+pub use batch::EvalBatch;
+pub use gumbel::GumbelRoot;
 pub use helpers::SearchHelpers;
 pub use params::MctsParams;
+pub use pool::{Scope, ThreadPool};
+pub use report::{ChildInfo, PvInfo, Score, SearchInfo};
 pub use search_stats::SearchStats;
+pub use selection::{Gumbel, Puct, Rpo, Selection, SelectionPolicy, Ucb1Tuned};
 
 use crate::{
     chess::{GameState, Move},
-    networks::{PolicyNetwork, ValueNetwork},
-    tree::{Node, NodePtr, Tree},
+    networks::{PolicyNetwork, ValueAccumulators, ValueNetwork},
+    syzygy::{Tablebases, TB_DISTANCE},
+    tree::{Node, NodePtr, Outcome, Tree},
 };
 
 use std::{
     sync::atomic::{AtomicBool, Ordering},
-    thread,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 #[cfg(feature = "datagen")]
@@ -27,6 +37,12 @@ pub type SearchRet = (Move, f32);
 
 pub static REPORT_ITERS: AtomicBool = AtomicBool::new(false);
 
+/// Print search reports as JSON objects rather than `info` lines.
+pub static INFO_JSON: AtomicBool = AtomicBool::new(false);
+
+/// Follow each `info` report with the statistics of every root child.
+pub static VERBOSE_MOVE_STATS: AtomicBool = AtomicBool::new(false);
+
 fn calibrate_wdl(win: f32, draw: f32, loss: f32) -> [f32; 3] {
     const W: [[f64; 3]; 3] = [
         [3.75992276, 0.23714723, -1.85080033],
@@ -68,16 +84,38 @@ pub struct Limits {
     pub opt_time: Option<u128>,
     pub max_depth: usize,
     pub max_nodes: usize,
+    pub mate: Option<usize>,
     #[cfg(feature = "datagen")]
     pub kld_min_gain: Option<f64>,
 }
 
+impl Default for Limits {
+    /// No limits, the search running until it is stopped.
+    fn default() -> Self {
+        Self {
+            max_time: None,
+            opt_time: None,
+            max_depth: 256,
+            max_nodes: usize::MAX,
+            mate: None,
+            #[cfg(feature = "datagen")]
+            kld_min_gain: None,
+        }
+    }
+}
+
 pub struct Searcher<'a> {
     tree: &'a Tree,
     params: &'a MctsParams,
     policy: &'a PolicyNetwork,
     value: &'a ValueNetwork,
     abort: &'a AtomicBool,
+    ponder: Option<&'a AtomicBool>,
+    tablebases: Option<&'a Tablebases>,
+    progress: Option<&'a (dyn Fn(&SearchInfo) + Sync)>,
+    selection: &'a dyn SelectionPolicy,
+    batch: Option<&'a EvalBatch>,
+    pool: Option<&'a ThreadPool>,
 }
 
 impl<'a> Searcher<'a> {
@@ -94,14 +132,66 @@ impl<'a> Searcher<'a> {
             policy,
             value,
             abort,
+            ponder: None,
+            tablebases: None,
+            progress: None,
+            selection: &Puct,
+            batch: None,
+            pool: None,
         }
     }
 
+    /// Search in ponder mode: whilst `ponder` is set the search ignores
+    /// its limits, and once it is cleared (on `ponderhit`) the search
+    /// continues as a normal limited search on the tree built so far,
+    /// with the clock starting at that point.
+    pub fn with_ponder(mut self, ponder: &'a AtomicBool) -> Self {
+        self.ponder = Some(ponder);
+        self
+    }
+
+    /// Probe `tablebases` at leaf nodes after zeroing moves.
+    pub fn with_tablebases(mut self, tablebases: &'a Tablebases) -> Self {
+        self.tablebases = Some(tablebases);
+        self
+    }
+
+    /// Call `progress` with every report made during the search, and
+    /// once more when it finishes, whether or not UCI output is enabled.
+    pub fn with_progress(mut self, progress: &'a (dyn Fn(&SearchInfo) + Sync)) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Choose children during selection with `selection` rather than PUCT.
+    pub fn with_selection(mut self, selection: &'a dyn SelectionPolicy) -> Self {
+        self.selection = selection;
+        self
+    }
+
+    /// Evaluate leaves in batches shared between the search threads.
+    pub fn with_batching(mut self, batch: &'a EvalBatch) -> Self {
+        self.batch = Some(batch);
+        self
+    }
+
+    /// Run the search threads on `pool`, rather than on
+    /// threads spawned for the duration of the search.
+    pub fn with_pool(mut self, pool: &'a ThreadPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    fn is_pondering(&self) -> bool {
+        self.ponder.is_some_and(|p| p.load(Ordering::Relaxed))
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn playout_until_full_main(
         &self,
         limits: &Limits,
-        timer: &Instant,
+        #[cfg(not(feature = "uci-minimal"))] timer: &Instant,
+        limits_timer: &mut Option<Instant>,
         #[cfg(not(feature = "uci-minimal"))] timer_last_output: &mut Instant,
         search_stats: &SearchStats,
         best_move: &mut Move,
@@ -116,7 +206,9 @@ impl<'a> Searcher<'a> {
         if self.playout_until_full_internal(search_stats, true, thread_id, || {
             self.check_limits(
                 limits,
+                #[cfg(not(feature = "uci-minimal"))]
                 timer,
+                limits_timer,
                 #[cfg(not(feature = "uci-minimal"))]
                 timer_last_output,
                 search_stats,
@@ -151,6 +243,12 @@ impl<'a> Searcher<'a> {
     where
         F: FnMut() -> bool,
     {
+        let _active = self.batch.map(EvalBatch::join);
+
+        // each thread keeps its own value network accumulators for the line
+        // it is searching, which are updated as it descends the tree
+        let mut accs = ValueAccumulators::default();
+
         loop {
             let mut pos = self.tree.root_position().clone();
             let mut this_depth = 0;
@@ -161,6 +259,7 @@ impl<'a> Searcher<'a> {
                 self.tree.root_node(),
                 &mut this_depth,
                 thread_id,
+                &mut accs,
             )
             .is_none()
             {
@@ -189,7 +288,8 @@ impl<'a> Searcher<'a> {
     fn check_limits(
         &self,
         limits: &Limits,
-        timer: &Instant,
+        #[cfg(not(feature = "uci-minimal"))] timer: &Instant,
+        limits_timer: &mut Option<Instant>,
         #[cfg(not(feature = "uci-minimal"))] timer_last_output: &mut Instant,
         search_stats: &SearchStats,
         best_move: &mut Move,
@@ -202,10 +302,24 @@ impl<'a> Searcher<'a> {
     ) -> bool {
         let iters = search_stats.main_iters();
 
-        if search_stats.total_iters() >= limits.max_nodes {
+        // whilst pondering no limits apply, and the clock
+        // for time limits only starts once pondering ends
+        let pondering = self.is_pondering();
+        if !pondering && limits_timer.is_none() {
+            *limits_timer = Some(Instant::now());
+        }
+
+        if !pondering && search_stats.total_iters() >= limits.max_nodes {
             return true;
         }
 
+        // `go mate <n>` is satisfied once a short enough mate is proven
+        if let (Some(moves), Some(mate)) = (limits.mate, self.proven_mate()) {
+            if mate > 0 && mate as usize <= moves {
+                return true;
+            }
+        }
+
         #[cfg(feature = "datagen")]
         {
             if let Some(min_gain) = limits.kld_min_gain {
@@ -232,8 +346,8 @@ impl<'a> Searcher<'a> {
         }
 
         if iters.is_multiple_of(128) {
-            if let Some(time) = limits.max_time {
-                if timer.elapsed().as_millis() >= time {
+            if let (Some(time), Some(limits_timer)) = (limits.max_time, limits_timer.as_ref()) {
+                if limits_timer.elapsed().as_millis() >= time {
                     return true;
                 }
             }
@@ -246,10 +360,10 @@ impl<'a> Searcher<'a> {
         }
 
         if iters.is_multiple_of(4096) {
-            if let Some(time) = limits.opt_time {
+            if let (Some(time), Some(limits_timer)) = (limits.opt_time, limits_timer.as_ref()) {
                 let (should_stop, score) = SearchHelpers::soft_time_cutoff(
                     self,
-                    timer,
+                    limits_timer,
                     *previous_score,
                     *best_move_changes,
                     iters,
@@ -277,18 +391,19 @@ impl<'a> Searcher<'a> {
         let new_depth = total_depth / search_stats.total_iters();
         if new_depth > search_stats.avg_depth.load(Ordering::Relaxed) {
             search_stats.avg_depth.store(new_depth, Ordering::Relaxed);
-            if new_depth >= limits.max_depth {
+            if !pondering && new_depth >= limits.max_depth {
                 return true;
             }
 
             #[cfg(not(feature = "uci-minimal"))]
-            if uci_output {
+            if uci_output || self.progress.is_some() {
                 self.search_report(
                     new_depth,
                     search_stats.seldepth(),
                     timer,
                     search_stats.total_nodes(),
                     search_stats.total_iters(),
+                    uci_output,
                     multipv,
                     gui_compatibility,
                 );
@@ -298,13 +413,17 @@ impl<'a> Searcher<'a> {
         }
 
         #[cfg(not(feature = "uci-minimal"))]
-        if uci_output && iters.is_multiple_of(8192) && timer_last_output.elapsed().as_secs() >= 1 {
+        if (uci_output || self.progress.is_some())
+            && iters.is_multiple_of(8192)
+            && timer_last_output.elapsed().as_secs() >= 1
+        {
             self.search_report(
                 search_stats.avg_depth.load(Ordering::Relaxed),
                 search_stats.seldepth(),
                 timer,
                 search_stats.total_nodes(),
                 search_stats.total_iters(),
+                uci_output,
                 multipv,
                 gui_compatibility,
             );
@@ -328,6 +447,7 @@ impl<'a> Searcher<'a> {
         #[cfg(feature = "datagen")] temp: f32,
     ) -> SearchRet {
         let timer = Instant::now();
+        let mut limits_timer = (!self.is_pondering()).then_some(timer);
         #[cfg(not(feature = "uci-minimal"))]
         let mut timer_last_output = Instant::now();
 
@@ -335,6 +455,14 @@ impl<'a> Searcher<'a> {
         let root_stm = pos.stm();
         let node = self.tree.root_node();
 
+        // entries stored by earlier searches become the first to be replaced
+        self.tree.age_hash();
+
+        // the `searchmoves` restriction may differ from when the tree was built
+        if !self.tree.is_empty() {
+            self.tree.apply_search_moves();
+        }
+
         // the root node is added to an empty tree, **and not counted** towards the
         // total node count, in order for `go nodes 1` to give the expected result
         if self.tree.is_empty() {
@@ -390,34 +518,43 @@ impl<'a> Searcher<'a> {
         #[cfg(feature = "datagen")]
         let mut previous_kld = Vec::new();
 
-        // search loop
-        while !self.abort.load(Ordering::Relaxed) {
-            thread::scope(|s| {
-                s.spawn(|| {
-                    self.playout_until_full_main(
-                        &limits,
-                        &timer,
-                        #[cfg(not(feature = "uci-minimal"))]
-                        &mut timer_last_output,
-                        stats_ref,
-                        &mut best_move,
-                        &mut best_move_changes,
-                        &mut previous_score,
-                        #[cfg(feature = "datagen")]
-                        &mut previous_kld,
-                        #[cfg(not(feature = "uci-minimal"))]
-                        uci_output,
-                        #[cfg(not(feature = "uci-minimal"))]
-                        multipv,
-                        #[cfg(not(feature = "uci-minimal"))]
-                        gui_compatibility,
-                        0,
-                    );
-                });
+        let local_pool;
+        let pool = if let Some(pool) = self.pool {
+            pool
+        } else {
+            local_pool = ThreadPool::default();
+            &local_pool
+        };
 
+        // search loop, with the main search thread being the calling
+        // thread and the workers parked in the pool between tree flips
+        while !self.abort.load(Ordering::Relaxed) {
+            pool.scope(|s| {
                 for i in 1..threads {
                     s.spawn(move || self.playout_until_full_worker(stats_ref, i));
                 }
+
+                self.playout_until_full_main(
+                    &limits,
+                    #[cfg(not(feature = "uci-minimal"))]
+                    &timer,
+                    &mut limits_timer,
+                    #[cfg(not(feature = "uci-minimal"))]
+                    &mut timer_last_output,
+                    stats_ref,
+                    &mut best_move,
+                    &mut best_move_changes,
+                    &mut previous_score,
+                    #[cfg(feature = "datagen")]
+                    &mut previous_kld,
+                    #[cfg(not(feature = "uci-minimal"))]
+                    uci_output,
+                    #[cfg(not(feature = "uci-minimal"))]
+                    multipv,
+                    #[cfg(not(feature = "uci-minimal"))]
+                    gui_compatibility,
+                    0,
+                );
             });
 
             if !self.abort.load(Ordering::Relaxed) {
@@ -429,13 +566,14 @@ impl<'a> Searcher<'a> {
 
         *update_nodes += search_stats.total_nodes();
 
-        if uci_output {
+        if uci_output || self.progress.is_some() {
             self.search_report(
                 search_stats.avg_depth.load(Ordering::Relaxed).max(1),
                 search_stats.seldepth(),
                 &timer,
                 search_stats.total_nodes(),
                 search_stats.total_iters(),
+                uci_output,
                 multipv,
                 gui_compatibility,
             );
@@ -464,82 +602,131 @@ impl<'a> Searcher<'a> {
         timer: &Instant,
         nodes: usize,
         iters: usize,
+        uci_output: bool,
         multipv: usize,
         gui_compatibility: bool,
     ) {
-        let elapsed = timer.elapsed();
-        let pv_lines = self.multipv_lines(depth, seldepth, nodes, multipv);
+        let info = self.search_info(depth, seldepth, timer.elapsed(), nodes, iters, multipv);
 
-        let elapsed_secs = elapsed.as_secs_f32();
-        let ms = elapsed.as_millis();
-
-        for (idx, pv_line) in pv_lines.iter().enumerate() {
-            let line_depth = if multipv > 1 {
-                pv_line.depth.max(1)
-            } else {
-                depth
-            };
+        if let Some(progress) = self.progress {
+            progress(&info);
+        }
 
-            let line_seldepth = if multipv > 1 {
-                pv_line.seldepth.max(1)
-            } else {
-                seldepth
-            };
+        if uci_output {
+            let pos = self.tree.root_position();
 
-            let line_nodes = if multipv > 1 {
-                pv_line.nodes
-            } else if REPORT_ITERS.load(Ordering::Relaxed) {
-                iters
+            if INFO_JSON.load(Ordering::Relaxed) {
+                println!("{}", info.to_json(pos));
             } else {
-                nodes
-            };
+                info.print_uci(pos, multipv, gui_compatibility);
 
-            let nps = line_nodes as f32 / elapsed_secs;
-
-            print!("info depth {line_depth} seldepth {line_seldepth} ");
-            if multipv > 1 {
-                print!("multipv {} ", idx + 1);
+                if VERBOSE_MOVE_STATS.load(Ordering::Relaxed) {
+                    info.print_move_stats(pos);
+                }
             }
+        }
+    }
 
-            if pv_line.score > 1.0 {
-                print!("score mate {} ", pv_line.line.len().div_ceil(2));
-            } else if pv_line.score < 0.0 {
-                print!("score mate -{} ", pv_line.line.len() / 2);
-            } else {
-                let (mut scaled, mut cal) = if multipv > 1 {
-                    self.get_display_score_for(pv_line.node)
-                } else {
-                    self.get_display_score()
-                };
-
-                if multipv > 1 && pv_line.node != self.tree.root_node() {
-                    scaled = -scaled;
-                    cal = [cal[2], cal[1], cal[0]];
+    fn search_info(
+        &self,
+        depth: usize,
+        seldepth: usize,
+        elapsed: Duration,
+        nodes: usize,
+        iters: usize,
+        multipv: usize,
+    ) -> SearchInfo {
+        let lines = self
+            .multipv_lines(depth, seldepth, nodes, multipv)
+            .iter()
+            .map(|pv_line| {
+                let (depth, seldepth, nodes) =
+                    Self::line_stats(pv_line, depth, seldepth, nodes, iters, multipv);
+                let (scaled, wdl) = self.line_score(pv_line, multipv);
+
+                PvInfo {
+                    depth,
+                    seldepth,
+                    nodes,
+                    score: pv_line
+                        .mate
+                        .map_or(Score::Cp(scaled.round() as i32), Score::Mate),
+                    wdl,
+                    policy: pv_line.policy,
+                    moves: pv_line.line.clone(),
                 }
+            })
+            .collect();
 
-                print!("score cp {scaled:.0} ");
-
-                if !gui_compatibility {
-                    let wdl_i = cal.map(|v| (v * 1000.0).round() as i32);
-                    print!("wdl {} {} {} ", wdl_i[0], wdl_i[1], wdl_i[2]);
+        let children = self
+            .root_children_by_score(usize::MAX)
+            .into_iter()
+            .map(|(ptr, mov)| {
+                let child = &self.tree[ptr];
+
+                ChildInfo {
+                    mov,
+                    visits: child.visits(),
+                    q: child.q(),
+                    draw: child.draw(),
+                    var: if child.visits() > 0 { child.var() } else { 0.0 },
+                    policy: child.policy(),
+                    state: child.state(),
+                    mate: self.mate_score(ptr),
                 }
-            }
-
-            print!("time {ms} nodes {line_nodes} nps {nps:.0} ");
+            })
+            .collect();
 
-            if !gui_compatibility {
-                let policy = (pv_line.policy * 10000.0).round();
-                print!("policy {policy:.0} ");
-            }
+        SearchInfo {
+            depth,
+            seldepth,
+            elapsed,
+            nodes: if REPORT_ITERS.load(Ordering::Relaxed) {
+                iters
+            } else {
+                nodes
+            },
+            wdl: self.get_display_score().1,
+            hashfull: self.tree.hashfull(),
+            lines,
+            children,
+        }
+    }
 
-            print!("pv");
+    /// The depth, seldepth and node count to report for a line, which
+    /// are those of the whole search unless there are multiple lines.
+    fn line_stats(
+        pv_line: &PvLine,
+        depth: usize,
+        seldepth: usize,
+        nodes: usize,
+        iters: usize,
+        multipv: usize,
+    ) -> (usize, usize, usize) {
+        if multipv > 1 {
+            (pv_line.depth.max(1), pv_line.seldepth.max(1), pv_line.nodes)
+        } else if REPORT_ITERS.load(Ordering::Relaxed) {
+            (depth, seldepth, iters)
+        } else {
+            (depth, seldepth, nodes)
+        }
+    }
 
-            for mov in &pv_line.line {
-                print!(" {}", self.tree.root_position().conv_mov_to_str(*mov));
-            }
+    /// The displayed score and calibrated WDL of a line,
+    /// from the point of view of the side to move at the root.
+    fn line_score(&self, pv_line: &PvLine, multipv: usize) -> (f32, [f32; 3]) {
+        let (mut scaled, mut cal) = if multipv > 1 {
+            self.get_display_score_for(pv_line.node)
+        } else {
+            self.get_display_score()
+        };
 
-            println!();
+        if multipv > 1 && pv_line.node != self.tree.root_node() {
+            scaled = -scaled;
+            cal = [cal[2], cal[1], cal[0]];
         }
+
+        (scaled, cal)
     }
 
     fn get_display_score(&self) -> (f32, [f32; 3]) {
@@ -553,6 +740,11 @@ impl<'a> Searcher<'a> {
             &self.tree[node]
         };
 
+        // a proven draw is reported exactly, whatever the search has averaged
+        if node_ref.state() == GameState::Draw {
+            return (0.0, [0.0, 1.0, 0.0]);
+        }
+
         let draw = node_ref.draw().clamp(0.0, 1.0);
 
         let score = (1.0 - node_ref.q()).clamp(0.0, 1.0);
@@ -586,7 +778,7 @@ impl<'a> Searcher<'a> {
         if children.is_empty() {
             return vec![PvLine {
                 line: Vec::new(),
-                score: 0.0,
+                mate: None,
                 policy: 0.0,
                 node: self.tree.root_node(),
                 depth,
@@ -645,6 +837,8 @@ impl<'a> Searcher<'a> {
             GameState::Lost(n) => 1.0 + f32::from(n),
             GameState::Won(n) => f32::from(n) - 256.0,
             GameState::Draw => 0.5,
+            // a child proven to be at best a draw is never preferred to one
+            GameState::Ongoing if node.bounds().lower >= Outcome::Draw => node.q().min(0.5),
             GameState::Ongoing => node.q(),
         }
     }
@@ -659,10 +853,10 @@ impl<'a> Searcher<'a> {
         let mut pv = Vec::new();
         let mut ptr = start_ptr;
         let mut mov = start_move;
-        let score = if start_ptr.is_null() {
-            0.0
+        let mate_score = if start_ptr.is_null() {
+            None
         } else {
-            self.pv_score(start_ptr, self.tree[start_ptr].q())
+            self.mate_score(start_ptr)
         };
 
         let mut pv_depth = 0;
@@ -686,7 +880,7 @@ impl<'a> Searcher<'a> {
 
         PvLine {
             line: pv,
-            score,
+            mate: mate_score,
             policy,
             node: start_ptr,
             depth: pv_depth,
@@ -699,17 +893,43 @@ impl<'a> Searcher<'a> {
         }
     }
 
-    fn pv_score(&self, ptr: NodePtr, q: f32) -> f32 {
-        if ptr.is_null() {
-            return q;
+    /// Exact mate score (in moves, from the root's point of view)
+    /// after playing the root child `ptr`, if its result is proven.
+    fn mate_score(&self, ptr: NodePtr) -> Option<i32> {
+        match self.tree[ptr].state() {
+            GameState::Lost(n) if n < TB_DISTANCE => Some(i32::from(n) / 2 + 1),
+            GameState::Won(n) if n < TB_DISTANCE => Some(-(i32::from(n) + 1) / 2),
+            _ => None,
         }
+    }
 
-        match self.tree[ptr].state() {
-            GameState::Lost(_) => 1.1,
-            GameState::Won(_) => -0.1,
-            GameState::Draw => 0.5,
-            GameState::Ongoing => q,
+    /// The proven mate distance at the root in moves, negative
+    /// if the side to move is the one getting mated.
+    pub fn proven_mate(&self) -> Option<i32> {
+        match self.tree[self.tree.root_node()].state() {
+            GameState::Won(n) if n < TB_DISTANCE => Some((i32::from(n) + 1) / 2),
+            GameState::Lost(n) if n < TB_DISTANCE => Some(-(i32::from(n) / 2)),
+            _ => None,
+        }
+    }
+
+    /// The reply expected after the best move, taken from the
+    /// principal variation, if the search has got that far.
+    pub fn ponder_move(&self) -> Option<Move> {
+        let root = self.tree.root_node();
+
+        if !self.tree[root].has_children() || self.get_best_child(root) == usize::MAX {
+            return None;
         }
+
+        let (ptr, _, _) = self.get_best_action(root);
+
+        if !self.tree[ptr].has_children() || self.get_best_child(ptr) == usize::MAX {
+            return None;
+        }
+
+        let (_, mov, _) = self.get_best_action(ptr);
+        Some(mov)
     }
 
     fn get_best_action(&self, node: NodePtr) -> (NodePtr, Move, f32) {
@@ -720,18 +940,8 @@ impl<'a> Searcher<'a> {
     }
 
     fn get_best_child(&self, node: NodePtr) -> usize {
-        self.tree.get_best_child_by_key(node, |child| {
-            if child.visits() == 0 {
-                f32::NEG_INFINITY
-            } else {
-                match child.state() {
-                    GameState::Lost(n) => 1.0 + f32::from(n),
-                    GameState::Won(n) => f32::from(n) - 256.0,
-                    GameState::Draw => 0.5,
-                    GameState::Ongoing => child.q(),
-                }
-            }
-        })
+        self.tree
+            .get_best_child_by_key(node, Self::node_order_score)
     }
 
     pub fn display_moves(&self) {
@@ -754,7 +964,7 @@ impl<'a> Searcher<'a> {
 
 struct PvLine {
     line: Vec<Move>,
-    score: f32,
+    mate: Option<i32>,
     policy: f32,
     node: NodePtr,
     depth: usize,
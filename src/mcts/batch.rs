@@ -0,0 +1,156 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use montyformat::chess::Position;
+
+use crate::networks::{Accumulator, PolicyNetwork, ValueNetwork, POLICY_L1};
+
+/// How long a queued leaf waits for the batch to fill before
+/// its thread evaluates whatever has been queued so far. Threads
+/// can be held up by a lock another queued thread is holding, so
+/// a batch is not guaranteed to ever fill.
+const MAX_WAIT: Duration = Duration::from_micros(100);
+
+struct Queue<O> {
+    pending: Vec<(u64, Position)>,
+    done: Vec<(u64, O)>,
+    next_ticket: u64,
+}
+
+/// Positions queued by the search threads, evaluated together by
+/// whichever thread fills the batch.
+struct Batcher<O> {
+    queue: Mutex<Queue<O>>,
+    ready: Condvar,
+}
+
+impl<O> Batcher<O> {
+    fn new() -> Self {
+        Self {
+            queue: Mutex::new(Queue {
+                pending: Vec::new(),
+                done: Vec::new(),
+                next_ticket: 0,
+            }),
+            ready: Condvar::new(),
+        }
+    }
+
+    fn eval(&self, pos: &Position, size: usize, f: impl Fn(&[Position]) -> Vec<O>) -> O {
+        let mut queue = self.queue.lock().unwrap();
+
+        let ticket = queue.next_ticket;
+        queue.next_ticket += 1;
+        queue.pending.push((ticket, *pos));
+
+        let deadline = Instant::now() + MAX_WAIT;
+
+        loop {
+            if let Some(idx) = queue.done.iter().position(|&(t, _)| t == ticket) {
+                return queue.done.swap_remove(idx).1;
+            }
+
+            let now = Instant::now();
+            let queued = queue.pending.iter().any(|&(t, _)| t == ticket);
+
+            if queued && (queue.pending.len() >= size || now >= deadline) {
+                let (tickets, positions): (Vec<_>, Vec<_>) =
+                    std::mem::take(&mut queue.pending).into_iter().unzip();
+
+                drop(queue);
+                let outputs = f(&positions);
+                queue = self.queue.lock().unwrap();
+
+                let mut mine = None;
+                for (t, out) in tickets.into_iter().zip(outputs) {
+                    if t == ticket {
+                        mine = Some(out);
+                    } else {
+                        queue.done.push((t, out));
+                    }
+                }
+
+                self.ready.notify_all();
+                return mine.unwrap();
+            }
+
+            // once another thread has taken the batch there
+            // is nothing to do but wait for it to finish
+            queue = if queued {
+                self.ready.wait_timeout(queue, deadline - now).unwrap().0
+            } else {
+                self.ready.wait(queue).unwrap()
+            };
+        }
+    }
+}
+
+/// Batched network evaluation, for use with `Searcher::with_batching`.
+///
+/// Rather than each thread running the networks on its own leaf, leaves
+/// (with virtual loss already applied on the path to them) are queued until
+/// `size` of them, or one from every searching thread, are waiting for the
+/// same network, and are then run through the first layer of the network
+/// together. Threads held by a lock or waiting for the other network are
+/// not detected, so a batch that doesn't fill is instead run by a queued
+/// thread once it has waited `MAX_WAIT`.
+pub struct EvalBatch {
+    size: usize,
+    active: AtomicUsize,
+    value: Batcher<(f32, f32, f32)>,
+    policy: Batcher<Accumulator<i16, { POLICY_L1 / 2 }>>,
+}
+
+/// Counts a thread as searching whilst alive, see `EvalBatch::join`.
+pub(super) struct Active<'a>(&'a AtomicUsize);
+
+impl Drop for Active<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl EvalBatch {
+    pub fn new(size: usize) -> Self {
+        Self {
+            size: size.max(1),
+            active: AtomicUsize::new(0),
+            value: Batcher::new(),
+            policy: Batcher::new(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Batches are never larger than the number of threads searching,
+    /// so each search thread joins for the duration of its playouts.
+    pub(super) fn join(&self) -> Active<'_> {
+        self.active.fetch_add(1, Ordering::Relaxed);
+        Active(&self.active)
+    }
+
+    fn limit(&self) -> usize {
+        self.size.min(self.active.load(Ordering::Relaxed)).max(1)
+    }
+
+    pub(super) fn value(&self, value: &ValueNetwork, pos: &Position) -> (f32, f32, f32) {
+        self.value
+            .eval(pos, self.limit(), |batch| value.eval_batch(batch))
+    }
+
+    pub(super) fn policy_hl(
+        &self,
+        policy: &PolicyNetwork,
+        pos: &Position,
+    ) -> Accumulator<i16, { POLICY_L1 / 2 }> {
+        self.policy
+            .eval(pos, self.limit(), |batch| policy.hl_batch(batch))
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::Mutex;
+
+use crate::{
+    chess::Move,
+    tree::{Node, NodePtr, Tree},
+};
+
+use super::{
+    selection::{child_stats, Gumbel},
+    SearchHelpers, Searcher, SelectionPolicy,
+};
+
+/// More than the number of legal moves in any position.
+const MAX_ACTIONS: usize = 256;
+
+struct Halving {
+    /// Root children still being considered.
+    remaining: Vec<usize>,
+    /// Root visits at which the current phase ends.
+    phase_end: u64,
+    phases_left: usize,
+}
+
+/// Gumbel MuZero's root search (Danihelka et al. 2022): sample the top
+/// `considered` root moves without replacement via the Gumbel-top-k trick,
+/// then split the node budget between them with sequential halving. Below
+/// the root, children are chosen deterministically as in `Gumbel`.
+///
+/// Use with `Searcher::with_selection` and a node limit of `budget`; the
+/// move to play is then `best_move` and the training target for the
+/// policy is `improved_policy`.
+pub struct GumbelRoot {
+    gumbels: Vec<f32>,
+    budget: usize,
+    considered: usize,
+    halving: Mutex<Option<Halving>>,
+}
+
+impl GumbelRoot {
+    /// `uniform` must produce samples from (0, 1).
+    pub fn new(budget: usize, considered: usize, mut uniform: impl FnMut() -> f32) -> Self {
+        let gumbels = (0..MAX_ACTIONS)
+            .map(|_| -(-uniform().clamp(1e-9, 1.0 - 1e-7).ln()).ln())
+            .collect();
+
+        Self {
+            gumbels,
+            budget,
+            considered: considered.max(1),
+            halving: Mutex::new(None),
+        }
+    }
+
+    /// `g(a) + log p(a) + sigma(q(a))`, the score by which root moves are ranked.
+    fn scores(&self, tree: &Tree, root: &Node, fpu: f32) -> Vec<f32> {
+        let stats = child_stats(tree, root, root.num_actions(), |child| {
+            SearchHelpers::get_action_value(child, fpu)
+        });
+
+        Gumbel::scores(&stats)
+            .into_iter()
+            .zip(&self.gumbels)
+            .map(|(score, gumbel)| score + gumbel)
+            .collect()
+    }
+
+    fn phase_visits(&self, remaining: usize, phases_left: usize) -> u64 {
+        (self.budget / (phases_left.max(1) * remaining)).max(1) as u64
+    }
+
+    fn start(&self, tree: &Tree, root: &Node) -> Halving {
+        let scores = self.scores(tree, root, SearchHelpers::get_fpu(root));
+        let mut remaining = (0..root.num_actions()).collect::<Vec<_>>();
+
+        remaining.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+        remaining.truncate(self.considered);
+
+        let phases_left = remaining.len().next_power_of_two().trailing_zeros() as usize;
+        let phase_end = root.visits()
+            + self.phase_visits(remaining.len(), phases_left) * remaining.len() as u64;
+
+        Halving {
+            remaining,
+            phase_end,
+            phases_left,
+        }
+    }
+
+    /// The root move to play: the best remaining candidate.
+    pub fn best_move(&self, tree: &Tree) -> Move {
+        let root_ptr = tree.root_node();
+        let root = &tree[root_ptr];
+
+        if !root.has_children() {
+            return Move::NULL;
+        }
+
+        let scores = self.scores(tree, root, SearchHelpers::get_fpu(root));
+        let halving = self.halving.lock().unwrap();
+        let candidates = halving.as_ref().map_or_else(
+            || (0..root.num_actions()).collect(),
+            |h| h.remaining.clone(),
+        );
+
+        let best = candidates
+            .into_iter()
+            .max_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+            .unwrap_or(0);
+
+        tree[root.actions() + best].parent_move()
+    }
+
+    /// The improved policy `softmax(log p + sigma(completed q))` over all root
+    /// moves, with unvisited moves taking the root's value as their Q.
+    pub fn improved_policy(&self, tree: &Tree) -> Vec<(Move, f32)> {
+        let root = &tree[tree.root_node()];
+        let first_child_ptr = root.actions();
+        let fpu = SearchHelpers::get_fpu(root);
+        let stats = child_stats(tree, root, root.num_actions(), |child| {
+            SearchHelpers::get_action_value(child, fpu)
+        });
+
+        Gumbel::improved_policy(&stats)
+            .into_iter()
+            .enumerate()
+            .map(|(action, pi)| (tree[first_child_ptr + action].parent_move(), pi))
+            .collect()
+    }
+}
+
+impl SelectionPolicy for GumbelRoot {
+    fn pick_action(&self, searcher: &Searcher, ptr: NodePtr, node: &Node) -> usize {
+        if ptr != searcher.tree.root_node() {
+            return Gumbel.pick_action(searcher, ptr, node);
+        }
+
+        let mut halving = self.halving.lock().unwrap();
+        let halving = halving.get_or_insert_with(|| self.start(searcher.tree, node));
+
+        // halve the candidates at the end of each phase
+        if node.visits() >= halving.phase_end && halving.remaining.len() > 1 {
+            let scores = self.scores(searcher.tree, node, SearchHelpers::get_fpu(node));
+
+            halving
+                .remaining
+                .sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+            halving
+                .remaining
+                .truncate(halving.remaining.len().div_ceil(2));
+            halving.phases_left = halving.phases_left.saturating_sub(1);
+
+            let remaining = halving.remaining.len();
+            halving.phase_end = node.visits()
+                + self.phase_visits(remaining, halving.phases_left) * remaining as u64;
+        }
+
+        // share visits equally between the candidates
+        let first_child_ptr = node.actions();
+        halving
+            .remaining
+            .iter()
+            .copied()
+            .min_by_key(|&action| searcher.tree[first_child_ptr + action].visits())
+            .unwrap_or(0)
+    }
+}
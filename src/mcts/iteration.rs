@@ -1,9 +1,14 @@
 use crate::{
     chess::{ChessState, GameState},
-    tree::{Node, NodePtr},
+    networks::ValueAccumulators,
+    tree::NodePtr,
 };
 
-use super::{SearchHelpers, Searcher};
+use super::Searcher;
+
+/// How far the value of a transposed child may drift from that of its
+/// shared position in MCGS before it is corrected rather than searched.
+const GRAPH_EPSILON: f32 = 0.01;
 
 pub fn perform_one(
     searcher: &Searcher,
@@ -11,8 +16,10 @@ pub fn perform_one(
     ptr: NodePtr,
     depth: &mut usize,
     thread_id: usize,
+    accs: &mut ValueAccumulators,
 ) -> Option<(f32, f32)> {
     *depth += 1;
+    accs.push(*depth - 1, &pos.board());
 
     let cur_hash = pos.hash();
     let mut child_hash: Option<u64> = None;
@@ -20,41 +27,69 @@ pub fn perform_one(
     let tree = searcher.tree;
     let node = &tree[ptr];
 
+    // whether this visit adds to the statistics shared by its position in MCGS
+    let mut share = true;
+
     let mut value = if node.is_terminal() || node.visits() == 0 {
         if node.visits() == 0 {
             node.set_state(pos.game_state());
+
+            // the root is never made terminal by the tablebases,
+            // its moves are instead filtered before the search
+            if node.state() == GameState::Ongoing && ptr != tree.root_node() {
+                if let Some(state) = searcher.tablebases.and_then(|tb| tb.probe_state(pos)) {
+                    node.set_state(state);
+                }
+            }
         }
 
-        // probe hash table to use in place of network
+        // in MCGS, a position visited elsewhere in the tree takes its shared
+        // value, and otherwise probe hash table to use in place of network
         if node.state() == GameState::Ongoing {
-            if let Some(entry) = tree.probe_hash(cur_hash) {
+            if let Some(entry) = tree.probe_graph(cur_hash) {
+                share = false;
+                (1.0 - entry.q(), entry.d())
+            } else if let Some(entry) = tree.probe_hash(cur_hash) {
                 (entry.q(), entry.d())
             } else {
-                get_utility(searcher, ptr, pos)
+                get_utility(searcher, ptr, pos, accs)
             }
         } else {
-            get_utility(searcher, ptr, pos)
+            get_utility(searcher, ptr, pos, accs)
         }
     } else {
         // expand node on the second visit
         if node.is_not_expanded() {
-            tree.expand_node(
-                ptr,
-                pos,
-                searcher.params,
-                searcher.policy,
-                *depth,
-                thread_id,
-            )?;
+            if let Some(batch) = searcher.batch {
+                let hl = batch.policy_hl(searcher.policy, &pos.board());
+                tree.expand_node_with_hl(
+                    ptr,
+                    pos,
+                    searcher.params,
+                    searcher.policy,
+                    &hl,
+                    *depth,
+                    thread_id,
+                )?;
+            } else {
+                tree.expand_node(
+                    ptr,
+                    pos,
+                    searcher.params,
+                    searcher.policy,
+                    *depth,
+                    thread_id,
+                )?;
+            }
         }
 
         // this node has now been accessed so we need to move its
         // children across if they are in the other tree half
         tree.fetch_children(ptr, thread_id)?;
 
-        // select action to take via PUCT
+        // select action to take via the selection policy (PUCT by default)
         let stm = pos.stm();
-        let action = pick_action(searcher, ptr, node);
+        let action = searcher.selection.pick_action(searcher, ptr, node);
 
         let child_ptr = node.actions() + action;
 
@@ -66,30 +101,35 @@ pub fn perform_one(
         child_hash = Some(pos.hash());
 
         child_visits = tree[child_ptr].visits();
-        tree[child_ptr].inc_threads();
 
-        // acquire lock to avoid issues with desynced setting of
-        // game state between threads when threads > 1
-        let lock = if tree[child_ptr].visits() == 0 {
-            Some(node.actions_mut())
+        let u = if let Some(u) = transfer_from_graph(searcher, child_ptr, child_hash, thread_id) {
+            u
         } else {
-            None
-        };
+            tree[child_ptr].inc_threads();
 
-        // descend further
-        let maybe_u = perform_one(searcher, pos, child_ptr, depth, thread_id);
+            // acquire lock to avoid issues with desynced setting of
+            // game state between threads when threads > 1
+            let lock = if tree[child_ptr].visits() == 0 {
+                Some(node.actions_mut())
+            } else {
+                None
+            };
 
-        drop(lock);
+            // descend further
+            let maybe_u = perform_one(searcher, pos, child_ptr, depth, thread_id, accs);
 
-        tree[child_ptr].dec_threads();
+            drop(lock);
 
-        let u = maybe_u?;
+            tree[child_ptr].dec_threads();
+
+            maybe_u?
+        };
 
         if tree[child_ptr].state() == GameState::Ongoing {
             tree.update_butterfly(stm, mov, u.0, searcher.params);
         }
 
-        tree.propogate_proven_mates(ptr, tree[child_ptr].state());
+        tree.propogate_proven_mates(ptr, child_ptr);
 
         u
     };
@@ -105,17 +145,71 @@ pub fn perform_one(
     // flip perspective and backpropagate
     value.0 = 1.0 - value.0;
     tree.update_node_stats(ptr, value.0, value.1, thread_id);
+
+    // `pos` is now the leaf of this iteration, and a value ending in a draw
+    // by repetition depends on the path taken, so is not shared, nor is that
+    // of a proven draw, which may rest on such draws further down the tree
+    let path_dependent = pos.is_repetition() || node.state() == GameState::Draw;
+
+    if share && tree.has_graph() && !path_dependent {
+        tree.push_graph(cur_hash, value.0, value.1);
+    }
+
     Some(value)
 }
 
-fn get_utility(searcher: &Searcher, ptr: NodePtr, pos: &ChessState) -> (f32, f32) {
+/// In MCGS, a child that has fallen behind the shared statistics of its
+/// position, having been visited less often than the position has elsewhere
+/// in the tree and with a value too different from the shared one, is not
+/// searched again. It instead takes the value that brings its own in line
+/// with the shared one, as in Czech et al., "Monte-Carlo Graph Search for
+/// AlphaZero" (2020).
+fn transfer_from_graph(
+    searcher: &Searcher,
+    child_ptr: NodePtr,
+    child_hash: Option<u64>,
+    thread_id: usize,
+) -> Option<(f32, f32)> {
+    let tree = searcher.tree;
+    let child = &tree[child_ptr];
+
+    // unvisited children are first visited as usual, to set their state
+    if child.visits() == 0 || child.is_terminal() {
+        return None;
+    }
+
+    let entry = tree.probe_graph(child_hash?)?;
+    let visits = child.visits() as f32;
+
+    if entry.visits() <= child.visits() || (child.q() - entry.q()).abs() <= GRAPH_EPSILON {
+        return None;
+    }
+
+    let target = |shared: f32, own: f32| (shared * (visits + 1.0) - own * visits).clamp(0.0, 1.0);
+    let q = target(entry.q(), child.q());
+    let draw = target(entry.d(), child.draw());
+
+    tree.update_node_stats(child_ptr, q, draw, thread_id);
+
+    Some((q, draw))
+}
+
+fn get_utility(
+    searcher: &Searcher,
+    ptr: NodePtr,
+    pos: &ChessState,
+    accs: &mut ValueAccumulators,
+) -> (f32, f32) {
     match searcher.tree[ptr].state() {
         GameState::Ongoing => {
-            let eval = pos.eval_with_contempt(
-                searcher.value,
-                searcher.params,
-                searcher.tree.root_position().stm(),
-            );
+            let root_stm = searcher.tree.root_position().stm();
+            let eval = if let Some(batch) = searcher.batch {
+                let wdl = batch.value(searcher.value, &pos.board());
+                pos.wdl_with_contempt(wdl, searcher.params, root_stm)
+            } else {
+                let wdl = searcher.value.eval_incremental(accs);
+                pos.wdl_with_contempt(wdl, searcher.params, root_stm)
+            };
             (eval.contempt.score(), eval.contempt.draw)
         }
         GameState::Draw => (0.5, 1.0),
@@ -123,47 +217,3 @@ fn get_utility(searcher: &Searcher, ptr: NodePtr, pos: &ChessState) -> (f32, f32
         GameState::Won(_) => (1.0, 0.0),
     }
 }
-
-fn pick_action(searcher: &Searcher, ptr: NodePtr, node: &Node) -> usize {
-    let is_root = ptr == searcher.tree.root_node();
-
-    let cpuct = SearchHelpers::get_cpuct(searcher.params, node, is_root);
-    let fpu = SearchHelpers::get_fpu(node);
-    let expl_scale = SearchHelpers::get_explore_scaling(searcher.params, node);
-
-    let expl = cpuct * expl_scale;
-
-    let actions_ptr = node.actions();
-    let mut acc = 0.0;
-    let mut k = 0;
-    while k < node.num_actions() && acc < searcher.params.policy_top_p() {
-        acc += searcher.tree[actions_ptr + k].policy();
-        k += 1;
-    }
-    let mut limit = k.max(searcher.params.min_policy_actions() as usize);
-    let mut thresh = 1u64 << (searcher.params.visit_threshold_power() as u32);
-    while node.visits() >= thresh && limit < node.num_actions() {
-        limit += 2;
-        thresh = thresh.checked_shl(1).unwrap_or(u64::MAX);
-    }
-    limit = limit.min(node.num_actions());
-
-    searcher
-        .tree
-        .get_best_child_by_key_lim(ptr, limit, |child| {
-            let mut q = SearchHelpers::get_action_value(child, fpu);
-
-            // virtual loss
-            let threads = f64::from(child.threads());
-            if threads > 0.0 {
-                let visits = child.visits() as f64;
-                let q2 = f64::from(q) * visits
-                    / (visits + 1.0 + searcher.params.virtual_loss_weight() * (threads - 1.0));
-                q = q2 as f32;
-            }
-
-            let u = expl * child.policy() / (1 + child.visits()) as f32;
-
-            q + u
-        })
-}
@@ -0,0 +1,295 @@
+use std::{
+    marker::PhantomData,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+type Task = Box<dyn FnOnce() + Send + 'static>;
+
+/// Tracks the jobs spawned in a `Scope` that are yet to finish.
+#[derive(Default)]
+struct ScopeState {
+    pending: Mutex<usize>,
+    finished: Condvar,
+    panicked: AtomicBool,
+}
+
+impl ScopeState {
+    fn finish(&self, panicked: bool) {
+        if panicked {
+            self.panicked.store(true, Ordering::Relaxed);
+        }
+
+        let mut pending = self.pending.lock().unwrap();
+        *pending -= 1;
+
+        if *pending == 0 {
+            self.finished.notify_all();
+        }
+    }
+
+    fn wait(&self) {
+        let mut pending = self.pending.lock().unwrap();
+
+        while *pending > 0 {
+            pending = self.finished.wait(pending).unwrap();
+        }
+    }
+}
+
+struct Job {
+    task: Task,
+    scope: Arc<ScopeState>,
+}
+
+#[derive(Default)]
+struct Worker {
+    job: Mutex<Option<Job>>,
+    wake: Condvar,
+    busy: AtomicBool,
+    exit: AtomicBool,
+}
+
+/// A pool of long-lived threads, parked between jobs, for running the
+/// search threads without spawning new OS threads on every tree flip.
+/// Workers are only created when every existing worker is busy, so the
+/// pool grows to the largest number of threads searched with at once.
+#[derive(Default)]
+pub struct ThreadPool {
+    workers: Mutex<Vec<(Arc<Worker>, JoinHandle<()>)>>,
+    affinity: Arc<Affinity>,
+}
+
+impl ThreadPool {
+    /// Run `f`, which may spawn jobs onto the pool that borrow from the
+    /// enclosing stack frame, and wait for all of them to finish, as with
+    /// `std::thread::scope`. Panics if any of the jobs panicked.
+    pub fn scope<'env, R>(&'env self, f: impl FnOnce(&Scope<'env>) -> R) -> R {
+        let scope = Scope {
+            pool: self,
+            state: Arc::default(),
+            _env: PhantomData,
+        };
+
+        // jobs must not outlive the borrows they were spawned with,
+        // even if `f` unwinds
+        struct WaitOnDrop<'a>(&'a ScopeState);
+
+        impl Drop for WaitOnDrop<'_> {
+            fn drop(&mut self) {
+                self.0.wait();
+            }
+        }
+
+        let result = {
+            let _wait = WaitOnDrop(&scope.state);
+            f(&scope)
+        };
+
+        assert!(
+            !scope.state.panicked.load(Ordering::Relaxed),
+            "a thread in the pool panicked"
+        );
+
+        result
+    }
+
+    /// Pin each worker to its own CPU, of those the process was allowed to
+    /// run on when the pool was created, or release them again. Only has an
+    /// effect on Linux, and otherwise returns `false`.
+    pub fn set_affinity(&self, pinned: bool) -> bool {
+        self.affinity.pinned.store(pinned, Ordering::Relaxed);
+        !self.affinity.cpus.is_empty()
+    }
+
+    fn run(&self, job: Job) {
+        let mut workers = self.workers.lock().unwrap();
+
+        let idle = workers.iter().find(|(worker, _)| {
+            worker
+                .busy
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+        });
+
+        let worker = if let Some((worker, _)) = idle {
+            worker.clone()
+        } else {
+            let worker = Arc::new(Worker {
+                busy: AtomicBool::new(true),
+                ..Worker::default()
+            });
+
+            let idx = workers.len();
+            let handle = {
+                let worker = worker.clone();
+                let affinity = self.affinity.clone();
+                thread::Builder::new()
+                    .name(format!("search-{idx}"))
+                    .spawn(move || work(&worker, idx, &affinity))
+                    .expect("failed to spawn search thread")
+            };
+
+            workers.push((worker.clone(), handle));
+            worker
+        };
+
+        drop(workers);
+
+        *worker.job.lock().unwrap() = Some(job);
+        worker.wake.notify_one();
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        for (worker, handle) in mem::take(self.workers.get_mut().unwrap()) {
+            worker.exit.store(true, Ordering::Relaxed);
+
+            // hold the lock so that the wake up can't be missed
+            let _job = worker.job.lock().unwrap();
+            worker.wake.notify_one();
+            drop(_job);
+
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Jobs spawned in a `Scope` are waited for when it ends.
+pub struct Scope<'env> {
+    pool: &'env ThreadPool,
+    state: Arc<ScopeState>,
+    /// Invariant in `'env`, so that a job can't borrow anything
+    /// that doesn't outlive the whole of `ThreadPool::scope`.
+    _env: PhantomData<&'env mut &'env ()>,
+}
+
+impl<'env> Scope<'env> {
+    pub fn spawn(&self, f: impl FnOnce() + Send + 'env) {
+        let task: Box<dyn FnOnce() + Send + 'env> = Box::new(f);
+
+        // SAFETY: `ThreadPool::scope` does not return, or unwind, until
+        // every job spawned in its scope has finished, so the task can't
+        // outlive anything it borrows
+        let task = unsafe { mem::transmute::<Box<dyn FnOnce() + Send + 'env>, Task>(task) };
+
+        *self.state.pending.lock().unwrap() += 1;
+
+        self.pool.run(Job {
+            task,
+            scope: self.state.clone(),
+        });
+    }
+}
+
+fn work(worker: &Worker, idx: usize, affinity: &Affinity) {
+    let mut pinned = false;
+
+    loop {
+        let job = {
+            let mut slot = worker.job.lock().unwrap();
+
+            loop {
+                if let Some(job) = slot.take() {
+                    break job;
+                }
+
+                if worker.exit.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                slot = worker.wake.wait(slot).unwrap();
+            }
+        };
+
+        let pin = affinity.pinned.load(Ordering::Relaxed);
+        if pin != pinned {
+            affinity.apply(idx, pin);
+            pinned = pin;
+        }
+
+        let panicked = panic::catch_unwind(AssertUnwindSafe(job.task)).is_err();
+
+        // become available before the scope can end, so that the
+        // next scope reuses this worker rather than spawning another
+        worker.busy.store(false, Ordering::Release);
+        job.scope.finish(panicked);
+    }
+}
+
+/// CPU affinity of the workers, with `cpus` the CPUs the process
+/// was allowed to run on when the pool was created.
+struct Affinity {
+    pinned: AtomicBool,
+    cpus: Vec<usize>,
+}
+
+impl Default for Affinity {
+    fn default() -> Self {
+        Self {
+            pinned: AtomicBool::new(false),
+            cpus: cpus::allowed(),
+        }
+    }
+}
+
+impl Affinity {
+    /// Pin the calling worker `idx` to a single CPU, or release it.
+    fn apply(&self, idx: usize, pin: bool) {
+        if self.cpus.is_empty() {
+            return;
+        }
+
+        if pin {
+            cpus::set(&[self.cpus[idx % self.cpus.len()]]);
+        } else {
+            cpus::set(&self.cpus);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod cpus {
+    use std::mem;
+
+    pub fn allowed() -> Vec<usize> {
+        unsafe {
+            let mut set: libc::cpu_set_t = mem::zeroed();
+
+            if libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+                return Vec::new();
+            }
+
+            (0..libc::CPU_SETSIZE as usize)
+                .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+                .collect()
+        }
+    }
+
+    pub fn set(cpus: &[usize]) {
+        unsafe {
+            let mut set: libc::cpu_set_t = mem::zeroed();
+
+            for &cpu in cpus {
+                libc::CPU_SET(cpu, &mut set);
+            }
+
+            libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod cpus {
+    pub fn allowed() -> Vec<usize> {
+        Vec::new()
+    }
+
+    pub fn set(_cpus: &[usize]) {}
+}
@@ -0,0 +1,236 @@
+use std::{fmt::Write, time::Duration};
+
+use crate::chess::{ChessState, GameState, Move};
+
+/// A snapshot of the state of a search, as reported
+/// periodically and once the search has finished.
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: usize,
+    pub seldepth: usize,
+    pub elapsed: Duration,
+    pub nodes: usize,
+    /// Calibrated win/draw/loss probabilities for the side to move.
+    pub wdl: [f32; 3],
+    /// Occupancy of the hash table by the current search, per mille.
+    pub hashfull: usize,
+    /// The MultiPV lines, best first.
+    pub lines: Vec<PvInfo>,
+    /// Every child of the root, best first.
+    pub children: Vec<ChildInfo>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Score {
+    Cp(i32),
+    /// Moves until mate, negative if the side to move is getting mated.
+    Mate(i32),
+}
+
+#[derive(Clone, Debug)]
+pub struct PvInfo {
+    pub depth: usize,
+    pub seldepth: usize,
+    pub nodes: usize,
+    pub score: Score,
+    pub wdl: [f32; 3],
+    pub policy: f32,
+    pub moves: Vec<Move>,
+}
+
+/// Statistics of a root child, from the point of view of the side to move.
+#[derive(Clone, Copy, Debug)]
+pub struct ChildInfo {
+    pub mov: Move,
+    pub visits: u64,
+    pub q: f32,
+    pub draw: f32,
+    pub var: f32,
+    pub policy: f32,
+    /// The proven result after playing the move, if any.
+    pub state: GameState,
+    pub mate: Option<i32>,
+}
+
+impl ChildInfo {
+    /// The proven result after playing the move, flipped from
+    /// the opponent's point of view to the side to move's.
+    pub fn result(&self) -> &'static str {
+        match self.state {
+            GameState::Ongoing => "ongoing",
+            GameState::Draw => "draw",
+            GameState::Lost(_) => "won",
+            GameState::Won(_) => "lost",
+        }
+    }
+}
+
+fn wdl_permill(wdl: [f32; 3]) -> [i32; 3] {
+    wdl.map(|v| (v * 1000.0).round() as i32)
+}
+
+impl SearchInfo {
+    pub fn nps(&self, nodes: usize) -> u64 {
+        let secs = self.elapsed.as_secs_f64();
+
+        if secs > 0.0 {
+            (nodes as f64 / secs) as u64
+        } else {
+            0
+        }
+    }
+
+    /// Print the report as standard UCI `info` lines, one per MultiPV line.
+    pub fn print_uci(&self, pos: &ChessState, multipv: usize, gui_compatibility: bool) {
+        for (idx, line) in self.lines.iter().enumerate() {
+            print!("info depth {} seldepth {} ", line.depth, line.seldepth);
+            if multipv > 1 {
+                print!("multipv {} ", idx + 1);
+            }
+
+            match line.score {
+                Score::Mate(mate) => print!("score mate {mate} "),
+                Score::Cp(cp) => {
+                    print!("score cp {cp} ");
+
+                    if !gui_compatibility {
+                        let [w, d, l] = wdl_permill(line.wdl);
+                        print!("wdl {w} {d} {l} ");
+                    }
+                }
+            }
+
+            print!(
+                "time {} nodes {} nps {} hashfull {} ",
+                self.elapsed.as_millis(),
+                line.nodes,
+                self.nps(line.nodes),
+                self.hashfull
+            );
+
+            if !gui_compatibility {
+                let policy = (line.policy * 10000.0).round();
+                print!("policy {policy:.0} ");
+            }
+
+            print!("pv");
+
+            for &mov in &line.moves {
+                print!(" {}", pos.conv_mov_to_str(mov));
+            }
+
+            println!();
+        }
+    }
+
+    /// Print an `info string` line with the statistics of each root child,
+    /// for GUIs to show as a live move list.
+    pub fn print_move_stats(&self, pos: &ChessState) {
+        for child in &self.children {
+            print!(
+                "info string {:<5} N: {:>8} P: {:>6.2}% Q: {:.4} D: {:.4} V: {:.4} S: {}",
+                pos.conv_mov_to_str(child.mov),
+                child.visits,
+                child.policy * 100.0,
+                child.q,
+                child.draw,
+                child.var,
+                child.result(),
+            );
+
+            if let Some(mate) = child.mate {
+                print!(" M: {mate}");
+            }
+
+            println!();
+        }
+    }
+
+    /// The report as a single line JSON object, for consumption by analysis tools.
+    pub fn to_json(&self, pos: &ChessState) -> String {
+        let mut json = String::new();
+        let [w, d, l] = wdl_permill(self.wdl);
+
+        write!(
+            json,
+            "{{\"depth\":{},\"seldepth\":{},\"time\":{},\"nodes\":{},\"nps\":{},\"hashfull\":{},\"wdl\":[{w},{d},{l}],\"lines\":[",
+            self.depth,
+            self.seldepth,
+            self.elapsed.as_millis(),
+            self.nodes,
+            self.nps(self.nodes),
+            self.hashfull,
+        )
+        .unwrap();
+
+        for (idx, line) in self.lines.iter().enumerate() {
+            if idx > 0 {
+                json.push(',');
+            }
+
+            write!(
+                json,
+                "{{\"multipv\":{},\"depth\":{},\"seldepth\":{},\"nodes\":{},\"nps\":{},",
+                idx + 1,
+                line.depth,
+                line.seldepth,
+                line.nodes,
+                self.nps(line.nodes),
+            )
+            .unwrap();
+
+            match line.score {
+                Score::Mate(mate) => write!(json, "\"score\":{{\"mate\":{mate}}},").unwrap(),
+                Score::Cp(cp) => {
+                    let [w, d, l] = wdl_permill(line.wdl);
+                    write!(json, "\"score\":{{\"cp\":{cp}}},\"wdl\":[{w},{d},{l}],").unwrap();
+                }
+            }
+
+            let pv = line
+                .moves
+                .iter()
+                .map(|&mov| format!("\"{}\"", pos.conv_mov_to_str(mov)))
+                .collect::<Vec<_>>();
+
+            write!(
+                json,
+                "\"policy\":{:.4},\"pv\":[{}]}}",
+                line.policy,
+                pv.join(",")
+            )
+            .unwrap();
+        }
+
+        json.push_str("],\"children\":[");
+
+        for (idx, child) in self.children.iter().enumerate() {
+            if idx > 0 {
+                json.push(',');
+            }
+
+            write!(
+                json,
+                "{{\"move\":\"{}\",\"visits\":{},\"q\":{:.4},\"draw\":{:.4},\"var\":{:.6},\"policy\":{:.4},\"state\":\"{}\"",
+                pos.conv_mov_to_str(child.mov),
+                child.visits,
+                child.q,
+                child.draw,
+                child.var,
+                child.policy,
+                child.result(),
+            )
+            .unwrap();
+
+            if let Some(mate) = child.mate {
+                write!(json, ",\"mate\":{mate}").unwrap();
+            }
+
+            json.push('}');
+        }
+
+        json.push_str("]}");
+
+        json
+    }
+}
@@ -0,0 +1,313 @@
+use crate::tree::{Node, NodePtr, Tree};
+
+use super::{SearchHelpers, Searcher};
+
+/// A rule for choosing which child to descend into during selection.
+pub trait SelectionPolicy: Sync {
+    fn pick_action(&self, searcher: &Searcher, ptr: NodePtr, node: &Node) -> usize;
+}
+
+/// The available selection policies, as chosen by the `SelectionPolicy` UCI option.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Selection {
+    #[default]
+    Puct,
+    Ucb1Tuned,
+    Gumbel,
+    Rpo,
+}
+
+impl Selection {
+    pub const ALL: [Self; 4] = [Self::Puct, Self::Ucb1Tuned, Self::Gumbel, Self::Rpo];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Puct => "puct",
+            Self::Ucb1Tuned => "ucb1-tuned",
+            Self::Gumbel => "gumbel",
+            Self::Rpo => "rpo",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|selection| selection.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn policy(self) -> &'static dyn SelectionPolicy {
+        match self {
+            Self::Puct => &Puct,
+            Self::Ucb1Tuned => &Ucb1Tuned,
+            Self::Gumbel => &Gumbel,
+            Self::Rpo => &Rpo,
+        }
+    }
+}
+
+/// The number of children considered for selection, pruning those
+/// outside the top-p of the policy until the node has enough visits.
+fn action_limit(searcher: &Searcher, node: &Node) -> usize {
+    let actions_ptr = node.actions();
+    let mut acc = 0.0;
+    let mut k = 0;
+    while k < node.num_actions() && acc < searcher.params.policy_top_p() {
+        acc += searcher.tree[actions_ptr + k].policy();
+        k += 1;
+    }
+    let mut limit = k.max(searcher.params.min_policy_actions() as usize);
+    let mut thresh = 1u64 << (searcher.params.visit_threshold_power() as u32);
+    while node.visits() >= thresh && limit < node.num_actions() {
+        limit += 2;
+        thresh = thresh.checked_shl(1).unwrap_or(u64::MAX);
+    }
+    limit.min(node.num_actions())
+}
+
+/// The value assigned to a child that is proven unable to improve on
+/// the result already guaranteed to its parent, finite so that a node
+/// with only such children still selects one of them.
+const PRUNED_VALUE: f32 = -1e6;
+
+/// The value of a child, with unvisited children taking
+/// the first play urgency and a virtual loss applied for
+/// each other thread currently searching below it.
+fn action_value(searcher: &Searcher, node: &Node, child: &Node, fpu: f32) -> f32 {
+    if Tree::is_pruned(node, child) {
+        return PRUNED_VALUE;
+    }
+
+    let mut q = SearchHelpers::get_action_value(child, fpu);
+
+    // virtual loss
+    let threads = f64::from(child.threads());
+    if threads > 0.0 {
+        let visits = child.visits() as f64;
+        let q2 = f64::from(q) * visits
+            / (visits + 1.0 + searcher.params.virtual_loss_weight() * (threads - 1.0));
+        q = q2 as f32;
+    }
+
+    q
+}
+
+fn exploration(searcher: &Searcher, ptr: NodePtr, node: &Node) -> f32 {
+    let is_root = ptr == searcher.tree.root_node();
+
+    let cpuct = SearchHelpers::get_cpuct(searcher.params, node, is_root);
+    let expl_scale = SearchHelpers::get_explore_scaling(searcher.params, node);
+
+    cpuct * expl_scale
+}
+
+/// The first `limit` children, as `(visits, q, policy)`.
+pub(super) fn child_stats(
+    tree: &Tree,
+    node: &Node,
+    limit: usize,
+    mut value: impl FnMut(&Node) -> f32,
+) -> Vec<(f32, f32, f32)> {
+    let first_child_ptr = node.actions();
+
+    (0..limit)
+        .map(|action| {
+            let child = &tree[first_child_ptr + action];
+            (child.visits() as f32, value(child), child.policy())
+        })
+        .collect()
+}
+
+fn selection_stats(searcher: &Searcher, node: &Node, limit: usize) -> Vec<(f32, f32, f32)> {
+    let fpu = SearchHelpers::get_fpu(node);
+    child_stats(searcher.tree, node, limit, |child| {
+        action_value(searcher, node, child, fpu)
+    })
+}
+
+/// Pick the child maximising the target policy minus the
+/// fraction of visits it has received so far.
+fn pick_by_target(stats: &[(f32, f32, f32)], target: &[f32]) -> usize {
+    let total = 1.0 + stats.iter().map(|&(visits, _, _)| visits).sum::<f32>();
+
+    let mut best = 0;
+    let mut best_score = f32::NEG_INFINITY;
+
+    for (action, (&(visits, _, _), &pi)) in stats.iter().zip(target).enumerate() {
+        let score = pi - visits / total;
+
+        if score > best_score {
+            best_score = score;
+            best = action;
+        }
+    }
+
+    best
+}
+
+/// AlphaZero-style PUCT, the default.
+pub struct Puct;
+
+impl SelectionPolicy for Puct {
+    fn pick_action(&self, searcher: &Searcher, ptr: NodePtr, node: &Node) -> usize {
+        let fpu = SearchHelpers::get_fpu(node);
+        let expl = exploration(searcher, ptr, node);
+        let limit = action_limit(searcher, node);
+
+        searcher
+            .tree
+            .get_best_child_by_key_lim(ptr, limit, |child| {
+                let q = action_value(searcher, node, child, fpu);
+                let u = expl * child.policy() / (1 + child.visits()) as f32;
+
+                q + u
+            })
+    }
+}
+
+/// UCB1-tuned, using the variance of each child's Q in the
+/// exploration bound. Unvisited children fall back to PUCT.
+pub struct Ucb1Tuned;
+
+impl SelectionPolicy for Ucb1Tuned {
+    fn pick_action(&self, searcher: &Searcher, ptr: NodePtr, node: &Node) -> usize {
+        let fpu = SearchHelpers::get_fpu(node);
+        let expl = exploration(searcher, ptr, node);
+        let limit = action_limit(searcher, node);
+        let ln_parent = (node.visits().max(1) as f32).ln();
+
+        searcher
+            .tree
+            .get_best_child_by_key_lim(ptr, limit, |child| {
+                let q = action_value(searcher, node, child, fpu);
+
+                if child.visits() == 0 {
+                    return q + expl * child.policy();
+                }
+
+                let visits = child.visits() as f32;
+                let var_bound = child.var() + (2.0 * ln_parent / visits).sqrt();
+                let u = expl * (ln_parent / visits * var_bound.min(0.25)).sqrt();
+
+                q + u
+            })
+    }
+}
+
+/// Gumbel MuZero's deterministic selection: the child furthest behind the
+/// improved policy `softmax(log p + sigma(q))` in visits. At the root
+/// only the best few children are considered, halving the number each
+/// time the root visits double, as in sequential halving.
+pub struct Gumbel;
+
+impl Gumbel {
+    const C_VISIT: f32 = 50.0;
+    const C_SCALE: f32 = 1.0;
+    const ROOT_CONSIDERED: usize = 16;
+    const ROOT_PHASE_VISITS: u64 = 64;
+
+    /// The Gumbel-free part of the root ranking of a child, `log p + sigma(q)`.
+    pub(super) fn scores(stats: &[(f32, f32, f32)]) -> Vec<f32> {
+        let max_visits = stats
+            .iter()
+            .map(|&(visits, _, _)| visits)
+            .fold(0.0, f32::max);
+        let sigma = (Self::C_VISIT + max_visits) * Self::C_SCALE;
+
+        stats
+            .iter()
+            .map(|&(_, q, policy)| policy.max(1e-6).ln() + sigma * q)
+            .collect()
+    }
+
+    pub(super) fn improved_policy(stats: &[(f32, f32, f32)]) -> Vec<f32> {
+        let scores = Self::scores(stats);
+        let max = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let exp = scores.iter().map(|s| (s - max).exp()).collect::<Vec<_>>();
+        let sum = exp.iter().sum::<f32>();
+
+        exp.iter().map(|e| e / sum).collect()
+    }
+}
+
+impl SelectionPolicy for Gumbel {
+    fn pick_action(&self, searcher: &Searcher, ptr: NodePtr, node: &Node) -> usize {
+        let limit = action_limit(searcher, node);
+        let stats = selection_stats(searcher, node, limit);
+
+        if ptr != searcher.tree.root_node() {
+            return pick_by_target(&stats, &Self::improved_policy(&stats));
+        }
+
+        // sequential halving over the children with the best scores
+        let mut considered = Self::ROOT_CONSIDERED.min(stats.len());
+        let mut phase_visits = Self::ROOT_PHASE_VISITS * considered as u64;
+        while node.visits() >= phase_visits && considered > 2 {
+            considered /= 2;
+            phase_visits *= 2;
+        }
+
+        let scores = Self::scores(&stats);
+        let mut ranked = (0..stats.len()).collect::<Vec<_>>();
+        ranked.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+        ranked.truncate(considered);
+
+        // visit the remaining candidates equally
+        ranked
+            .into_iter()
+            .min_by(|&a, &b| {
+                stats[a]
+                    .0
+                    .total_cmp(&stats[b].0)
+                    .then(scores[b].total_cmp(&scores[a]))
+            })
+            .unwrap_or(0)
+    }
+}
+
+/// Regularised policy optimisation (Grill et al. 2020): follow the
+/// solution of `max q.y - lambda KL(p, y)`, with `lambda` decaying
+/// as `sqrt(N) / (|A| + N)`.
+pub struct Rpo;
+
+impl SelectionPolicy for Rpo {
+    fn pick_action(&self, searcher: &Searcher, ptr: NodePtr, node: &Node) -> usize {
+        let expl = exploration(searcher, ptr, node);
+        let limit = action_limit(searcher, node);
+        let stats = selection_stats(searcher, node, limit);
+
+        let visits = node.visits() as f32;
+        let lambda = expl * visits.sqrt() / (stats.len() as f32 + visits);
+
+        if lambda <= 0.0 {
+            return pick_by_target(&stats, &stats.iter().map(|s| s.2).collect::<Vec<_>>());
+        }
+
+        // the solution is `y = lambda * p / (alpha - q)`, with
+        // `alpha` found by bisection such that `y` sums to 1
+        let target = |alpha: f32| {
+            stats
+                .iter()
+                .map(|&(_, q, policy)| lambda * policy / (alpha - q).max(1e-6))
+                .collect::<Vec<_>>()
+        };
+
+        let max_q = stats.iter().map(|s| s.1).fold(f32::NEG_INFINITY, f32::max);
+        let mut lo = stats
+            .iter()
+            .map(|&(_, q, policy)| q + lambda * policy)
+            .fold(f32::NEG_INFINITY, f32::max);
+        let mut hi = max_q + lambda;
+
+        for _ in 0..32 {
+            let mid = (lo + hi) / 2.0;
+
+            if target(mid).iter().sum::<f32>() > 1.0 {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+
+        pick_by_target(&stats, &target(hi))
+    }
+}
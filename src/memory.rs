@@ -0,0 +1,398 @@
+use std::{
+    alloc::{self, Layout},
+    fmt,
+    mem::{self, MaybeUninit},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The size of the huge pages used for explicit huge page allocations.
+const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// Alignment of allocations made without `mmap`, so that
+/// buffers always start on a page boundary.
+const PAGE_ALIGN: usize = 4096;
+
+static LARGE_PAGES: AtomicBool = AtomicBool::new(true);
+static NUMA_INTERLEAVE: AtomicBool = AtomicBool::new(false);
+
+/// Back large allocations made from now on with huge pages where available,
+/// as set by the `LargePages` UCI option.
+pub fn set_large_pages(enabled: bool) {
+    LARGE_PAGES.store(enabled, Ordering::Relaxed);
+}
+
+pub fn large_pages() -> bool {
+    LARGE_PAGES.load(Ordering::Relaxed)
+}
+
+/// Interleave large allocations made from now on over every NUMA
+/// node, as set by the `NumaInterleave` UCI option.
+pub fn set_numa_interleave(enabled: bool) {
+    NUMA_INTERLEAVE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn numa_interleave() -> bool {
+    NUMA_INTERLEAVE.load(Ordering::Relaxed)
+}
+
+/// The kind of pages backing a `LargeBuffer`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backing {
+    /// Explicit huge pages reserved by the system, `MAP_HUGETLB`.
+    Explicit,
+    /// Transparent huge pages requested with `madvise`.
+    Transparent,
+    Regular,
+}
+
+impl fmt::Display for Backing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Explicit => "explicit huge pages",
+            Self::Transparent => "transparent huge pages",
+            Self::Regular => "regular pages",
+        })
+    }
+}
+
+/// A fixed size, page aligned array for the large tables of the search,
+/// allocated with huge pages and interleaved over NUMA nodes according to
+/// `large_pages` and `numa_interleave` at the time of allocation, falling
+/// back to regular pages where these aren't available.
+pub struct LargeBuffer<T> {
+    ptr: NonNull<T>,
+    len: usize,
+    bytes: usize,
+    mapped: bool,
+    backing: Backing,
+    numa_nodes: usize,
+}
+
+// SAFETY: a `LargeBuffer` owns its elements, like a `Vec`
+unsafe impl<T: Send> Send for LargeBuffer<T> {}
+unsafe impl<T: Sync> Sync for LargeBuffer<T> {}
+
+impl<T> LargeBuffer<T> {
+    /// A buffer of `len` elements made by `init`, which is split between
+    /// `threads` threads as each thread's first touch of the memory can
+    /// decide where it is placed.
+    pub fn new(len: usize, threads: usize, init: impl Fn() -> T + Sync) -> Self
+    where
+        T: Send,
+    {
+        let mut buffer = Self::uninit(len);
+
+        if len > 0 {
+            let chunk_size = len.div_ceil(threads.max(1));
+
+            // SAFETY: the memory is allocated for `len` elements, which
+            // are all written below before the buffer is used
+            let uninit = unsafe {
+                slice::from_raw_parts_mut(buffer.ptr.as_ptr().cast::<MaybeUninit<T>>(), len)
+            };
+
+            std::thread::scope(|s| {
+                for chunk in uninit.chunks_mut(chunk_size) {
+                    s.spawn(|| {
+                        for elem in chunk {
+                            elem.write(init());
+                        }
+                    });
+                }
+            });
+        }
+
+        buffer.len = len;
+        buffer
+    }
+
+    /// A copy of `value`, for placing large read-only data such as the
+    /// network weights.
+    ///
+    /// # Safety
+    /// `T` must be valid to copy bitwise, without any pointers or handles.
+    pub unsafe fn copy_of(value: &T) -> Self {
+        let mut buffer = Self::uninit(1);
+
+        unsafe { ptr::copy_nonoverlapping(value, buffer.ptr.as_ptr(), 1) };
+
+        buffer.len = 1;
+        buffer
+    }
+
+    /// Allocated memory for `len` elements, with `self.len` zero
+    /// so that nothing is dropped if initialising them panics.
+    fn uninit(len: usize) -> Self {
+        let bytes = len
+            .checked_mul(mem::size_of::<T>())
+            .expect("allocation too large");
+
+        assert!(mem::align_of::<T>() <= PAGE_ALIGN);
+
+        if bytes == 0 {
+            return Self {
+                ptr: NonNull::dangling(),
+                len: 0,
+                bytes: 0,
+                mapped: false,
+                backing: Backing::Regular,
+                numa_nodes: 1,
+            };
+        }
+
+        let (ptr, bytes, mapped, backing) = sys::map(bytes, large_pages())
+            .map(|(ptr, bytes, backing)| (ptr, bytes, true, backing))
+            .unwrap_or_else(|| (heap_alloc(bytes), bytes, false, Backing::Regular));
+
+        let numa_nodes = if numa_interleave() {
+            sys::interleave(ptr, bytes)
+        } else {
+            1
+        };
+
+        Self {
+            ptr: ptr.cast(),
+            len: 0,
+            bytes,
+            mapped,
+            backing,
+            numa_nodes,
+        }
+    }
+
+    pub fn backing(&self) -> Backing {
+        self.backing
+    }
+
+    /// The number of NUMA nodes the buffer is spread over.
+    pub fn numa_nodes(&self) -> usize {
+        self.numa_nodes
+    }
+
+    /// A description of the placement of the buffer, for `info string`s.
+    pub fn placement(&self) -> String {
+        if self.numa_nodes > 1 {
+            format!(
+                "{}, interleaved over {} NUMA nodes",
+                self.backing, self.numa_nodes
+            )
+        } else {
+            self.backing.to_string()
+        }
+    }
+}
+
+fn heap_layout(bytes: usize) -> Layout {
+    Layout::from_size_align(bytes, PAGE_ALIGN).expect("allocation too large")
+}
+
+fn heap_alloc(bytes: usize) -> NonNull<u8> {
+    let layout = heap_layout(bytes);
+
+    // SAFETY: `bytes` is not zero
+    NonNull::new(unsafe { alloc::alloc(layout) })
+        .unwrap_or_else(|| alloc::handle_alloc_error(layout))
+}
+
+impl<T> Deref for LargeBuffer<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // SAFETY: the first `len` elements are initialised
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for LargeBuffer<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        // SAFETY: the first `len` elements are initialised
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T> Drop for LargeBuffer<T> {
+    fn drop(&mut self) {
+        if self.bytes == 0 {
+            return;
+        }
+
+        unsafe {
+            ptr::drop_in_place(&mut **self);
+
+            if self.mapped {
+                sys::unmap(self.ptr.cast(), self.bytes);
+            } else {
+                alloc::dealloc(self.ptr.as_ptr().cast(), heap_layout(self.bytes));
+            }
+        }
+    }
+}
+
+/// A copy of read-only data, kept in step with the data it is made from
+/// and only made whilst `numa_interleave` is set, so that all threads
+/// read it at the same average speed rather than some from a remote node.
+pub struct Placed<T> {
+    source: *const T,
+    copy: Option<LargeBuffer<T>>,
+}
+
+impl<T> Default for Placed<T> {
+    fn default() -> Self {
+        Self {
+            source: ptr::null(),
+            copy: None,
+        }
+    }
+}
+
+impl<T> Placed<T> {
+    /// The copy of `source`, made again if `source` has changed since the
+    /// last call, or `source` itself whilst NUMA interleaving is disabled.
+    ///
+    /// # Safety
+    /// `T` must be valid to copy bitwise, without any pointers or handles.
+    pub unsafe fn get<'a>(&'a mut self, source: &'a T) -> &'a T {
+        if !numa_interleave() {
+            self.copy = None;
+            return source;
+        }
+
+        if self.copy.is_none() || !ptr::eq(self.source, source) {
+            // drop the old copy first, as networks can be large
+            self.copy = None;
+            self.copy = Some(unsafe { LargeBuffer::copy_of(source) });
+            self.source = source;
+        }
+
+        &self.copy.as_ref().unwrap()[0]
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::{fs, ptr::NonNull};
+
+    use super::{Backing, HUGE_PAGE_SIZE};
+
+    const MPOL_INTERLEAVE: libc::c_int = 3;
+
+    unsafe fn mmap(bytes: usize, flags: libc::c_int) -> Option<NonNull<u8>> {
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                bytes,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
+                -1,
+                0,
+            )
+        };
+
+        if ptr == libc::MAP_FAILED {
+            None
+        } else {
+            NonNull::new(ptr.cast())
+        }
+    }
+
+    /// Map `bytes` of zeroed memory, preferring explicit and then transparent
+    /// huge pages if `huge_pages` is set, returning the size actually mapped.
+    pub fn map(bytes: usize, huge_pages: bool) -> Option<(NonNull<u8>, usize, Backing)> {
+        if !huge_pages {
+            return unsafe { mmap(bytes, 0) }.map(|ptr| (ptr, bytes, Backing::Regular));
+        }
+
+        let rounded = bytes.next_multiple_of(HUGE_PAGE_SIZE);
+
+        if let Some(ptr) = unsafe { mmap(rounded, libc::MAP_HUGETLB) } {
+            return Some((ptr, rounded, Backing::Explicit));
+        }
+
+        let ptr = unsafe { mmap(bytes, 0) }?;
+        let advised = unsafe { libc::madvise(ptr.as_ptr().cast(), bytes, libc::MADV_HUGEPAGE) };
+
+        let backing = if advised == 0 {
+            Backing::Transparent
+        } else {
+            Backing::Regular
+        };
+
+        Some((ptr, bytes, backing))
+    }
+
+    pub unsafe fn unmap(ptr: NonNull<u8>, bytes: usize) {
+        unsafe { libc::munmap(ptr.as_ptr().cast(), bytes) };
+    }
+
+    /// The online NUMA nodes, from a list such as `0-3,6`.
+    fn numa_nodes() -> Vec<usize> {
+        let Ok(online) = fs::read_to_string("/sys/devices/system/node/online") else {
+            return Vec::new();
+        };
+
+        online
+            .trim()
+            .split(',')
+            .filter_map(|range| {
+                let (start, end) = range.split_once('-').unwrap_or((range, range));
+                Some(start.parse().ok()?..=end.parse().ok()?)
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Interleave the pages of untouched memory over the NUMA nodes,
+    /// returning the number of nodes used.
+    pub fn interleave(ptr: NonNull<u8>, bytes: usize) -> usize {
+        let nodes = numa_nodes();
+
+        if nodes.len() < 2 {
+            return 1;
+        }
+
+        let max_node = nodes.iter().max().unwrap() + 1;
+        let mut mask = vec![0u64; max_node.div_ceil(64)];
+        for &node in &nodes {
+            mask[node / 64] |= 1 << (node % 64);
+        }
+
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_mbind,
+                ptr.as_ptr(),
+                bytes,
+                MPOL_INTERLEAVE,
+                mask.as_ptr(),
+                // the kernel ignores the last bit of `maxnode`
+                max_node + 1,
+                0,
+            )
+        };
+
+        if res == 0 {
+            nodes.len()
+        } else {
+            1
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::ptr::NonNull;
+
+    use super::Backing;
+
+    pub fn map(_bytes: usize, _huge_pages: bool) -> Option<(NonNull<u8>, usize, Backing)> {
+        None
+    }
+
+    pub unsafe fn unmap(_ptr: NonNull<u8>, _bytes: usize) {}
+
+    pub fn interleave(_ptr: NonNull<u8>, _bytes: usize) -> usize {
+        1
+    }
+}
@@ -1,8 +1,10 @@
 pub mod common;
+pub mod file;
 pub mod policy;
 pub mod value;
 
 pub use common::Accumulator;
+pub use file::{NetworkError, NetworkFile};
 
 // Choose the file name type based on the feature
 #[cfg(feature = "datagen")]
@@ -16,4 +18,4 @@ pub use policy::PolicyFileDefaultName;
 pub use value::ValueFileDefaultName;
 
 pub use policy::{PolicyNetwork, L1 as POLICY_L1};
-pub use value::ValueNetwork;
+pub use value::{ValueAccumulators, ValueNetwork};
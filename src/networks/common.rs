@@ -1,3 +1,5 @@
+pub mod simd;
+
 use std::ops::{AddAssign, Mul};
 
 #[repr(C)]
@@ -28,57 +30,33 @@ impl<T: AddAssign<T> + Copy + Mul<T, Output = T> + From<i16>, const N: usize> Ac
 
 impl<const N: usize> Accumulator<i16, N> {
     pub fn add_multi(&mut self, adds: &[usize], weights: &[Self]) {
-        const REGS: usize = 8;
-        const PER: usize = REGS * 16;
-
-        let mut regs = [0i16; PER];
-
-        for i in 0..N / PER {
-            let offset = PER * i;
-
-            for (j, reg) in regs.iter_mut().enumerate() {
-                *reg = self.0[offset + j];
-            }
-
-            for &add in adds {
-                let this_weight = &weights[add];
-
-                for (j, reg) in regs.iter_mut().enumerate() {
-                    *reg += this_weight.0[offset + j];
-                }
-            }
-
-            for (j, reg) in regs.iter().enumerate() {
-                self.0[offset + j] = *reg;
-            }
-        }
+        simd::add_sub_i16(simd::level(), &mut self.0, 0..N, adds, &[], weights);
     }
-}
 
-impl<const N: usize> Accumulator<i16, N> {
     pub fn add_multi_i8(&mut self, adds: &[usize], weights: &[Accumulator<i8, N>]) {
-        const REGS: usize = 8;
-        const PER: usize = REGS * 16;
-
-        let mut regs = [0i16; PER];
-
-        for i in 0..N / PER {
-            let offset = PER * i;
-
-            for (j, reg) in regs.iter_mut().enumerate() {
-                *reg = self.0[offset + j];
-            }
-
-            for &add in adds {
-                let this_weight = &weights[add];
+        self.add_sub_i8(adds, &[], weights);
+    }
 
-                for (j, reg) in regs.iter_mut().enumerate() {
-                    *reg += i16::from(this_weight.0[offset + j]);
-                }
-            }
+    /// Add the rows of `weights` for features `adds` and take away those for
+    /// `subs`, to update an accumulator from a change of position.
+    pub fn add_sub_i8(&mut self, adds: &[usize], subs: &[usize], weights: &[Accumulator<i8, N>]) {
+        simd::add_sub_i8(simd::level(), &mut self.0, 0..N, adds, subs, weights);
+    }
 
-            for (j, reg) in regs.iter().enumerate() {
-                self.0[offset + j] = *reg;
+    /// `add_multi_i8` for several accumulators at once, working through
+    /// the weights block by block so that rows of features shared between
+    /// positions are still in cache when the next position needs them.
+    pub fn add_multi_i8_batch(
+        accs: &mut [Self],
+        adds: &[&[usize]],
+        weights: &[Accumulator<i8, N>],
+    ) {
+        let level = simd::level();
+
+        for offset in (0..N).step_by(simd::BLOCK) {
+            for (acc, adds) in accs.iter_mut().zip(adds) {
+                let range = offset..offset + simd::BLOCK;
+                simd::add_sub_i8(level, &mut acc.0, range, adds, &[], weights);
             }
         }
     }
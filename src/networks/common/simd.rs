@@ -0,0 +1,621 @@
+//! Explicitly vectorised versions of the integer network kernels, chosen at
+//! runtime from the features of the CPU so that a portable binary does not
+//! rely on `-Ctarget-cpu=native` to be fast. Every kernel has a scalar
+//! fallback, and all of them give exactly the same results as it.
+//!
+//! The kernels take a `Supported` level, so the CPU is only checked when the
+//! level is chosen, and the lengths of their inputs are fixed by their types,
+//! leaving nothing but debug assertions to check on each call.
+
+use std::{ops::Range, sync::OnceLock};
+
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use super::Accumulator;
+
+/// Accumulators are updated in blocks of this many elements,
+/// so that the block stays in registers whilst rows are added.
+pub const BLOCK: usize = 128;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Level {
+    Scalar,
+    Avx2,
+    /// AVX-512 with the BW and VNNI extensions.
+    Avx512,
+    Neon,
+}
+
+impl Level {
+    pub const ALL: [Self; 4] = [Self::Scalar, Self::Avx2, Self::Avx512, Self::Neon];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Scalar => "scalar",
+            Self::Avx2 => "avx2",
+            Self::Avx512 => "avx512",
+            Self::Neon => "neon",
+        }
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => {
+                is_x86_feature_detected!("avx512f")
+                    && is_x86_feature_detected!("avx512bw")
+                    && is_x86_feature_detected!("avx512vnni")
+            }
+            #[cfg(target_arch = "aarch64")]
+            Self::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// This level, if it is supported by this CPU.
+    pub fn supported(self) -> Option<Supported> {
+        self.is_supported().then_some(Supported(self))
+    }
+
+    /// The fastest level supported by this CPU.
+    pub fn detect() -> Supported {
+        [Self::Avx512, Self::Avx2, Self::Neon]
+            .into_iter()
+            .find_map(Self::supported)
+            .unwrap_or(Supported::SCALAR)
+    }
+}
+
+/// A level which has been checked to be supported by this CPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Supported(Level);
+
+impl Supported {
+    /// The scalar fallback, which every CPU supports.
+    pub const SCALAR: Self = Self(Level::Scalar);
+
+    pub fn level(self) -> Level {
+        self.0
+    }
+}
+
+/// The level used by the networks, detected on first use.
+pub fn level() -> Supported {
+    static LEVEL: OnceLock<Supported> = OnceLock::new();
+    *LEVEL.get_or_init(Level::detect)
+}
+
+/// Add the rows `adds` of `weights` to `acc` and subtract the rows `subs`,
+/// only touching the elements in `range`, which must be made of whole blocks.
+/// Each block is sliced from `acc` and the rows, so a bad range or row panics.
+pub fn add_sub_i8<const N: usize>(
+    level: Supported,
+    acc: &mut [i16; N],
+    range: Range<usize>,
+    adds: &[usize],
+    subs: &[usize],
+    weights: &[Accumulator<i8, N>],
+) {
+    debug_assert!(range.start.is_multiple_of(BLOCK) && range.end.is_multiple_of(BLOCK));
+    debug_assert!(range.end <= N);
+    debug_assert!(adds.iter().chain(subs).all(|&row| row < weights.len()));
+
+    match level.0 {
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx2 => unsafe { add_sub_i8_avx2(acc, range, adds, subs, weights) },
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx512 => unsafe { add_sub_i8_avx512(acc, range, adds, subs, weights) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => unsafe { add_sub_i8_neon(acc, range, adds, subs, weights) },
+        _ => add_sub_i8_scalar(acc, range, adds, subs, weights),
+    }
+}
+
+/// As `add_sub_i8`, for `i16` weights.
+pub fn add_sub_i16<const N: usize>(
+    level: Supported,
+    acc: &mut [i16; N],
+    range: Range<usize>,
+    adds: &[usize],
+    subs: &[usize],
+    weights: &[Accumulator<i16, N>],
+) {
+    debug_assert!(range.start.is_multiple_of(BLOCK) && range.end.is_multiple_of(BLOCK));
+    debug_assert!(range.end <= N);
+    debug_assert!(adds.iter().chain(subs).all(|&row| row < weights.len()));
+
+    match level.0 {
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx2 => unsafe { add_sub_i16_avx2(acc, range, adds, subs, weights) },
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx512 => unsafe { add_sub_i16_avx512(acc, range, adds, subs, weights) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => unsafe { add_sub_i16_neon(acc, range, adds, subs, weights) },
+        _ => add_sub_i16_scalar(acc, range, adds, subs, weights),
+    }
+}
+
+/// `sum(a[i] * b[i])`, wrapping on overflow.
+pub fn dot_i8_i16<const N: usize>(level: Supported, a: &[i8; N], b: &[i16; N]) -> i32 {
+    match level.0 {
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx2 => unsafe { dot_i8_i16_avx2(a, b) },
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx512 => unsafe { dot_i8_i16_avx512(a, b) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => unsafe { dot_i8_i16_neon(a, b) },
+        _ => dot_i8_i16_scalar(a, b),
+    }
+}
+
+/// `sum(a[i] * b[i])`, wrapping on overflow.
+pub fn dot_i16_i16<const N: usize>(level: Supported, a: &[i16; N], b: &[i16; N]) -> i32 {
+    match level.0 {
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx2 => unsafe { dot_i16_i16_avx2(a, b) },
+        #[cfg(target_arch = "x86_64")]
+        Level::Avx512 => unsafe { dot_i16_i16_avx512(a, b) },
+        #[cfg(target_arch = "aarch64")]
+        Level::Neon => unsafe { dot_i16_i16_neon(a, b) },
+        _ => dot_i16_i16_scalar(a, b),
+    }
+}
+
+fn add_sub_i8_scalar<const N: usize>(
+    acc: &mut [i16; N],
+    range: Range<usize>,
+    adds: &[usize],
+    subs: &[usize],
+    weights: &[Accumulator<i8, N>],
+) {
+    let mut regs = [0i16; BLOCK];
+
+    for offset in range.step_by(BLOCK) {
+        regs.copy_from_slice(&acc[offset..offset + BLOCK]);
+
+        for &add in adds {
+            let this_weight = &weights[add].0[offset..offset + BLOCK];
+
+            for (reg, &w) in regs.iter_mut().zip(this_weight) {
+                *reg = reg.wrapping_add(i16::from(w));
+            }
+        }
+
+        for &sub in subs {
+            let this_weight = &weights[sub].0[offset..offset + BLOCK];
+
+            for (reg, &w) in regs.iter_mut().zip(this_weight) {
+                *reg = reg.wrapping_sub(i16::from(w));
+            }
+        }
+
+        acc[offset..offset + BLOCK].copy_from_slice(&regs);
+    }
+}
+
+fn add_sub_i16_scalar<const N: usize>(
+    acc: &mut [i16; N],
+    range: Range<usize>,
+    adds: &[usize],
+    subs: &[usize],
+    weights: &[Accumulator<i16, N>],
+) {
+    let mut regs = [0i16; BLOCK];
+
+    for offset in range.step_by(BLOCK) {
+        regs.copy_from_slice(&acc[offset..offset + BLOCK]);
+
+        for &add in adds {
+            let this_weight = &weights[add].0[offset..offset + BLOCK];
+
+            for (reg, &w) in regs.iter_mut().zip(this_weight) {
+                *reg = reg.wrapping_add(w);
+            }
+        }
+
+        for &sub in subs {
+            let this_weight = &weights[sub].0[offset..offset + BLOCK];
+
+            for (reg, &w) in regs.iter_mut().zip(this_weight) {
+                *reg = reg.wrapping_sub(w);
+            }
+        }
+
+        acc[offset..offset + BLOCK].copy_from_slice(&regs);
+    }
+}
+
+fn dot_i8_i16_scalar(a: &[i8], b: &[i16]) -> i32 {
+    a.iter().zip(b).fold(0i32, |res, (&w, &v)| {
+        res.wrapping_add(i32::from(w) * i32::from(v))
+    })
+}
+
+fn dot_i16_i16_scalar(a: &[i16], b: &[i16]) -> i32 {
+    a.iter().zip(b).fold(0i32, |res, (&w, &v)| {
+        res.wrapping_add(i32::from(w) * i32::from(v))
+    })
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn add_sub_i8_avx2<const N: usize>(
+    acc: &mut [i16; N],
+    range: Range<usize>,
+    adds: &[usize],
+    subs: &[usize],
+    weights: &[Accumulator<i8, N>],
+) {
+    const LANES: usize = 16;
+    let mut regs = [_mm256_setzero_si256(); BLOCK / LANES];
+
+    for offset in range.step_by(BLOCK) {
+        let ptr = acc[offset..offset + BLOCK].as_mut_ptr();
+
+        for (j, reg) in regs.iter_mut().enumerate() {
+            *reg = _mm256_loadu_si256(ptr.add(LANES * j).cast());
+        }
+
+        for &add in adds {
+            let w = weights[add].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                let w = _mm256_cvtepi8_epi16(_mm_loadu_si128(w.add(LANES * j).cast()));
+                *reg = _mm256_add_epi16(*reg, w);
+            }
+        }
+
+        for &sub in subs {
+            let w = weights[sub].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                let w = _mm256_cvtepi8_epi16(_mm_loadu_si128(w.add(LANES * j).cast()));
+                *reg = _mm256_sub_epi16(*reg, w);
+            }
+        }
+
+        for (j, reg) in regs.iter().enumerate() {
+            _mm256_storeu_si256(ptr.add(LANES * j).cast(), *reg);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn add_sub_i16_avx2<const N: usize>(
+    acc: &mut [i16; N],
+    range: Range<usize>,
+    adds: &[usize],
+    subs: &[usize],
+    weights: &[Accumulator<i16, N>],
+) {
+    const LANES: usize = 16;
+    let mut regs = [_mm256_setzero_si256(); BLOCK / LANES];
+
+    for offset in range.step_by(BLOCK) {
+        let ptr = acc[offset..offset + BLOCK].as_mut_ptr();
+
+        for (j, reg) in regs.iter_mut().enumerate() {
+            *reg = _mm256_loadu_si256(ptr.add(LANES * j).cast());
+        }
+
+        for &add in adds {
+            let w = weights[add].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                let w = _mm256_loadu_si256(w.add(LANES * j).cast());
+                *reg = _mm256_add_epi16(*reg, w);
+            }
+        }
+
+        for &sub in subs {
+            let w = weights[sub].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                let w = _mm256_loadu_si256(w.add(LANES * j).cast());
+                *reg = _mm256_sub_epi16(*reg, w);
+            }
+        }
+
+        for (j, reg) in regs.iter().enumerate() {
+            _mm256_storeu_si256(ptr.add(LANES * j).cast(), *reg);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn hsum_avx2(sum: __m256i) -> i32 {
+    let sum = _mm_add_epi32(
+        _mm256_castsi256_si128(sum),
+        _mm256_extracti128_si256(sum, 1),
+    );
+    let sum = _mm_add_epi32(sum, _mm_shuffle_epi32(sum, 0b01_00_11_10));
+    let sum = _mm_add_epi32(sum, _mm_shuffle_epi32(sum, 0b10_11_00_01));
+    _mm_cvtsi128_si32(sum)
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn dot_i8_i16_avx2(a: &[i8], b: &[i16]) -> i32 {
+    debug_assert_eq!(a.len(), b.len());
+    const LANES: usize = 16;
+    let chunks = a.len() / LANES;
+    let mut sum = _mm256_setzero_si256();
+
+    for i in 0..chunks {
+        let w = _mm256_cvtepi8_epi16(_mm_loadu_si128(a.as_ptr().add(LANES * i).cast()));
+        let v = _mm256_loadu_si256(b.as_ptr().add(LANES * i).cast());
+        sum = _mm256_add_epi32(sum, _mm256_madd_epi16(w, v));
+    }
+
+    let tail = LANES * chunks;
+    hsum_avx2(sum).wrapping_add(dot_i8_i16_scalar(&a[tail..], &b[tail..]))
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn dot_i16_i16_avx2(a: &[i16], b: &[i16]) -> i32 {
+    debug_assert_eq!(a.len(), b.len());
+    const LANES: usize = 16;
+    let chunks = a.len() / LANES;
+    let mut sum = _mm256_setzero_si256();
+
+    for i in 0..chunks {
+        let w = _mm256_loadu_si256(a.as_ptr().add(LANES * i).cast());
+        let v = _mm256_loadu_si256(b.as_ptr().add(LANES * i).cast());
+        sum = _mm256_add_epi32(sum, _mm256_madd_epi16(w, v));
+    }
+
+    let tail = LANES * chunks;
+    hsum_avx2(sum).wrapping_add(dot_i16_i16_scalar(&a[tail..], &b[tail..]))
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn add_sub_i8_avx512<const N: usize>(
+    acc: &mut [i16; N],
+    range: Range<usize>,
+    adds: &[usize],
+    subs: &[usize],
+    weights: &[Accumulator<i8, N>],
+) {
+    const LANES: usize = 32;
+    let mut regs = [_mm512_setzero_si512(); BLOCK / LANES];
+
+    for offset in range.step_by(BLOCK) {
+        let ptr = acc[offset..offset + BLOCK].as_mut_ptr();
+
+        for (j, reg) in regs.iter_mut().enumerate() {
+            *reg = _mm512_loadu_si512(ptr.add(LANES * j).cast());
+        }
+
+        for &add in adds {
+            let w = weights[add].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                let w = _mm512_cvtepi8_epi16(_mm256_loadu_si256(w.add(LANES * j).cast()));
+                *reg = _mm512_add_epi16(*reg, w);
+            }
+        }
+
+        for &sub in subs {
+            let w = weights[sub].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                let w = _mm512_cvtepi8_epi16(_mm256_loadu_si256(w.add(LANES * j).cast()));
+                *reg = _mm512_sub_epi16(*reg, w);
+            }
+        }
+
+        for (j, reg) in regs.iter().enumerate() {
+            _mm512_storeu_si512(ptr.add(LANES * j).cast(), *reg);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f,avx512bw")]
+unsafe fn add_sub_i16_avx512<const N: usize>(
+    acc: &mut [i16; N],
+    range: Range<usize>,
+    adds: &[usize],
+    subs: &[usize],
+    weights: &[Accumulator<i16, N>],
+) {
+    const LANES: usize = 32;
+    let mut regs = [_mm512_setzero_si512(); BLOCK / LANES];
+
+    for offset in range.step_by(BLOCK) {
+        let ptr = acc[offset..offset + BLOCK].as_mut_ptr();
+
+        for (j, reg) in regs.iter_mut().enumerate() {
+            *reg = _mm512_loadu_si512(ptr.add(LANES * j).cast());
+        }
+
+        for &add in adds {
+            let w = weights[add].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                let w = _mm512_loadu_si512(w.add(LANES * j).cast());
+                *reg = _mm512_add_epi16(*reg, w);
+            }
+        }
+
+        for &sub in subs {
+            let w = weights[sub].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                let w = _mm512_loadu_si512(w.add(LANES * j).cast());
+                *reg = _mm512_sub_epi16(*reg, w);
+            }
+        }
+
+        for (j, reg) in regs.iter().enumerate() {
+            _mm512_storeu_si512(ptr.add(LANES * j).cast(), *reg);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f,avx512bw,avx512vnni")]
+unsafe fn dot_i8_i16_avx512(a: &[i8], b: &[i16]) -> i32 {
+    debug_assert_eq!(a.len(), b.len());
+    const LANES: usize = 32;
+    let chunks = a.len() / LANES;
+    let mut sum = _mm512_setzero_si512();
+
+    for i in 0..chunks {
+        let w = _mm512_cvtepi8_epi16(_mm256_loadu_si256(a.as_ptr().add(LANES * i).cast()));
+        let v = _mm512_loadu_si512(b.as_ptr().add(LANES * i).cast());
+        sum = _mm512_dpwssd_epi32(sum, w, v);
+    }
+
+    let tail = LANES * chunks;
+    _mm512_reduce_add_epi32(sum).wrapping_add(dot_i8_i16_scalar(&a[tail..], &b[tail..]))
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f,avx512bw,avx512vnni")]
+unsafe fn dot_i16_i16_avx512(a: &[i16], b: &[i16]) -> i32 {
+    debug_assert_eq!(a.len(), b.len());
+    const LANES: usize = 32;
+    let chunks = a.len() / LANES;
+    let mut sum = _mm512_setzero_si512();
+
+    for i in 0..chunks {
+        let w = _mm512_loadu_si512(a.as_ptr().add(LANES * i).cast());
+        let v = _mm512_loadu_si512(b.as_ptr().add(LANES * i).cast());
+        sum = _mm512_dpwssd_epi32(sum, w, v);
+    }
+
+    let tail = LANES * chunks;
+    _mm512_reduce_add_epi32(sum).wrapping_add(dot_i16_i16_scalar(&a[tail..], &b[tail..]))
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn add_sub_i8_neon<const N: usize>(
+    acc: &mut [i16; N],
+    range: Range<usize>,
+    adds: &[usize],
+    subs: &[usize],
+    weights: &[Accumulator<i8, N>],
+) {
+    const LANES: usize = 8;
+    let mut regs = [vdupq_n_s16(0); BLOCK / LANES];
+
+    for offset in range.step_by(BLOCK) {
+        let ptr = acc[offset..offset + BLOCK].as_mut_ptr();
+
+        for (j, reg) in regs.iter_mut().enumerate() {
+            *reg = vld1q_s16(ptr.add(LANES * j));
+        }
+
+        for &add in adds {
+            let w = weights[add].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                *reg = vaddq_s16(*reg, vmovl_s8(vld1_s8(w.add(LANES * j))));
+            }
+        }
+
+        for &sub in subs {
+            let w = weights[sub].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                *reg = vsubq_s16(*reg, vmovl_s8(vld1_s8(w.add(LANES * j))));
+            }
+        }
+
+        for (j, reg) in regs.iter().enumerate() {
+            vst1q_s16(ptr.add(LANES * j), *reg);
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn add_sub_i16_neon<const N: usize>(
+    acc: &mut [i16; N],
+    range: Range<usize>,
+    adds: &[usize],
+    subs: &[usize],
+    weights: &[Accumulator<i16, N>],
+) {
+    const LANES: usize = 8;
+    let mut regs = [vdupq_n_s16(0); BLOCK / LANES];
+
+    for offset in range.step_by(BLOCK) {
+        let ptr = acc[offset..offset + BLOCK].as_mut_ptr();
+
+        for (j, reg) in regs.iter_mut().enumerate() {
+            *reg = vld1q_s16(ptr.add(LANES * j));
+        }
+
+        for &add in adds {
+            let w = weights[add].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                *reg = vaddq_s16(*reg, vld1q_s16(w.add(LANES * j)));
+            }
+        }
+
+        for &sub in subs {
+            let w = weights[sub].0[offset..offset + BLOCK].as_ptr();
+
+            for (j, reg) in regs.iter_mut().enumerate() {
+                *reg = vsubq_s16(*reg, vld1q_s16(w.add(LANES * j)));
+            }
+        }
+
+        for (j, reg) in regs.iter().enumerate() {
+            vst1q_s16(ptr.add(LANES * j), *reg);
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn dot_i8_i16_neon(a: &[i8], b: &[i16]) -> i32 {
+    debug_assert_eq!(a.len(), b.len());
+    const LANES: usize = 8;
+    let chunks = a.len() / LANES;
+    let mut sum = vdupq_n_s32(0);
+
+    for i in 0..chunks {
+        let w = vmovl_s8(vld1_s8(a.as_ptr().add(LANES * i)));
+        let v = vld1q_s16(b.as_ptr().add(LANES * i));
+        sum = vmlal_s16(sum, vget_low_s16(w), vget_low_s16(v));
+        sum = vmlal_high_s16(sum, w, v);
+    }
+
+    let tail = LANES * chunks;
+    vaddvq_s32(sum).wrapping_add(dot_i8_i16_scalar(&a[tail..], &b[tail..]))
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn dot_i16_i16_neon(a: &[i16], b: &[i16]) -> i32 {
+    debug_assert_eq!(a.len(), b.len());
+    const LANES: usize = 8;
+    let chunks = a.len() / LANES;
+    let mut sum = vdupq_n_s32(0);
+
+    for i in 0..chunks {
+        let w = vld1q_s16(a.as_ptr().add(LANES * i));
+        let v = vld1q_s16(b.as_ptr().add(LANES * i));
+        sum = vmlal_s16(sum, vget_low_s16(w), vget_low_s16(v));
+        sum = vmlal_high_s16(sum, w, v);
+    }
+
+    let tail = LANES * chunks;
+    vaddvq_s32(sum).wrapping_add(dot_i16_i16_scalar(&a[tail..], &b[tail..]))
+}
@@ -0,0 +1,302 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Write},
+    marker::PhantomData,
+};
+
+use memmap2::Mmap;
+use sha2::{Digest, Sha256};
+
+pub const MAGIC: [u8; 8] = *b"MONTYNET";
+pub const VERSION: u32 = 1;
+
+/// The header is padded to this size, so that the weights
+/// following it stay aligned for the network structs.
+pub const HEADER_SIZE: usize = 64;
+
+/// Describes the layout of a network, as recorded in the header of its file.
+pub trait NetworkArch: Sized {
+    const NAME: &'static str;
+    const ARCH: u32;
+    /// Input size, then the size of each layer, padded with zeroes.
+    const LAYERS: [u32; 4];
+    const QA: u32;
+    const QB: u32;
+}
+
+#[derive(Debug)]
+pub enum NetworkError {
+    Io(io::Error),
+    UnsupportedVersion(u32),
+    WrongArch {
+        expected: u32,
+        found: u32,
+    },
+    WrongLayers {
+        expected: [u32; 4],
+        found: [u32; 4],
+    },
+    WrongQuantisation {
+        expected: (u32, u32),
+        found: (u32, u32),
+    },
+    WrongSize {
+        expected: usize,
+        found: usize,
+    },
+    BadChecksum,
+    Misaligned,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "unsupported network version {version}, expected {VERSION}"
+                )
+            }
+            Self::WrongArch { expected, found } => {
+                write!(
+                    f,
+                    "wrong architecture {found:#010x}, expected {expected:#010x}"
+                )
+            }
+            Self::WrongLayers { expected, found } => {
+                write!(f, "wrong layer sizes {found:?}, expected {expected:?}")
+            }
+            Self::WrongQuantisation { expected, found } => write!(
+                f,
+                "wrong quantisation QA={} QB={}, expected QA={} QB={}",
+                found.0, found.1, expected.0, expected.1
+            ),
+            Self::WrongSize { expected, found } => {
+                write!(f, "weights are {found} bytes, expected {expected}")
+            }
+            Self::BadChecksum => write!(f, "checksum mismatch, the file is corrupt"),
+            Self::Misaligned => write!(f, "weights are not aligned in memory"),
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+impl From<io::Error> for NetworkError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// The first 12 hex digits of the SHA-256 of `data`, as used
+/// in the `nn-<sha>.network` names of released networks.
+pub fn sha_prefix(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))[..12].to_string()
+}
+
+/// FNV-1a over little endian 64-bit words, with the last
+/// partial word zero padded.
+pub fn checksum(data: &[u8]) -> u64 {
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+
+    for chunk in data.chunks(8) {
+        let mut word = [0; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        hash ^= u64::from_le_bytes(word);
+        hash = hash.wrapping_mul(PRIME);
+    }
+
+    hash
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetworkHeader {
+    pub version: u32,
+    pub arch: u32,
+    pub layers: [u32; 4],
+    pub qa: u32,
+    pub qb: u32,
+    pub payload_len: u64,
+    pub checksum: u64,
+}
+
+impl NetworkHeader {
+    pub fn new<T: NetworkArch>(payload: &[u8]) -> Self {
+        Self {
+            version: VERSION,
+            arch: T::ARCH,
+            layers: T::LAYERS,
+            qa: T::QA,
+            qb: T::QB,
+            payload_len: payload.len() as u64,
+            checksum: checksum(payload),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let mut bytes = [0; HEADER_SIZE];
+
+        let fields = [self.version, self.arch]
+            .into_iter()
+            .chain(self.layers)
+            .chain([self.qa, self.qb]);
+
+        bytes[..8].copy_from_slice(&MAGIC);
+        for (i, field) in fields.enumerate() {
+            bytes[8 + 4 * i..12 + 4 * i].copy_from_slice(&field.to_le_bytes());
+        }
+        bytes[40..48].copy_from_slice(&self.payload_len.to_le_bytes());
+        bytes[48..56].copy_from_slice(&self.checksum.to_le_bytes());
+
+        bytes
+    }
+
+    /// The header at the start of `bytes`, if it has one.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_SIZE || bytes[..8] != MAGIC {
+            return None;
+        }
+
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(bytes[i..i + 8].try_into().unwrap());
+
+        Some(Self {
+            version: u32_at(8),
+            arch: u32_at(12),
+            layers: [u32_at(16), u32_at(20), u32_at(24), u32_at(28)],
+            qa: u32_at(32),
+            qb: u32_at(36),
+            payload_len: u64_at(40),
+            checksum: u64_at(48),
+        })
+    }
+
+    /// Check the header describes a network of type `T`, and that it matches `payload`.
+    pub fn validate<T: NetworkArch>(&self, payload: &[u8]) -> Result<(), NetworkError> {
+        if self.version != VERSION {
+            return Err(NetworkError::UnsupportedVersion(self.version));
+        }
+
+        if self.arch != T::ARCH {
+            return Err(NetworkError::WrongArch {
+                expected: T::ARCH,
+                found: self.arch,
+            });
+        }
+
+        if self.layers != T::LAYERS {
+            return Err(NetworkError::WrongLayers {
+                expected: T::LAYERS,
+                found: self.layers,
+            });
+        }
+
+        if (self.qa, self.qb) != (T::QA, T::QB) {
+            return Err(NetworkError::WrongQuantisation {
+                expected: (T::QA, T::QB),
+                found: (self.qa, self.qb),
+            });
+        }
+
+        if self.payload_len != payload.len() as u64 || payload.len() != size_of::<T>() {
+            return Err(NetworkError::WrongSize {
+                expected: size_of::<T>(),
+                found: payload.len(),
+            });
+        }
+
+        if self.checksum != checksum(payload) {
+            return Err(NetworkError::BadChecksum);
+        }
+
+        Ok(())
+    }
+}
+
+/// Write the weights of a `T` to `out`, preceded by their header.
+pub fn write_network<T: NetworkArch>(out: &mut impl Write, payload: &[u8]) -> io::Result<()> {
+    out.write_all(&NetworkHeader::new::<T>(payload).to_bytes())?;
+    out.write_all(payload)
+}
+
+/// A memory mapped network file, checked to hold a valid `T`.
+///
+/// Files without a header, as written before headers were added,
+/// are accepted if they are exactly the size of a `T`.
+pub struct NetworkFile<T> {
+    mmap: Mmap,
+    offset: usize,
+    header: Option<NetworkHeader>,
+    _network: PhantomData<T>,
+}
+
+impl<T: NetworkArch> NetworkFile<T> {
+    pub fn open(path: &str) -> Result<Self, NetworkError> {
+        let file = File::open(path)?;
+        // SAFETY: the file is only ever read
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        Self::from_mmap(mmap)
+    }
+
+    pub fn from_mmap(mmap: Mmap) -> Result<Self, NetworkError> {
+        let header = NetworkHeader::parse(&mmap);
+
+        let offset = if let Some(header) = header {
+            header.validate::<T>(&mmap[HEADER_SIZE..])?;
+            HEADER_SIZE
+        } else if mmap.len() != size_of::<T>() {
+            return Err(NetworkError::WrongSize {
+                expected: size_of::<T>(),
+                found: mmap.len(),
+            });
+        } else {
+            0
+        };
+
+        if !(mmap.as_ptr() as usize + offset).is_multiple_of(align_of::<T>()) {
+            return Err(NetworkError::Misaligned);
+        }
+
+        // only a hint, as huge pages for file mappings depend on the filesystem
+        #[cfg(target_os = "linux")]
+        if crate::memory::large_pages() {
+            let _ = mmap.advise(memmap2::Advice::HugePage);
+        }
+
+        Ok(Self {
+            mmap,
+            offset,
+            header,
+            _network: PhantomData,
+        })
+    }
+
+    /// `open`, printing the error and exiting if the file is not a valid network.
+    pub fn open_or_exit(path: &str) -> Self {
+        Self::open(path).unwrap_or_else(|err| {
+            eprintln!("failed to load {} network {path}: {err}", T::NAME);
+            std::process::exit(1);
+        })
+    }
+
+    pub fn header(&self) -> Option<&NetworkHeader> {
+        self.header.as_ref()
+    }
+
+    /// The `sha_prefix` of the whole file.
+    pub fn sha_prefix(&self) -> String {
+        sha_prefix(&self.mmap)
+    }
+
+    pub fn get(&self) -> &T {
+        // SAFETY: the size and alignment have been checked, and the
+        // networks are made only of integers and floats, so any bytes
+        // are a valid network
+        unsafe { &*self.mmap.as_ptr().add(self.offset).cast() }
+    }
+}
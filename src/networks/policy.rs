@@ -4,7 +4,10 @@ pub mod see;
 
 use montyformat::chess::{Move, Position};
 
-use super::common::{Accumulator, Layer, TransposedLayer};
+use super::{
+    common::{simd, Accumulator, Layer, TransposedLayer},
+    file::NetworkArch,
+};
 
 // DO NOT MOVE
 #[allow(non_upper_case_globals, dead_code)]
@@ -33,14 +36,46 @@ pub struct PolicyNetwork {
     l2: TransposedLayer<i8, { L1 / 2 }, { outputs::NUM_MOVES_INDICES }>,
 }
 
+impl NetworkArch for PolicyNetwork {
+    const NAME: &'static str = "policy";
+    const ARCH: u32 = u32::from_le_bytes(*b"POL1");
+    const LAYERS: [u32; 4] = [
+        INPUT_SIZE as u32,
+        L1 as u32,
+        outputs::NUM_MOVES_INDICES as u32,
+        0,
+    ];
+    const QA: u32 = QA as u32;
+    const QB: u32 = QB as u32;
+}
+
 impl PolicyNetwork {
     pub fn hl(&self, pos: &Position) -> Accumulator<i16, { L1 / 2 }> {
-        let mut l1 = Accumulator([0; L1]);
+        let mut l1 = self.l1_biases();
 
-        for (r, &b) in l1.0.iter_mut().zip(self.l1.biases.0.iter()) {
-            *r = i16::from(b);
-        }
+        let (feats, count) = Self::features(pos);
+        l1.add_multi_i8(&feats[..count], &self.l1.weights);
+
+        Self::activate(&l1)
+    }
+
+    /// `hl` for several positions, sharing the first layer's weights
+    /// between them. Gives exactly the same results as `hl`.
+    pub fn hl_batch(&self, positions: &[Position]) -> Vec<Accumulator<i16, { L1 / 2 }>> {
+        let inputs = positions.iter().map(Self::features).collect::<Vec<_>>();
+
+        let adds = inputs
+            .iter()
+            .map(|(feats, count)| &feats[..*count])
+            .collect::<Vec<_>>();
+
+        let mut l1s = vec![self.l1_biases(); positions.len()];
+        Accumulator::add_multi_i8_batch(&mut l1s, &adds, &self.l1.weights);
+
+        l1s.iter().map(Self::activate).collect()
+    }
 
+    fn features(pos: &Position) -> ([usize; 256], usize) {
         let mut feats = [0usize; 256];
         let mut count = 0;
         inputs::map_features(pos, |feat| {
@@ -48,8 +83,20 @@ impl PolicyNetwork {
             count += 1;
         });
 
-        l1.add_multi_i8(&feats[..count], &self.l1.weights);
+        (feats, count)
+    }
 
+    fn l1_biases(&self) -> Accumulator<i16, L1> {
+        let mut l1 = Accumulator([0; L1]);
+
+        for (r, &b) in l1.0.iter_mut().zip(self.l1.biases.0.iter()) {
+            *r = i16::from(b);
+        }
+
+        l1
+    }
+
+    fn activate(l1: &Accumulator<i16, L1>) -> Accumulator<i16, { L1 / 2 }> {
         let mut res = Accumulator([0; L1 / 2]);
 
         for (elem, (&i, &j)) in res
@@ -69,11 +116,7 @@ impl PolicyNetwork {
         let idx = outputs::map_move_to_index(pos, *mov);
         let weights = &self.l2.weights[idx];
 
-        let mut res = 0;
-
-        for (&w, &v) in weights.0.iter().zip(hl.0.iter()) {
-            res += i32::from(w) * i32::from(v);
-        }
+        let res = simd::dot_i8_i16(simd::level(), &weights.0, &hl.0);
 
         (res as f32 / f32::from(QA * FACTOR) + f32::from(self.l2.biases.0[idx])) / f32::from(QB)
     }
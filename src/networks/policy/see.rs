@@ -192,7 +192,7 @@ pub fn greater_or_equal_to(pos: &Position, mov: &Move, threshold: i32) -> bool {
     checkers &= pieces_after[side];
 
     let opp_in_check = checkers != 0;
-    let double_check = checkers & (checkers - 1) != 0;
+    let double_check = checkers & checkers.wrapping_sub(1) != 0;
     let checker_on_to = (checkers & to_bb) != 0;
 
     let mut stm = side ^ 1;
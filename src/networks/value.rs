@@ -3,7 +3,10 @@ pub mod threats;
 
 use montyformat::chess::Position;
 
-use super::common::{Accumulator, Layer, SCReLU, TransposedLayer};
+use super::{
+    common::{simd, Accumulator, Layer, SCReLU, TransposedLayer},
+    file::NetworkArch,
+};
 
 // DO NOT MOVE
 #[allow(non_upper_case_globals, dead_code)]
@@ -27,26 +30,277 @@ pub struct ValueNetwork {
     l4: Layer<f32, 128, 3>,
 }
 
+impl NetworkArch for ValueNetwork {
+    const NAME: &'static str = "value";
+    const ARCH: u32 = u32::from_le_bytes(*b"VAL1");
+    const LAYERS: [u32; 4] = [threats::TOTAL as u32, L1 as u32, 16, 128];
+    const QA: u32 = QA as u32;
+    const QB: u32 = QB as u32;
+}
+
+/// How many plies back along the current line an accumulator is looked for
+/// to update from, before a full refresh is used instead.
+const MAX_UPDATES: usize = 8;
+
+/// The first layer of the value network from one side's point of view.
+#[derive(Clone, Copy)]
+struct ValueAccumulator {
+    l1: Accumulator<i16, L1>,
+    pst: Accumulator<f32, 3>,
+    /// The number of features active.
+    count: usize,
+}
+
+/// A position on the line being searched, with the accumulators that
+/// have been computed for it so far.
+#[derive(Default)]
+struct Ply {
+    bbs: [u64; 8],
+    stm: usize,
+    accs: [Option<Box<ValueAccumulator>>; 2],
+    valid: [bool; 2],
+}
+
+/// The value network accumulators for each position of the line from the
+/// root to the current node, filled in as the search descends. A position
+/// is evaluated by updating the accumulator of the nearest position above
+/// it with the features changed by each move since, see `eval_incremental`.
+///
+/// Accumulators are kept between lines for as long as their position is
+/// unchanged, so the shared start of consecutive lines is reused.
+#[derive(Default)]
+pub struct ValueAccumulators {
+    plies: Vec<Ply>,
+    len: usize,
+    old: Vec<usize>,
+    new: Vec<usize>,
+    adds: Vec<usize>,
+    subs: Vec<usize>,
+}
+
+impl ValueAccumulators {
+    /// Set the position `ply` plies below the root, discarding any below it.
+    pub fn push(&mut self, ply: usize, board: &Position) {
+        if ply == self.plies.len() {
+            self.plies.push(Ply::default());
+        }
+
+        let entry = &mut self.plies[ply];
+
+        if entry.bbs != board.bbs() || entry.stm != board.stm() {
+            entry.bbs = board.bbs();
+            entry.stm = board.stm();
+            entry.valid = [false; 2];
+        }
+
+        self.len = ply + 1;
+    }
+}
+
+impl Ply {
+    /// The accumulator of `side`, which is then valid.
+    fn slot(&mut self, side: usize) -> &mut ValueAccumulator {
+        self.valid[side] = true;
+        self.accs[side].get_or_insert_with(|| {
+            Box::new(ValueAccumulator {
+                l1: Accumulator([0; L1]),
+                pst: Accumulator([0.0; 3]),
+                count: 0,
+            })
+        })
+    }
+}
+
+/// The features in `old` but not `new`, and in `new` but not `old`.
+fn feature_delta(old: &[usize], new: &[usize], adds: &mut Vec<usize>, subs: &mut Vec<usize>) {
+    let (mut i, mut j) = (0, 0);
+
+    while i < old.len() && j < new.len() {
+        match old[i].cmp(&new[j]) {
+            std::cmp::Ordering::Less => {
+                subs.push(old[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                adds.push(new[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+
+    subs.extend_from_slice(&old[i..]);
+    adds.extend_from_slice(&new[j..]);
+}
+
 impl ValueNetwork {
     pub fn eval(&self, board: &Position) -> (f32, f32, f32) {
+        let (pst, feats, count) = self.features(&board.bbs(), board.stm());
+
+        let mut l2 = self.l1_biases();
+        l2.add_multi_i8(&feats[..count], &self.l1.weights);
+
+        self.output(&l2, &pst)
+    }
+
+    /// `eval` for several positions, sharing the first layer's weights
+    /// between them. Gives exactly the same results as `eval`.
+    pub fn eval_batch(&self, boards: &[Position]) -> Vec<(f32, f32, f32)> {
+        let inputs = boards
+            .iter()
+            .map(|board| self.features(&board.bbs(), board.stm()))
+            .collect::<Vec<_>>();
+
+        let adds = inputs
+            .iter()
+            .map(|(_, feats, count)| &feats[..*count])
+            .collect::<Vec<_>>();
+
+        let mut l2s = vec![self.l1_biases(); boards.len()];
+        Accumulator::add_multi_i8_batch(&mut l2s, &adds, &self.l1.weights);
+
+        l2s.iter()
+            .zip(&inputs)
+            .map(|(l2, (pst, _, _))| self.output(l2, pst))
+            .collect()
+    }
+
+    /// `eval` of the last position pushed to `accs`. The accumulator of the
+    /// side to move is updated from the nearest position above it on the
+    /// line, adding and removing the features changed by each move since:
+    /// those of the pieces that moved and the threats made by, against or
+    /// through their squares. Falls back to a full refresh when there is no
+    /// such position close by, the king crosses the mirroring boundary, or
+    /// a move changes more features than a refresh would add.
+    ///
+    /// In debug builds the result is checked against a full refresh.
+    pub fn eval_incremental(&self, accs: &mut ValueAccumulators) -> (f32, f32, f32) {
+        let ply = accs.len - 1;
+        let side = accs.plies[ply].stm;
+
+        // without a position to update from close by, the root is refreshed
+        // if it is close instead, as every line of the search shares it
+        let start = (ply.saturating_sub(MAX_UPDATES)..=ply)
+            .rev()
+            .find(|&i| accs.plies[i].valid[side])
+            .unwrap_or_else(|| {
+                let start = if ply <= MAX_UPDATES { 0 } else { ply };
+                self.refresh(&mut accs.plies[start], side);
+                start
+            });
+
+        for i in start + 1..=ply {
+            self.update(accs, i, side);
+        }
+
+        let acc = accs.plies[ply].accs[side].as_deref().unwrap();
+
+        #[cfg(debug_assertions)]
+        {
+            let (pst, feats, count) = self.features(&accs.plies[ply].bbs, side);
+            let mut refreshed = self.l1_biases();
+            refreshed.add_multi_i8(&feats[..count], &self.l1.weights);
+            assert!(
+                refreshed.0 == acc.l1.0,
+                "incremental value accumulator differs from a full refresh"
+            );
+            assert!(
+                pst.0
+                    .iter()
+                    .zip(acc.pst.0)
+                    .all(|(a, b)| (a - b).abs() < 1e-3),
+                "incremental value pst differs from a full refresh"
+            );
+        }
+
+        self.output(&acc.l1, &acc.pst)
+    }
+
+    /// Compute the accumulator of `side` at `ply` from the one above it.
+    fn update(&self, accs: &mut ValueAccumulators, ply: usize, side: usize) {
+        let (above, below) = accs.plies.split_at_mut(ply);
+        let (prev, next) = (&above[ply - 1], &mut below[0]);
+
+        let (old, old_mirror) = threats::orient(prev.bbs, side);
+        let (new, new_mirror) = threats::orient(next.bbs, side);
+
+        if old_mirror != new_mirror {
+            self.refresh(next, side);
+            return;
+        }
+
+        accs.old.clear();
+        accs.new.clear();
+        threats::map_changes(&old, &new, |f| accs.old.push(f), |f| accs.new.push(f));
+        accs.old.sort_unstable();
+        accs.new.sort_unstable();
+
+        accs.adds.clear();
+        accs.subs.clear();
+        feature_delta(&accs.old, &accs.new, &mut accs.adds, &mut accs.subs);
+
+        let prev = prev.accs[side].as_deref().unwrap();
+
+        if accs.adds.len() + accs.subs.len() >= prev.count {
+            self.refresh(next, side);
+            return;
+        }
+
+        let acc = next.slot(side);
+        *acc = *prev;
+        acc.l1.add_sub_i8(&accs.adds, &accs.subs, &self.l1.weights);
+        acc.count += accs.adds.len();
+        acc.count -= accs.subs.len();
+
+        for &feat in &accs.adds {
+            acc.pst.add(&self.pst[feat]);
+        }
+
+        for &feat in &accs.subs {
+            for (p, &w) in acc.pst.0.iter_mut().zip(self.pst[feat].0.iter()) {
+                *p -= w;
+            }
+        }
+    }
+
+    fn refresh(&self, ply: &mut Ply, side: usize) {
+        let (pst, feats, count) = self.features(&ply.bbs, side);
+
+        let acc = ply.slot(side);
+        acc.l1 = self.l1_biases();
+        acc.l1.add_multi_i8(&feats[..count], &self.l1.weights);
+        acc.pst = pst;
+        acc.count = count;
+    }
+
+    fn features(&self, bbs: &[u64; 8], side: usize) -> (Accumulator<f32, 3>, [usize; 160], usize) {
         let mut pst = Accumulator([0.0; 3]);
 
         let mut count = 0;
         let mut feats = [0; 160];
-        threats::map_features(board.bbs(), board.stm(), |feat| {
+        threats::map_features(*bbs, side, |feat| {
             feats[count] = feat;
             pst.add(&self.pst[feat]);
             count += 1;
         });
 
+        (pst, feats, count)
+    }
+
+    fn l1_biases(&self) -> Accumulator<i16, L1> {
         let mut l2 = Accumulator([0; L1]);
 
         for (r, &b) in l2.0.iter_mut().zip(self.l1.biases.0.iter()) {
             *r = i16::from(b);
         }
 
-        l2.add_multi_i8(&feats[..count], &self.l1.weights);
+        l2
+    }
 
+    fn output(&self, l2: &Accumulator<i16, L1>, pst: &Accumulator<f32, 3>) -> (f32, f32, f32) {
         let mut act = [0; L1 / 2];
 
         for (a, (&i, &j)) in act
@@ -58,12 +312,11 @@ impl ValueNetwork {
             *a = i * j;
         }
 
+        let level = simd::level();
         let mut fwd = [0; 16];
 
         for (f, row) in fwd.iter_mut().zip(self.l2.weights.iter()) {
-            for (&a, &w) in act.iter().zip(row.0.iter()) {
-                *f += i32::from(a) * i32::from(w);
-            }
+            *f = simd::dot_i16_i16(level, &act, &row.0);
         }
 
         let mut l3 = Accumulator([0.0; 16]);
@@ -74,7 +327,7 @@ impl ValueNetwork {
 
         let l4 = self.l3.forward::<SCReLU>(&l3);
         let mut out = self.l4.forward::<SCReLU>(&l4);
-        out.add(&pst);
+        out.add(pst);
 
         let mut win = out.0[2];
         let mut draw = out.0[1];
@@ -8,7 +8,14 @@ use crate::networks::value::attacks::{ValueAttacks, ValueIndices, ValueOffsets};
 const TOTAL_THREATS: usize = 2 * ValueOffsets::END;
 pub const TOTAL: usize = TOTAL_THREATS + 768;
 
-pub fn map_features<F: FnMut(usize)>(mut bbs: [u64; 8], stm: usize, mut f: F) {
+pub fn map_features<F: FnMut(usize)>(bbs: [u64; 8], stm: usize, f: F) {
+    let (bbs, _) = orient(bbs, stm);
+    map_region(&bbs, u64::MAX, bbs[0] | bbs[1], f);
+}
+
+/// The board from the point of view of `stm`, mirrored so that their king
+/// is on the left half of the board, and whether it was mirrored.
+pub fn orient(mut bbs: [u64; 8], stm: usize) -> ([u64; 8], bool) {
     // flip to stm perspective
     if stm == Side::BLACK {
         bbs.swap(0, 1);
@@ -19,12 +26,37 @@ pub fn map_features<F: FnMut(usize)>(mut bbs: [u64; 8], stm: usize, mut f: F) {
 
     // horiontal mirror
     let ksq = (bbs[0] & bbs[Piece::KING]).trailing_zeros();
-    if ksq % 8 > 3 {
+    let mirror = ksq % 8 > 3;
+    if mirror {
         for bb in bbs.iter_mut() {
             *bb = flip_horizontal(*bb);
         }
     };
 
+    (bbs, mirror)
+}
+
+/// The features of two boards from `orient`, with the same mirroring,
+/// that are not necessarily shared between them: those of pieces on
+/// squares that differ, threats against those squares, and threats of
+/// sliders whose rays pass through them on either board. Every other
+/// feature is the same for both boards.
+pub fn map_changes<F: FnMut(usize), G: FnMut(usize)>(
+    old: &[u64; 8],
+    new: &[u64; 8],
+    f_old: F,
+    f_new: G,
+) {
+    let changed = old.iter().zip(new).fold(0, |c, (o, n)| c | (o ^ n));
+    let (old_occ, new_occ) = (old[0] | old[1], new[0] | new[1]);
+
+    map_region(old, changed, new_occ, f_old);
+    map_region(new, changed, old_occ, f_new);
+}
+
+/// The features involving the squares in `changed`, where `other_occ`
+/// is the occupancy of the board being compared against.
+fn map_region<F: FnMut(usize)>(bbs: &[u64; 8], changed: u64, other_occ: u64, mut f: F) {
     let mut pieces = [13; 64];
     for side in [Side::WHITE, Side::BLACK] {
         for piece in Piece::PAWN..=Piece::KING {
@@ -41,7 +73,7 @@ pub fn map_features<F: FnMut(usize)>(mut bbs: [u64; 8], stm: usize, mut f: F) {
 
         for piece in Piece::PAWN..=Piece::KING {
             map_bb(bbs[side] & bbs[piece], |sq| {
-                let threats = match piece {
+                let attacks = |occ| match piece {
                     Piece::PAWN => Attacks::pawn(sq, side),
                     Piece::KNIGHT => Attacks::knight(sq),
                     Piece::BISHOP => Attacks::bishop(sq, occ),
@@ -49,10 +81,26 @@ pub fn map_features<F: FnMut(usize)>(mut bbs: [u64; 8], stm: usize, mut f: F) {
                     Piece::QUEEN => Attacks::queen(sq, occ),
                     Piece::KING => Attacks::king(sq),
                     _ => unreachable!(),
-                } & occ;
+                };
+
+                let threats = attacks(occ);
+
+                // a piece that moved, or a slider whose rays changed,
+                // may have different threats against any square
+                let mask = if (1 << sq) & changed > 0 {
+                    f(TOTAL_THREATS + [0, 384][side] + 64 * (piece - 2) + sq);
+                    u64::MAX
+                } else if (threats | attacks(other_occ)) & changed > 0 {
+                    if (Piece::BISHOP..=Piece::QUEEN).contains(&piece) {
+                        u64::MAX
+                    } else {
+                        changed
+                    }
+                } else {
+                    return;
+                };
 
-                f(TOTAL_THREATS + [0, 384][side] + 64 * (piece - 2) + sq);
-                map_bb(threats, |dest| {
+                map_bb(threats & occ & mask, |dest| {
                     let enemy = (1 << dest) & opps > 0;
                     if let Some(idx) = map_piece_threat(piece, sq, dest, pieces[dest], enemy) {
                         f(side_offset + idx);
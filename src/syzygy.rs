@@ -0,0 +1,493 @@
+mod index;
+mod table;
+
+use std::{collections::HashMap, fs, ops::Neg, path::PathBuf, sync::OnceLock};
+
+use montyformat::chess::{Castling, Piece, Position, Side};
+use table::{Table, TableKind};
+
+use crate::chess::{ChessState, GameState, Move};
+
+/// Distance given to results proven by the tablebases rather than by search,
+/// large enough that they are not mistaken for (and reported as) mates.
+/// Distances propagated back from them saturate at `u8::MAX`.
+pub const TB_DISTANCE: u8 = 128;
+
+const MAX_DTZ: i32 = 1 << 18;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_score(score: i32) -> Self {
+        match score {
+            ..=-2 => Self::Loss,
+            -1 => Self::BlessedLoss,
+            0 => Self::Draw,
+            1 => Self::CursedWin,
+            2.. => Self::Win,
+        }
+    }
+
+    fn sign(self) -> i32 {
+        match self {
+            Self::Loss | Self::BlessedLoss => -1,
+            Self::Draw => 0,
+            Self::CursedWin | Self::Win => 1,
+        }
+    }
+
+    /// DTZ of a position where the best move is zeroing.
+    fn dtz_before_zeroing(self) -> i32 {
+        match self {
+            Self::Loss => -1,
+            Self::BlessedLoss => -101,
+            Self::Draw => 0,
+            Self::CursedWin => 101,
+            Self::Win => 1,
+        }
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        match self {
+            Self::Loss => Self::Win,
+            Self::BlessedLoss => Self::CursedWin,
+            Self::Draw => Self::Draw,
+            Self::CursedWin => Self::BlessedLoss,
+            Self::Win => Self::Loss,
+        }
+    }
+}
+
+/// Material configuration of a table, e.g. `KRPvKR`.
+#[derive(Clone)]
+pub struct Material {
+    name: String,
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    /// Pawns of the leading colour, then of the other colour.
+    pawn_count: [usize; 2],
+    symmetric: bool,
+}
+
+impl Material {
+    fn parse(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+
+        for side in [white, black] {
+            if !side.starts_with('K')
+                || side[1..].contains('K')
+                || !side.chars().all(|c| "KQRBNP".contains(c))
+            {
+                return None;
+            }
+        }
+
+        let count = |side: &str, piece| side.chars().filter(|&c| c == piece).count();
+
+        let has_unique_pieces = [white, black]
+            .iter()
+            .any(|side| "QRBNP".chars().any(|piece| count(side, piece) == 1));
+
+        // the leading colour is the one with fewer pawns (if both have pawns)
+        let pawns = [count(white, 'P'), count(black, 'P')];
+        let white_leads = pawns[1] == 0 || (pawns[0] > 0 && pawns[1] >= pawns[0]);
+
+        Some(Self {
+            name: name.to_string(),
+            piece_count: white.len() + black.len(),
+            has_pawns: pawns[0] + pawns[1] > 0,
+            has_unique_pieces,
+            pawn_count: if white_leads {
+                pawns
+            } else {
+                [pawns[1], pawns[0]]
+            },
+            symmetric: white == black,
+        })
+    }
+
+    fn of(pos: &Position) -> String {
+        let side = |side| {
+            let mut name = String::from("K");
+
+            for (piece, c) in [
+                (Piece::QUEEN, 'Q'),
+                (Piece::ROOK, 'R'),
+                (Piece::BISHOP, 'B'),
+                (Piece::KNIGHT, 'N'),
+                (Piece::PAWN, 'P'),
+            ] {
+                let count = (pos.piece(piece) & pos.piece(side)).count_ones();
+                name.extend(std::iter::repeat_n(c, count as usize));
+            }
+
+            name
+        };
+
+        format!("{}v{}", side(Side::WHITE), side(Side::BLACK))
+    }
+}
+
+struct Entry {
+    material: Material,
+    wdl_path: PathBuf,
+    dtz_path: Option<PathBuf>,
+    wdl: OnceLock<Option<Table>>,
+    dtz: OnceLock<Option<Table>>,
+}
+
+/// Syzygy endgame tablebases. Files are found when the path is set,
+/// but only mapped into memory the first time they are probed.
+pub struct Tablebases {
+    entries: Vec<Entry>,
+    by_material: HashMap<String, usize>,
+    max_pieces: usize,
+}
+
+impl Tablebases {
+    /// Find all tables in `paths`, a list of directories
+    /// separated by `;` on Windows and `:` elsewhere.
+    pub fn new(paths: &str) -> Self {
+        let separator = if cfg!(windows) { ';' } else { ':' };
+
+        let mut tablebases = Self {
+            entries: Vec::new(),
+            by_material: HashMap::new(),
+            max_pieces: 0,
+        };
+
+        for dir in paths.split(separator).filter(|dir| !dir.is_empty()) {
+            let Ok(files) = fs::read_dir(dir) else {
+                continue;
+            };
+
+            for file in files.flatten() {
+                let path = file.path();
+
+                if path.extension().is_none_or(|ext| ext != "rtbw") {
+                    continue;
+                }
+
+                let Some(material) = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(Material::parse)
+                else {
+                    continue;
+                };
+
+                if tablebases.by_material.contains_key(&material.name) {
+                    continue;
+                }
+
+                let dtz_path = path.with_extension("rtbz");
+                let (white, black) = material.name.split_once('v').unwrap();
+                let mirrored = format!("{black}v{white}");
+
+                tablebases.max_pieces = tablebases.max_pieces.max(material.piece_count);
+
+                let idx = tablebases.entries.len();
+                tablebases.by_material.insert(material.name.clone(), idx);
+                tablebases.by_material.insert(mirrored, idx);
+
+                tablebases.entries.push(Entry {
+                    material,
+                    wdl_path: path,
+                    dtz_path: dtz_path.exists().then_some(dtz_path),
+                    wdl: OnceLock::new(),
+                    dtz: OnceLock::new(),
+                });
+            }
+        }
+
+        tablebases
+    }
+
+    pub fn num_tables(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    fn can_probe(&self, pos: &Position) -> bool {
+        pos.rights() == 0 && pos.occ().count_ones() as usize <= self.max_pieces
+    }
+
+    /// The table for the material in `pos`, and whether the colours need
+    /// to be flipped because the table is stored from the other side.
+    fn table(&self, pos: &Position, kind: TableKind) -> Option<(&Table, bool)> {
+        let name = Material::of(pos);
+        let entry = &self.entries[*self.by_material.get(&name)?];
+
+        let table = match kind {
+            TableKind::Wdl => entry
+                .wdl
+                .get_or_init(|| Table::open(&entry.wdl_path, kind, &entry.material)),
+            TableKind::Dtz => entry.dtz.get_or_init(|| {
+                let path = entry.dtz_path.as_ref()?;
+                Table::open(path, kind, &entry.material)
+            }),
+        };
+
+        // symmetric tables are stored for white to move only
+        let flip =
+            name != entry.material.name || (entry.material.symmetric && pos.stm() == Side::BLACK);
+
+        Some((table.as_ref()?, flip))
+    }
+
+    fn probe_wdl_table(&self, pos: &Position) -> Option<Wdl> {
+        if pos.occ().count_ones() == 2 {
+            return Some(Wdl::Draw);
+        }
+
+        let (table, flip) = self.table(pos, TableKind::Wdl)?;
+        table.probe(pos, flip, Wdl::Draw).map(Wdl::from_score)
+    }
+
+    /// DTZ for the known result `wdl`, with the inner `None` meaning
+    /// the table only stores positions with the other side to move.
+    fn probe_dtz_table(&self, pos: &Position, wdl: Wdl) -> Option<Option<i32>> {
+        let (table, flip) = self.table(pos, TableKind::Dtz)?;
+        Some(table.probe(pos, flip, wdl))
+    }
+
+    /// The tables do not store sensible values for positions where the best
+    /// move is a capture (or, for DTZ, a winning pawn move), nor do they
+    /// account for en passant, so these moves have to be searched as well.
+    /// Also returns whether the best move is zeroing.
+    fn search(&self, pos: &Position, check_zeroing: bool) -> Option<(Wdl, bool)> {
+        let castling = Castling::default();
+
+        let mut moves = Vec::new();
+        pos.map_legal_moves(&castling, |mov| moves.push(mov));
+
+        let mut best = None;
+        let mut searched = 0;
+
+        for &mov in &moves {
+            let zeroing =
+                mov.is_capture() || (check_zeroing && pos.get_pc(1 << mov.src()) == Piece::PAWN);
+
+            if !zeroing {
+                continue;
+            }
+
+            searched += 1;
+
+            let mut next = *pos;
+            next.make(mov, &castling);
+
+            let (value, _) = self.search(&next, false)?;
+            let value = -value;
+
+            if best.is_none_or(|best| value > best) {
+                best = Some(value);
+
+                if value == Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // every move has been searched, so the table need not (and in
+        // the case of en passant, may not be correct to) be probed
+        if searched > 0 && searched == moves.len() {
+            return best.map(|best| (best, true));
+        }
+
+        let value = self.probe_wdl_table(pos)?;
+
+        match best {
+            Some(best) if best >= value => Some((best, best > Wdl::Draw)),
+            _ => Some((value, false)),
+        }
+    }
+
+    /// Probe the result of a position without castling rights.
+    pub fn probe_wdl(&self, pos: &Position) -> Option<Wdl> {
+        if !self.can_probe(pos) {
+            return None;
+        }
+
+        self.search(pos, false).map(|(wdl, _)| wdl)
+    }
+
+    /// Probe the number of plies until the next zeroing move, with
+    /// optimal play, of a position without castling rights. Positive
+    /// if the side to move is winning, and `100` more than the plies
+    /// for cursed wins and blessed losses.
+    pub fn probe_dtz(&self, pos: &Position) -> Option<i32> {
+        if !self.can_probe(pos) {
+            return None;
+        }
+
+        let (wdl, zeroing) = self.search(pos, true)?;
+
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+
+        if zeroing {
+            return Some(wdl.dtz_before_zeroing());
+        }
+
+        if let Some(dtz) = self.probe_dtz_table(pos, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Some((dtz + if cursed { 100 } else { 0 }) * wdl.sign());
+        }
+
+        // the table is for the other side to move, so search one ply
+        // for the move that keeps the result with the best DTZ
+        let castling = Castling::default();
+        let mut best = i32::MAX;
+        let mut result = Some(());
+
+        pos.map_legal_moves(&castling, |mov| {
+            if result.is_none() {
+                return;
+            }
+
+            let zeroing = mov.is_capture() || pos.get_pc(1 << mov.src()) == Piece::PAWN;
+
+            let mut next = *pos;
+            next.make(mov, &castling);
+
+            // for zeroing moves the DTZ before the move is wanted,
+            // with the sign taken from the result after it
+            let dtz = if zeroing {
+                self.search(&next, false)
+                    .map(|(wdl, _)| -wdl.dtz_before_zeroing())
+            } else {
+                self.probe_dtz(&next).map(|dtz| match -dtz {
+                    1 if is_mate(&next) => 1,
+                    dtz => dtz + dtz.signum(),
+                })
+            };
+
+            let Some(dtz) = dtz else {
+                result = None;
+                return;
+            };
+
+            if dtz.signum() == wdl.sign() && dtz < best {
+                best = dtz;
+            }
+        });
+
+        result?;
+
+        // no legal moves means the position is mate
+        Some(if best == i32::MAX { -1 } else { best })
+    }
+
+    /// State of a node in the search tree, if it can be determined by the
+    /// tablebases. Only positions just after a zeroing move are probed, as
+    /// only then can the result not be changed by the fifty-move rule.
+    pub fn probe_state(&self, pos: &ChessState) -> Option<GameState> {
+        let board = pos.board();
+
+        if board.halfm() != 0 {
+            return None;
+        }
+
+        Some(match self.probe_wdl(&board)? {
+            Wdl::Win => GameState::Won(TB_DISTANCE),
+            Wdl::Loss => GameState::Lost(TB_DISTANCE),
+            _ => GameState::Draw,
+        })
+    }
+
+    /// The moves (out of `moves`, or all legal moves if empty) which keep
+    /// the best result at the root by DTZ: the fastest progress when
+    /// winning, any move keeping a draw, and the longest resistance when
+    /// losing.
+    pub fn root_moves(&self, pos: &ChessState, moves: &[Move]) -> Option<Vec<Move>> {
+        let board = pos.board();
+
+        if !self.can_probe(&board) {
+            return None;
+        }
+
+        let halfm = i32::from(board.halfm());
+        let mut candidates = Vec::new();
+
+        pos.map_legal_moves(|mov| {
+            if moves.is_empty() || moves.contains(&mov) {
+                candidates.push(mov);
+            }
+        });
+
+        let mut ranked = Vec::with_capacity(candidates.len());
+
+        for mov in candidates {
+            let mut next = pos.clone();
+            next.make_move(mov);
+
+            let dtz = match next.game_state() {
+                GameState::Lost(_) => {
+                    ranked.push((MAX_DTZ, mov));
+                    continue;
+                }
+                GameState::Draw => 0,
+                _ if next.board().halfm() == 0 => {
+                    (-self.probe_wdl(&next.board())?).dtz_before_zeroing()
+                }
+                _ => {
+                    let dtz = -self.probe_dtz(&next.board())?;
+                    dtz + dtz.signum()
+                }
+            };
+
+            // wins and losses which would be drawn by the
+            // fifty-move rule are ranked just either side of a draw
+            let rank = if dtz > 0 {
+                if dtz + halfm <= 99 {
+                    MAX_DTZ - dtz
+                } else {
+                    1
+                }
+            } else if dtz < 0 {
+                if -dtz + halfm <= 99 {
+                    -MAX_DTZ - dtz
+                } else {
+                    -1
+                }
+            } else {
+                0
+            };
+
+            ranked.push((rank, mov));
+        }
+
+        let best = ranked.iter().map(|&(rank, _)| rank).max()?;
+
+        Some(
+            ranked
+                .into_iter()
+                .filter(|&(rank, _)| rank == best)
+                .map(|(_, mov)| mov)
+                .collect(),
+        )
+    }
+}
+
+fn is_mate(pos: &Position) -> bool {
+    let mut count = 0;
+    pos.map_legal_moves(&Castling::default(), |_| count += 1);
+    count == 0 && pos.in_check()
+}
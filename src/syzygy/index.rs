@@ -0,0 +1,141 @@
+use montyformat::chess::Attacks;
+use once_cell::sync::Lazy;
+
+/// Lookup tables used to turn a position into an index into a table,
+/// matching the encoding used by the Syzygy generator.
+pub struct Index {
+    /// Squares a2-h7 mapped to 0..47, the pawn with the highest
+    /// value being the leading pawn of a table.
+    pub map_pawns: [usize; 64],
+    /// Squares below the a1-h8 diagonal mapped to 0..27.
+    pub map_b1h1h7: [usize; 64],
+    /// Squares in the a1-d1-d4 triangle mapped to 0..9.
+    pub map_a1d1d4: [usize; 64],
+    /// The 462 legal placements of two kings, with the
+    /// first in the a1-d1-d4 triangle.
+    pub map_kk: [[usize; 64]; 10],
+    pub binomial: [[u64; 64]; 6],
+    pub lead_pawn_idx: [[u64; 64]; 6],
+    pub lead_pawns_size: [[u64; 4]; 6],
+}
+
+pub static INDEX: Lazy<Index> = Lazy::new(Index::new);
+
+pub fn off_a1h8(sq: usize) -> i32 {
+    (sq >> 3) as i32 - (sq & 7) as i32
+}
+
+impl Index {
+    fn new() -> Self {
+        let mut index = Self {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let mut code = 0;
+        for sq in 0..64 {
+            if off_a1h8(sq) < 0 {
+                index.map_b1h1h7[sq] = code;
+                code += 1;
+            }
+        }
+
+        // diagonal squares are encoded last
+        let mut diagonal = Vec::new();
+        code = 0;
+        for sq in 0..28 {
+            if off_a1h8(sq) < 0 && sq & 7 <= 3 {
+                index.map_a1d1d4[sq] = code;
+                code += 1;
+            } else if off_a1h8(sq) == 0 && sq & 7 <= 3 {
+                diagonal.push(sq);
+            }
+        }
+
+        for sq in diagonal {
+            index.map_a1d1d4[sq] = code;
+            code += 1;
+        }
+
+        // if the first king is on the a1-d4 diagonal, the other may not be
+        // above the a1-h8 diagonal, and positions with both kings on the
+        // diagonal are encoded last
+        let mut both_on_diagonal = Vec::new();
+        code = 0;
+        for idx in 0..10 {
+            for sq1 in 0..28 {
+                // b1 is mapped to 0
+                if index.map_a1d1d4[sq1] != idx || (idx == 0 && sq1 != 1) {
+                    continue;
+                }
+
+                for sq2 in 0..64 {
+                    if (Attacks::king(sq1) | (1 << sq1)) & (1 << sq2) > 0 {
+                        continue;
+                    }
+
+                    if off_a1h8(sq1) == 0 && off_a1h8(sq2) > 0 {
+                        continue;
+                    }
+
+                    if off_a1h8(sq1) == 0 && off_a1h8(sq2) == 0 {
+                        both_on_diagonal.push((idx, sq2));
+                    } else {
+                        index.map_kk[idx][sq2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+
+        for (idx, sq2) in both_on_diagonal {
+            index.map_kk[idx][sq2] = code;
+            code += 1;
+        }
+
+        index.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                let with = if k > 0 {
+                    index.binomial[k - 1][n - 1]
+                } else {
+                    0
+                };
+                let without = if k < n { index.binomial[k][n - 1] } else { 0 };
+                index.binomial[k][n] = with + without;
+            }
+        }
+
+        // the number of squares available to the other pawns when the
+        // leading pawn is on a given square, reduced by 2 for each rank
+        // due to mirroring
+        let mut available = 47;
+        for lead_pawns in 1..6 {
+            for file in 0..4 {
+                let mut idx = 0;
+
+                for rank in 1..7 {
+                    let sq = 8 * rank + file;
+
+                    if lead_pawns == 1 {
+                        index.map_pawns[sq] = available;
+                        index.map_pawns[sq ^ 7] = available.saturating_sub(1);
+                        available = available.saturating_sub(2);
+                    }
+
+                    index.lead_pawn_idx[lead_pawns][sq] = idx;
+                    idx += index.binomial[lead_pawns - 1][index.map_pawns[sq]];
+                }
+
+                index.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+
+        index
+    }
+}
@@ -0,0 +1,722 @@
+use std::{fs::File, path::Path};
+
+use memmap2::Mmap;
+use montyformat::chess::{Piece, Position, Side};
+
+use super::{
+    index::{off_a1h8, INDEX},
+    Material, Wdl,
+};
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+struct Flag;
+impl Flag {
+    const STM: u8 = 1;
+    const MAPPED: u8 = 2;
+    const WIN_PLIES: u8 = 4;
+    const LOSS_PLIES: u8 = 8;
+    const WIDE: u8 = 16;
+    const SINGLE_VALUE: u8 = 128;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+/// Decoding information for one sub-table (side to move and, for pawnful
+/// tables, file of the leading pawn). Fields named after regions of the
+/// file hold byte offsets into the mapping.
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    min_sym_len: u8,
+    num_blocks: u32,
+    block_size: usize,
+    span: u64,
+    sparse_index_size: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    block_lengths: usize,
+    data: usize,
+    lowest_sym: usize,
+    btree: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    group_idx: [u64; 8],
+    group_len: [usize; 8],
+    pieces: [u8; 7],
+    map_idx: [usize; 4],
+}
+
+/// A single memory mapped `.rtbw` or `.rtbz` file.
+pub struct Table {
+    mmap: Mmap,
+    kind: TableKind,
+    material: Material,
+    pairs: [[PairsData; 4]; 2],
+    map: usize,
+}
+
+impl Table {
+    pub fn open(path: &Path, kind: TableKind, material: &Material) -> Option<Self> {
+        let file = File::open(path).ok()?;
+
+        // SAFETY: tablebase files are not expected to be modified whilst in use
+        let mmap = unsafe { Mmap::map(&file).ok()? };
+
+        let magic = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+
+        if mmap.len() < 5 || mmap[..4] != magic {
+            return None;
+        }
+
+        let mut table = Self {
+            mmap,
+            kind,
+            material: material.clone(),
+            pairs: Default::default(),
+            map: 0,
+        };
+
+        table.init()?;
+
+        Some(table)
+    }
+
+    fn sides(&self) -> usize {
+        if self.kind == TableKind::Wdl && !self.material.symmetric {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn files(&self) -> usize {
+        if self.material.has_pawns {
+            4
+        } else {
+            1
+        }
+    }
+
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        let file = if self.material.has_pawns { file } else { 0 };
+        &self.pairs[stm % self.sides()][file]
+    }
+
+    fn u16_le(&self, at: usize) -> u16 {
+        u16::from_le_bytes([self.mmap[at], self.mmap[at + 1]])
+    }
+
+    fn u32_le(&self, at: usize) -> u32 {
+        u32::from_le_bytes(self.mmap[at..at + 4].try_into().unwrap())
+    }
+
+    /// Decoding a block reads ahead by up to 8 bytes, which at the
+    /// end of the file are past the mapping, so these read as zeroes.
+    fn be_bytes<const N: usize>(&self, at: usize) -> [u8; N] {
+        let mut bytes = [0; N];
+        let available = self.mmap.get(at..).unwrap_or(&[]);
+        let len = N.min(available.len());
+        bytes[..len].copy_from_slice(&available[..len]);
+        bytes
+    }
+
+    fn u32_be(&self, at: usize) -> u32 {
+        u32::from_be_bytes(self.be_bytes(at))
+    }
+
+    fn u64_be(&self, at: usize) -> u64 {
+        u64::from_be_bytes(self.be_bytes(at))
+    }
+
+    fn init(&mut self) -> Option<()> {
+        const HAS_PAWNS: u8 = 2;
+
+        let material = self.material.clone();
+        let sides = self.sides();
+        let files = self.files();
+        let mut pairs: [[PairsData; 4]; 2] = Default::default();
+
+        if (self.mmap[4] & HAS_PAWNS > 0) != material.has_pawns {
+            return None;
+        }
+
+        let mut p = 5;
+
+        // pawns on both sides
+        let pp = material.has_pawns && material.pawn_count[1] > 0;
+
+        for file in 0..files {
+            let second = |byte: u8| if pp { byte } else { 0xF };
+            let order = [
+                [self.mmap[p] & 0xF, second(self.mmap[p + 1] & 0xF)],
+                [self.mmap[p] >> 4, second(self.mmap[p + 1] >> 4)],
+            ];
+
+            p += 1 + usize::from(pp);
+
+            for k in 0..material.piece_count {
+                for (side, side_pairs) in pairs.iter_mut().enumerate().take(sides) {
+                    let byte = self.mmap[p];
+                    side_pairs[file].pieces[k] = if side == 1 { byte >> 4 } else { byte & 0xF };
+                }
+
+                p += 1;
+            }
+
+            for (side, side_pairs) in pairs.iter_mut().enumerate().take(sides) {
+                set_groups(&mut side_pairs[file], &material, order[side], file);
+            }
+        }
+
+        p += p & 1;
+
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut().take(sides) {
+                p = self.set_sizes(&mut side_pairs[file], p)?;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            self.map = p;
+            p = self.set_dtz_map(&mut pairs, p, files)?;
+        }
+
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut().take(sides) {
+                side_pairs[file].sparse_index = p;
+                p += side_pairs[file].sparse_index_size * 6;
+            }
+        }
+
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut().take(sides) {
+                side_pairs[file].block_lengths = p;
+                p += side_pairs[file].block_length_size * 2;
+            }
+        }
+
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut().take(sides) {
+                p = (p + 0x3F) & !0x3F;
+                side_pairs[file].data = p;
+                p += side_pairs[file].num_blocks as usize * side_pairs[file].block_size;
+            }
+        }
+
+        if p > self.mmap.len() {
+            return None;
+        }
+
+        self.pairs = pairs;
+
+        Some(())
+    }
+
+    fn set_sizes(&self, d: &mut PairsData, mut p: usize) -> Option<usize> {
+        d.flags = *self.mmap.get(p)?;
+        p += 1;
+
+        if d.flags & Flag::SINGLE_VALUE > 0 {
+            d.min_sym_len = *self.mmap.get(p)?;
+            return Some(p + 1);
+        }
+
+        if p + 10 > self.mmap.len() {
+            return None;
+        }
+
+        // the size of the table is stored after the last group
+        let groups = d.group_len[..7]
+            .iter()
+            .position(|&len| len == 0)
+            .unwrap_or(7);
+        let tb_size = d.group_idx[groups];
+
+        d.block_size = 1 << self.mmap[p];
+        d.span = 1 << self.mmap[p + 1];
+        d.sparse_index_size = tb_size.div_ceil(d.span) as usize;
+        let padding = usize::from(self.mmap[p + 2]);
+        d.num_blocks = self.u32_le(p + 3);
+        d.block_length_size = d.num_blocks as usize + padding;
+
+        let max_sym_len = self.mmap[p + 7];
+        d.min_sym_len = self.mmap[p + 8];
+        p += 9;
+
+        if max_sym_len < d.min_sym_len || d.min_sym_len == 0 {
+            return None;
+        }
+
+        d.lowest_sym = p;
+
+        // canonical huffman code, see https://en.wikipedia.org/wiki/Canonical_Huffman_code
+        let lengths = usize::from(max_sym_len - d.min_sym_len) + 1;
+        d.base64 = vec![0; lengths];
+
+        for i in (0..lengths - 1).rev() {
+            let lowest = u64::from(self.u16_le(p + 2 * i));
+            let next_lowest = u64::from(self.u16_le(p + 2 * i + 2));
+
+            d.base64[i] = d.base64[i + 1]
+                .wrapping_add(lowest)
+                .wrapping_sub(next_lowest)
+                / 2;
+        }
+
+        for (i, base) in d.base64.iter_mut().enumerate() {
+            *base = base
+                .checked_shl((64 - i - usize::from(d.min_sym_len)) as u32)
+                .unwrap_or(0);
+        }
+
+        p += 2 * lengths;
+
+        let num_syms = usize::from(self.u16_le(p));
+        p += 2;
+
+        d.btree = p;
+
+        if p + 3 * num_syms > self.mmap.len() {
+            return None;
+        }
+
+        d.symlen = vec![0; num_syms];
+        let mut visited = vec![false; num_syms];
+
+        for sym in 0..num_syms {
+            if !visited[sym] {
+                d.symlen[sym] = self.set_symlen(d, sym, &mut visited);
+            }
+        }
+
+        Some(p + 3 * num_syms + (num_syms & 1))
+    }
+
+    fn set_symlen(&self, d: &mut PairsData, sym: usize, visited: &mut [bool]) -> u8 {
+        visited[sym] = true;
+
+        let (left, right) = self.btree(d, sym);
+
+        if right == 0xFFF {
+            return 0;
+        }
+
+        if !visited[left] {
+            d.symlen[left] = self.set_symlen(d, left, visited);
+        }
+
+        if !visited[right] {
+            d.symlen[right] = self.set_symlen(d, right, visited);
+        }
+
+        d.symlen[left].wrapping_add(d.symlen[right]).wrapping_add(1)
+    }
+
+    fn btree(&self, d: &PairsData, sym: usize) -> (usize, usize) {
+        let at = d.btree + 3 * sym;
+        let [b0, b1, b2] = [0, 1, 2].map(|i| usize::from(self.mmap[at + i]));
+        (((b1 & 0xF) << 8) | b0, (b2 << 4) | (b1 >> 4))
+    }
+
+    fn set_dtz_map(
+        &self,
+        pairs: &mut [[PairsData; 4]; 2],
+        mut p: usize,
+        files: usize,
+    ) -> Option<usize> {
+        for d in pairs[0].iter_mut().take(files) {
+            if d.flags & Flag::MAPPED == 0 {
+                continue;
+            }
+
+            if d.flags & Flag::WIDE > 0 {
+                p += p & 1;
+
+                for i in 0..4 {
+                    d.map_idx[i] = (p - self.map) / 2 + 1;
+                    p += 2 * usize::from(self.u16_le(p)) + 2;
+                }
+            } else {
+                for i in 0..4 {
+                    d.map_idx[i] = p - self.map + 1;
+                    p += usize::from(*self.mmap.get(p)?) + 1;
+                }
+            }
+
+            if p > self.mmap.len() {
+                return None;
+            }
+        }
+
+        Some(p + (p & 1))
+    }
+
+    fn decompress_pairs(&self, d: &PairsData, idx: u64) -> usize {
+        if d.flags & Flag::SINGLE_VALUE > 0 {
+            return usize::from(d.min_sym_len);
+        }
+
+        let block_length = |block: usize| i64::from(self.u16_le(d.block_lengths + 2 * block));
+
+        let k = (idx / d.span) as usize;
+        let mut block = self.u32_le(d.sparse_index + 6 * k) as usize;
+        let mut offset = i64::from(self.u16_le(d.sparse_index + 6 * k + 4));
+
+        offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+
+        while offset < 0 {
+            block -= 1;
+            offset += block_length(block) + 1;
+        }
+
+        while offset > block_length(block) {
+            offset -= block_length(block) + 1;
+            block += 1;
+        }
+
+        let mut ptr = d.data + block * d.block_size;
+        let mut buf = self.u64_be(ptr);
+        let mut buf_size = 64;
+        ptr += 8;
+
+        let min_sym_len = usize::from(d.min_sym_len);
+
+        let mut sym = loop {
+            let mut len = 0;
+            while buf < d.base64[len] {
+                len += 1;
+            }
+
+            let sym = ((buf - d.base64[len]) >> (64 - len - min_sym_len)) as usize
+                + usize::from(self.u16_le(d.lowest_sym + 2 * len));
+
+            let sym_len = i64::from(d.symlen[sym]) + 1;
+
+            if offset < sym_len {
+                break sym;
+            }
+
+            offset -= sym_len;
+            len += min_sym_len;
+            buf <<= len;
+            buf_size -= len;
+
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= u64::from(self.u32_be(ptr)) << (64 - buf_size);
+                ptr += 4;
+            }
+        };
+
+        // expand the pair until reaching a single value
+        while d.symlen[sym] > 0 {
+            let (left, right) = self.btree(d, sym);
+            let left_len = i64::from(d.symlen[left]) + 1;
+
+            if offset < left_len {
+                sym = left;
+            } else {
+                offset -= left_len;
+                sym = right;
+            }
+        }
+
+        self.btree(d, sym).0
+    }
+
+    /// Look up a position, with colours flipped if `flip` is set. For DTZ
+    /// tables `wdl` is the (known) result of the position, and `None` is
+    /// returned if the table only stores the other side to move.
+    pub fn probe(&self, pos: &Position, flip: bool, wdl: Wdl) -> Option<i32> {
+        let index = &*INDEX;
+        let material = &self.material;
+
+        let flip_colour = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = usize::from(flip) ^ pos.stm();
+
+        let mut squares = [0usize; 7];
+        let mut pieces = [0u8; 7];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut lead_pawns_cnt = 0;
+        let mut tb_file = 0;
+
+        // the leading pawns are those of the colour of the first piece
+        // in the table, the leading pawn is the one with the highest
+        // `map_pawns` value, being closest to the edge then lowest rank
+        if material.has_pawns {
+            let pc = self.get(0, 0).pieces[0] ^ flip_colour;
+            let side = usize::from(pc >> 3);
+            lead_pawns = pos.piece(Piece::PAWN) & pos.piece(side);
+
+            let mut bb = lead_pawns;
+            while bb > 0 {
+                squares[size] = bb.trailing_zeros() as usize ^ flip_squares;
+                size += 1;
+                bb &= bb - 1;
+            }
+
+            lead_pawns_cnt = size;
+
+            let lead = (0..lead_pawns_cnt)
+                .max_by_key(|&i| (index.map_pawns[squares[i]], usize::MAX - i))
+                .unwrap();
+
+            squares.swap(0, lead);
+
+            let file = squares[0] & 7;
+            tb_file = file.min(7 - file);
+        }
+
+        if self.kind == TableKind::Dtz {
+            let flags = self.get(stm, tb_file).flags;
+            if (material.has_pawns || !material.symmetric) && usize::from(flags & Flag::STM) != stm
+            {
+                return None;
+            }
+        }
+
+        let mut bb = pos.occ() ^ lead_pawns;
+        while bb > 0 {
+            let sq = bb.trailing_zeros() as usize;
+            let side = usize::from(pos.piece(Side::BLACK) & (1 << sq) > 0);
+            let pc = pos.get_pc(1 << sq) as u8 - 1;
+
+            squares[size] = sq ^ flip_squares;
+            pieces[size] = (pc | ((side as u8) << 3)) ^ flip_colour;
+            size += 1;
+            bb &= bb - 1;
+        }
+
+        let d = self.get(stm, tb_file);
+
+        // reorder the pieces to match the sequence in the table
+        for i in lead_pawns_cnt..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // map the leading piece to the a1-d1-d4 triangle
+        if squares[0] & 7 > 3 {
+            for sq in squares.iter_mut().take(size) {
+                *sq ^= 7;
+            }
+        }
+
+        let mut idx;
+
+        if material.has_pawns {
+            idx = index.lead_pawn_idx[lead_pawns_cnt][squares[0]];
+
+            squares[1..lead_pawns_cnt].sort_by_key(|&sq| index.map_pawns[sq]);
+
+            for (i, &sq) in squares.iter().enumerate().take(lead_pawns_cnt).skip(1) {
+                idx += index.binomial[i][index.map_pawns[sq]];
+            }
+        } else {
+            if squares[0] >> 3 > 3 {
+                for sq in squares.iter_mut().take(size) {
+                    *sq ^= 56;
+                }
+            }
+
+            // make sure the first piece of the leading group
+            // not on the a1-h8 diagonal is below it
+            for i in 0..d.group_len[0] {
+                if off_a1h8(squares[i]) == 0 {
+                    continue;
+                }
+
+                if off_a1h8(squares[i]) > 0 {
+                    for sq in squares.iter_mut().take(size).skip(i) {
+                        *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                    }
+                }
+
+                break;
+            }
+
+            idx = if material.has_unique_pieces {
+                encode_unique(&squares)
+            } else {
+                index.map_kk[index.map_a1d1d4[squares[0]]][squares[1]] as u64
+            };
+        }
+
+        idx *= d.group_idx[0];
+
+        // encode the remaining groups, with squares in ascending
+        // order and mapped down past those of previous groups
+        let mut remaining_pawns = material.has_pawns && material.pawn_count[1] > 0;
+        let mut start = d.group_len[0];
+        let mut next = 1;
+
+        while d.group_len[next] > 0 {
+            let len = d.group_len[next];
+            squares[start..start + len].sort_unstable();
+
+            let mut n = 0;
+            for i in 0..len {
+                let sq = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&s| sq > s).count();
+                let pawn_adjust = if remaining_pawns { 8 } else { 0 };
+                n += index.binomial[i + 1][sq - adjust - pawn_adjust];
+            }
+
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            start += len;
+            next += 1;
+        }
+
+        let value = self.decompress_pairs(d, idx) as i32;
+
+        Some(match self.kind {
+            TableKind::Wdl => value - 2,
+            TableKind::Dtz => self.map_dtz(tb_file, value, wdl),
+        })
+    }
+
+    fn map_dtz(&self, file: usize, mut value: i32, wdl: Wdl) -> i32 {
+        let d = self.get(0, file);
+
+        if d.flags & Flag::MAPPED > 0 {
+            let idx = d.map_idx[match wdl {
+                Wdl::Win => 0,
+                Wdl::Loss => 1,
+                Wdl::CursedWin => 2,
+                Wdl::BlessedLoss => 3,
+                Wdl::Draw => 0,
+            }] + value as usize;
+
+            value = if d.flags & Flag::WIDE > 0 {
+                i32::from(self.u16_le(self.map + 2 * idx))
+            } else {
+                i32::from(self.mmap[self.map + idx])
+            };
+        }
+
+        // convert to plies where stored as moves
+        let moves = match wdl {
+            Wdl::Win => d.flags & Flag::WIN_PLIES == 0,
+            Wdl::Loss => d.flags & Flag::LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false,
+        };
+
+        if moves {
+            value *= 2;
+        }
+
+        value + 1
+    }
+}
+
+/// Number of pieces per group, and the multiplier for each group, given
+/// the order in which the table encodes the groups.
+fn set_groups(d: &mut PairsData, material: &Material, order: [u8; 2], file: usize) {
+    let index = &*INDEX;
+
+    let mut n = 0;
+    let mut first_len: i32 = if material.has_pawns {
+        0
+    } else if material.has_unique_pieces {
+        3
+    } else {
+        2
+    };
+
+    d.group_len[0] = 1;
+
+    for i in 1..material.piece_count {
+        first_len -= 1;
+
+        if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+            d.group_len[n] += 1;
+        } else {
+            n += 1;
+            d.group_len[n] = 1;
+        }
+    }
+
+    n += 1;
+    d.group_len[n] = 0;
+
+    let pp = material.has_pawns && material.pawn_count[1] > 0;
+    let mut next = if pp { 2 } else { 1 };
+    let mut free_squares = 64 - d.group_len[0] - if pp { d.group_len[1] } else { 0 };
+    let mut idx = 1;
+    let mut k = 0;
+
+    let order = order.map(usize::from);
+
+    while next < n || k == order[0] || k == order[1] {
+        if k == order[0] {
+            d.group_idx[0] = idx;
+            idx *= if material.has_pawns {
+                index.lead_pawns_size[d.group_len[0]][file]
+            } else if material.has_unique_pieces {
+                31332
+            } else {
+                462
+            };
+        } else if k == order[1] {
+            d.group_idx[1] = idx;
+            idx *= index.binomial[d.group_len[1]][48 - d.group_len[0]];
+        } else {
+            d.group_idx[next] = idx;
+            idx *= index.binomial[d.group_len[next]][free_squares];
+            free_squares -= d.group_len[next];
+            next += 1;
+        }
+
+        k += 1;
+    }
+
+    d.group_idx[n] = idx;
+}
+
+/// Index of the first three pieces when they are all different,
+/// the first piece being in the a1-d1-d4 triangle.
+fn encode_unique(squares: &[usize; 7]) -> u64 {
+    let index = &*INDEX;
+    let [s0, s1, s2] = [squares[0], squares[1], squares[2]];
+    let adjust1 = usize::from(s1 > s0);
+    let adjust2 = usize::from(s2 > s0) + usize::from(s2 > s1);
+
+    let idx = if off_a1h8(s0) != 0 {
+        (index.map_a1d1d4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2
+    } else if off_a1h8(s1) != 0 {
+        (6 * 63 + (s0 >> 3) * 28 + index.map_b1h1h7[s1]) * 62 + s2 - adjust2
+    } else if off_a1h8(s2) != 0 {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + (s0 >> 3) * 7 * 28
+            + ((s1 >> 3) - adjust1) * 28
+            + index.map_b1h1h7[s2]
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + (s0 >> 3) * 6 * 7
+            + ((s1 >> 3) - adjust1) * 6
+            + ((s2 >> 3) - adjust2)
+    };
+
+    idx as u64
+}
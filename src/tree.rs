@@ -1,12 +1,15 @@
+mod graph;
 mod half;
 mod hash;
 mod lock;
 mod node;
+mod persist;
 
+use graph::{GraphEntry, GraphTable};
 use half::TreeHalf;
 use hash::{HashEntry, HashTable};
 use node::NodeStatsDelta;
-pub use node::{Node, NodePtr};
+pub use node::{Bounds, Node, NodePtr, Outcome};
 
 use std::{
     array,
@@ -19,7 +22,7 @@ use std::{
 use crate::{
     chess::{ChessState, GameState, Move},
     mcts::{MctsParams, SearchHelpers},
-    networks::PolicyNetwork,
+    networks::{Accumulator, PolicyNetwork, POLICY_L1},
 };
 
 const NUM_SIDES: usize = 2;
@@ -294,8 +297,11 @@ pub struct Tree {
     tree: [TreeHalf; 2],
     half: AtomicBool,
     hash: HashTable,
+    hash_cap: usize,
+    graph: Option<GraphTable>,
     butterfly: ButterflyTable,
     root_accumulator: RootAccumulator,
+    search_moves: Vec<Move>,
 }
 
 impl Index<NodePtr> for Tree {
@@ -321,6 +327,8 @@ impl Tree {
     }
 
     fn new(tree_cap: usize, hash_cap: usize, threads: usize) -> Self {
+        let (hash, _) = Self::table_sizes(hash_cap, false);
+
         let tree = Self {
             root: ChessState::default(),
             tree: [
@@ -328,9 +336,12 @@ impl Tree {
                 TreeHalf::new(tree_cap / 2, true, threads),
             ],
             half: AtomicBool::new(false),
-            hash: HashTable::new(hash_cap / 4, threads),
+            hash: HashTable::new(hash, threads),
+            hash_cap,
+            graph: None,
             butterfly: ButterflyTable::new(),
             root_accumulator: RootAccumulator::new(threads),
+            search_moves: Vec::new(),
         };
 
         tree.reset_root_accumulator();
@@ -342,15 +353,55 @@ impl Tree {
     /// allocation. Dropping the existing instance before building the new one
     /// prevents temporarily doubling the hash table's memory usage.
     pub fn rebuild(&mut self, mb: usize, threads: usize, root: ChessState) {
+        let graph = self.has_graph();
+
         unsafe {
             let ptr: *mut Tree = self;
             ptr::drop_in_place(ptr);
             ptr::write(ptr, Tree::new_mb(mb, threads));
         }
 
+        self.set_graph(graph, threads);
         self.set_root_position(&root);
     }
 
+    /// Enable or disable Monte-Carlo graph search, in which nodes reaching
+    /// the same position share their statistics through a `GraphTable`.
+    /// The table takes half of the memory of the hash table, so that the
+    /// total stays within the budget, and the hash table is cleared.
+    pub fn set_graph(&mut self, enabled: bool, threads: usize) {
+        if enabled == self.has_graph() {
+            return;
+        }
+
+        // free the old tables before allocating their replacements
+        self.graph = None;
+        self.hash = HashTable::new(0, threads);
+
+        let (hash, graph) = Self::table_sizes(self.hash_cap, enabled);
+        self.hash = HashTable::new(hash, threads);
+        self.graph = enabled.then(|| GraphTable::new(graph, threads));
+    }
+
+    /// The entries of the hash and graph tables sharing `hash_cap`, with
+    /// entries of both tables being the same size.
+    fn table_sizes(hash_cap: usize, graph: bool) -> (usize, usize) {
+        if graph {
+            (hash_cap / 8, hash_cap / 8)
+        } else {
+            (hash_cap / 4, 0)
+        }
+    }
+
+    /// How the memory of the tree's nodes is placed, for `info string`s.
+    pub fn placement(&self) -> String {
+        self.tree[0].nodes.placement()
+    }
+
+    pub fn has_graph(&self) -> bool {
+        self.graph.is_some()
+    }
+
     pub fn root_position(&self) -> &ChessState {
         &self.root
     }
@@ -455,6 +506,25 @@ impl Tree {
         self.hash.push(hash, wins, draw, visits);
     }
 
+    /// Start a new search generation in the hash table.
+    pub fn age_hash(&self) {
+        self.hash.age();
+    }
+
+    pub fn hashfull(&self) -> usize {
+        self.hash.hashfull()
+    }
+
+    pub fn probe_graph(&self, hash: u64) -> Option<GraphEntry> {
+        self.graph.as_ref().and_then(|graph| graph.get(hash))
+    }
+
+    pub fn push_graph(&self, hash: u64, q: f32, draw: f32) {
+        if let Some(graph) = &self.graph {
+            graph.push(hash, q, draw);
+        }
+    }
+
     pub fn update_node_stats(&self, ptr: NodePtr, value: f32, draw: f32, thread_id: usize) {
         let delta = NodeStatsDelta::from_value(value, draw);
         self.root_accumulator.add(ptr, &self[ptr], delta, thread_id);
@@ -476,8 +546,12 @@ impl Tree {
 
     pub fn clear(&mut self, threads: usize) {
         self.root = ChessState::default();
+        self.search_moves.clear();
         self.clear_halves();
         self.hash.clear(threads);
+        if let Some(graph) = &mut self.graph {
+            graph.clear(threads);
+        }
         self.butterfly.clear();
         self.root_accumulator.reset(self.root_node());
     }
@@ -486,6 +560,86 @@ impl Tree {
         self.tree[0].is_empty() && self.tree[1].is_empty()
     }
 
+    /// Restrict the moves searched at the root (`go searchmoves`),
+    /// an empty list meaning all legal moves. Moves which are not
+    /// legal in the root position are ignored.
+    pub fn set_search_moves(&mut self, moves: &[Move]) {
+        let mut legal = Vec::new();
+        self.root.map_legal_moves(|mov| {
+            if moves.contains(&mov) {
+                legal.push(mov);
+            }
+        });
+
+        self.search_moves = legal;
+    }
+
+    pub fn is_search_move(&self, mov: Move) -> bool {
+        self.search_moves.is_empty() || self.search_moves.contains(&mov)
+    }
+
+    /// Bring the children of an already expanded root in line with the
+    /// current `searchmoves` restriction, which may have changed since the
+    /// tree was built. Children that remain allowed keep their statistics
+    /// and subtrees; newly allowed moves are added unvisited, with their
+    /// policies to be filled in by `relabel_policy`.
+    pub fn apply_search_moves(&self) {
+        let root = self.root_node();
+        let node = &self[root];
+
+        if !node.has_children() {
+            return;
+        }
+
+        let mut wanted = Vec::new();
+        self.root.map_legal_moves(|mov| {
+            if self.is_search_move(mov) {
+                wanted.push(mov);
+            }
+        });
+
+        let first_child_ptr = node.actions();
+        let num_actions = node.num_actions();
+        let has_child =
+            |mov| (0..num_actions).any(|a| self[first_child_ptr + a].parent_move() == mov);
+
+        if num_actions == wanted.len() && wanted.iter().all(|&mov| has_child(mov)) {
+            return;
+        }
+
+        // if there is no space left, the root will simply be
+        // re-expanded (with the restriction) on its next visit
+        let Some(new_ptr) = self.tree[self.half()].reserve_nodes_thread(wanted.len(), 0) else {
+            node.clear_actions();
+            return;
+        };
+
+        let mut next = 0;
+
+        for action in 0..num_actions {
+            let ptr = first_child_ptr + action;
+
+            if wanted.contains(&self[ptr].parent_move()) {
+                self.copy_node_across(ptr, new_ptr + next, false);
+                next += 1;
+            }
+        }
+
+        for &mov in &wanted {
+            if !has_child(mov) {
+                self[new_ptr + next].set_new(mov, 0.0);
+                next += 1;
+            }
+        }
+
+        node.actions_mut().store(new_ptr);
+        node.set_num_actions(wanted.len());
+        self.tree[self.half()].register_cross_link(root, new_ptr);
+
+        // any result proven for the root may no longer hold
+        node.set_state(GameState::Ongoing);
+    }
+
     pub fn expand_node(
         &self,
         node_ptr: NodePtr,
@@ -494,6 +648,26 @@ impl Tree {
         policy: &PolicyNetwork,
         depth: usize,
         thread_id: usize,
+    ) -> Option<()> {
+        if !self[node_ptr].is_not_expanded() {
+            return Some(());
+        }
+
+        let hl = pos.get_policy_hl(policy);
+        self.expand_node_with_hl(node_ptr, pos, params, policy, &hl, depth, thread_id)
+    }
+
+    /// `expand_node` with the policy hidden layer of `pos` already computed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn expand_node_with_hl(
+        &self,
+        node_ptr: NodePtr,
+        pos: &ChessState,
+        params: &MctsParams,
+        policy: &PolicyNetwork,
+        hl: &Accumulator<i16, { POLICY_L1 / 2 }>,
+        depth: usize,
+        thread_id: usize,
     ) -> Option<()> {
         let node = &self[node_ptr];
 
@@ -510,8 +684,13 @@ impl Tree {
         let mut moves = [const { MaybeUninit::uninit() }; 256];
         let mut count = 0;
         let stm = pos.stm();
+        let is_root = node_ptr == self.root_node();
+
+        pos.map_moves_with_policy_hl(policy, hl, |mov, policy| {
+            if is_root && !self.is_search_move(mov) {
+                return;
+            }
 
-        pos.map_moves_with_policies(policy, |mov, policy| {
             let adjusted = policy + self.butterfly.policy_bonus(stm, mov, params);
             moves[count].write((mov, adjusted));
             count += 1;
@@ -609,11 +788,19 @@ impl Tree {
         self.butterfly.clear();
     }
 
-    pub fn propogate_proven_mates(&self, ptr: NodePtr, child_state: GameState) {
-        match child_state {
-            // if the child node resulted in a loss, then
-            // this node has a guaranteed win
-            GameState::Lost(n) => self[ptr].set_state(GameState::Won(n + 1)),
+    /// Update the proven result of `ptr` after one of its children has been
+    /// visited. Mates are propagated with their distance, and otherwise the
+    /// bounds of `ptr` are tightened from those of its children, making it a
+    /// proven draw if none of its children can do better than a draw.
+    pub fn propogate_proven_mates(&self, ptr: NodePtr, child_ptr: NodePtr) {
+        match self[child_ptr].state() {
+            // if the child node resulted in a loss, then this node has a
+            // guaranteed win, with distances saturating as tablebase results
+            // already start at `TB_DISTANCE`
+            GameState::Lost(n) => {
+                self[ptr].set_state(GameState::Won(n.saturating_add(1)));
+                return;
+            }
             // if the child node resulted in a win, then check if there are
             // any non-won children, and if not, guaranteed loss for this node
             GameState::Won(n) => {
@@ -635,46 +822,74 @@ impl Tree {
                 }
 
                 if proven_loss {
-                    self[ptr].set_state(GameState::Lost(max_win_len + 1));
+                    self[ptr].set_state(GameState::Lost(max_win_len.saturating_add(1)));
+                    return;
                 }
             }
-            // nothing to do otherwise
             _ => {}
         }
+
+        // bounds only tighten once a child has some proven result
+        if self[child_ptr].bounds() != Bounds::UNKNOWN && !self[ptr].is_terminal() {
+            self.update_bounds(ptr);
+        }
+    }
+
+    fn update_bounds(&self, ptr: NodePtr) {
+        let node = &self[ptr];
+        let first_child_ptr = node.actions();
+
+        let mut bounds = Bounds::exact(Outcome::Loss);
+
+        for action in 0..node.num_actions() {
+            let child = self[first_child_ptr + action].bounds().flip();
+            bounds.lower = bounds.lower.max(child.lower);
+            bounds.upper = bounds.upper.max(child.upper);
+        }
+
+        // proven wins and losses have already been found, with their distance
+        if bounds == Bounds::exact(Outcome::Draw) {
+            node.set_state(GameState::Draw);
+        } else {
+            node.set_bounds(bounds);
+        }
     }
 
-    pub fn set_root_position(&mut self, new_root: &ChessState) {
+    /// Whether `child` cannot improve on the result already guaranteed
+    /// to `parent`, so that there is nothing to gain by searching it.
+    pub fn is_pruned(parent: &Node, child: &Node) -> bool {
+        let parent = parent.bounds();
+        parent != Bounds::UNKNOWN && child.bounds().flip().upper <= parent.lower
+    }
+
+    /// Move the root to `new_root`, keeping the subtree of the new root if
+    /// it is found within the current tree. Returns whether it was found.
+    pub fn set_root_position(&mut self, new_root: &ChessState) -> bool {
         let old_root = self.root.clone();
         self.root = new_root.clone();
+        self.search_moves.clear();
 
         self.flush_root_accumulator();
         self.reset_root_accumulator();
 
         if self.is_empty() {
-            return;
+            return false;
         }
 
-        let mut found = false;
-
-        println!("info string searching for subtree");
-
         let root = self.recurse_find(self.root_node(), &old_root, new_root, 2);
 
         if !root.is_null() && self[root].has_children() {
-            found = true;
-
             if root != self.root_node() {
                 self[self.root_node()].clear();
                 self.copy_node_across(root, self.root_node(), false);
             }
 
-            println!("info string found subtree");
+            return true;
         }
 
-        if !found {
-            println!("info string no subtree found");
-            self.clear_halves();
-        }
+        self.clear_halves();
+
+        false
     }
 
     fn recurse_find(
@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::memory::LargeBuffer;
+
+const BUCKET_SIZE: usize = 4;
+
+const VALUE_BITS: u32 = 20;
+const VALUE_MAX: u64 = (1 << VALUE_BITS) - 1;
+const MAX_VISITS: u64 = (1 << (64 - 2 * VALUE_BITS)) - 1;
+
+/// The mean value and draw rate of a position over all of its visits,
+/// packed into one word so that it can be checked against its key.
+/// Every stored entry has at least one visit, so an empty entry is zero.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GraphEntry {
+    data: u64,
+}
+
+impl GraphEntry {
+    pub fn visits(&self) -> u64 {
+        self.data >> (2 * VALUE_BITS)
+    }
+
+    pub fn q(&self) -> f32 {
+        (self.data & VALUE_MAX) as f32 / VALUE_MAX as f32
+    }
+
+    pub fn d(&self) -> f32 {
+        ((self.data >> VALUE_BITS) & VALUE_MAX) as f32 / VALUE_MAX as f32
+    }
+
+    fn new(q: f64, draw: f64, visits: u64) -> Self {
+        let quantise = |x: f64| (x.clamp(0.0, 1.0) * VALUE_MAX as f64).round() as u64;
+
+        Self {
+            data: quantise(q)
+                | quantise(draw) << VALUE_BITS
+                | visits.min(MAX_VISITS) << (2 * VALUE_BITS),
+        }
+    }
+
+    /// The entry after one more visit with the given value.
+    fn with_visit(self, q: f32, draw: f32) -> Self {
+        let visits = self.visits() as f64;
+        let mean = |old: f32, new: f32| (f64::from(old) * visits + f64::from(new)) / (visits + 1.0);
+
+        Self::new(mean(self.q(), q), mean(self.d(), draw), self.visits() + 1)
+    }
+}
+
+/// An entry stores the full hash xor its data, so that both other
+/// positions in the same bucket and torn writes from other threads
+/// are detected when probing.
+#[derive(Default)]
+struct GraphEntryInternal {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+impl GraphEntryInternal {
+    fn load(&self) -> (u64, GraphEntry) {
+        let data = self.data.load(Ordering::Relaxed);
+        let key = self.check.load(Ordering::Relaxed) ^ data;
+
+        (key, GraphEntry { data })
+    }
+
+    fn store(&self, hash: u64, entry: GraphEntry) {
+        self.data.store(entry.data, Ordering::Relaxed);
+        self.check.store(hash ^ entry.data, Ordering::Relaxed);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.load(Ordering::Relaxed) == 0
+    }
+}
+
+#[derive(Default)]
+#[repr(align(64))]
+struct Bucket {
+    entries: [GraphEntryInternal; BUCKET_SIZE],
+}
+
+/// Statistics shared between all nodes of the tree that reach the same
+/// position, for Monte-Carlo graph search. Unlike the `HashTable`, entries
+/// accumulate every visit made to the position, with the value from the
+/// point of view of the side that moved into it, as in `Node`.
+pub struct GraphTable {
+    table: LargeBuffer<Bucket>,
+}
+
+impl GraphTable {
+    /// A table with room for `size` entries, in buckets sharing a cache line.
+    pub fn new(size: usize, threads: usize) -> Self {
+        let buckets = size.div_ceil(BUCKET_SIZE).max(1);
+
+        Self {
+            table: LargeBuffer::new(buckets, threads, Bucket::default),
+        }
+    }
+
+    pub fn clear(&mut self, threads: usize) {
+        let chunk_size = self.table.len().div_ceil(threads.max(1));
+
+        std::thread::scope(|s| {
+            for chunk in self.table.chunks_mut(chunk_size) {
+                s.spawn(|| {
+                    for bucket in chunk.iter_mut() {
+                        *bucket = Bucket::default();
+                    }
+                });
+            }
+        });
+    }
+
+    fn bucket(&self, hash: u64) -> &Bucket {
+        let idx = (u128::from(hash) * self.table.len() as u128) >> 64;
+        &self.table[idx as usize]
+    }
+
+    pub fn get(&self, hash: u64) -> Option<GraphEntry> {
+        self.bucket(hash)
+            .entries
+            .iter()
+            .map(GraphEntryInternal::load)
+            .find(|&(key, entry)| key == hash && entry.data != 0)
+            .map(|(_, entry)| entry)
+    }
+
+    /// Add a visit to the position with `hash`, otherwise replacing the
+    /// entry with the fewest visits in its bucket. Visits made at the same
+    /// time by other threads may be lost, but never attributed to another
+    /// position.
+    pub fn push(&self, hash: u64, q: f32, draw: f32) {
+        let bucket = self.bucket(hash);
+
+        let mut replace = &bucket.entries[0];
+        let mut fewest_visits = u64::MAX;
+
+        for slot in &bucket.entries {
+            if slot.is_empty() {
+                replace = slot;
+                break;
+            }
+
+            let (key, entry) = slot.load();
+
+            if key == hash {
+                slot.store(hash, entry.with_visit(q, draw));
+                return;
+            }
+
+            if entry.visits() < fewest_visits {
+                fewest_visits = entry.visits();
+                replace = slot;
+            }
+        }
+
+        replace.store(hash, GraphEntry::new(f64::from(q), f64::from(draw), 1));
+    }
+}
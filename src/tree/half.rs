@@ -4,12 +4,12 @@ use std::sync::{
 };
 
 use super::{Node, NodePtr};
-use crate::chess::GameState;
+use crate::{chess::GameState, memory::LargeBuffer};
 
 const CACHE_SIZE: usize = 1024;
 
 pub struct TreeHalf {
-    pub(super) nodes: Vec<Node>,
+    pub(super) nodes: LargeBuffer<Node>,
     used: AtomicUsize,
     next: Vec<AtomicUsize>,
     end: Vec<AtomicUsize>,
@@ -32,8 +32,8 @@ impl TreeHalf {
         let cross_links = Mutex::new(Vec::new());
         let cross_link_marks = (0..size).map(|_| AtomicU64::new(0)).collect();
 
-        let mut res = Self {
-            nodes: Vec::new(),
+        Self {
+            nodes: LargeBuffer::new(size, threads, || Node::new(GameState::Ongoing)),
             used: AtomicUsize::new(0),
             next: (0..threads).map(|_| AtomicUsize::new(0)).collect(),
             end: (0..threads).map(|_| AtomicUsize::new(0)).collect(),
@@ -41,30 +41,7 @@ impl TreeHalf {
             cross_links,
             cross_link_marks,
             cross_link_epoch: AtomicU64::new(1),
-        };
-
-        res.nodes.reserve_exact(size);
-
-        unsafe {
-            use std::mem::MaybeUninit;
-            let chunk_size = size.div_ceil(threads);
-            let ptr = res.nodes.as_mut_ptr().cast();
-            let uninit: &mut [MaybeUninit<Node>] = std::slice::from_raw_parts_mut(ptr, size);
-
-            std::thread::scope(|s| {
-                for chunk in uninit.chunks_mut(chunk_size) {
-                    s.spawn(|| {
-                        for node in chunk {
-                            node.write(Node::new(GameState::Ongoing));
-                        }
-                    });
-                }
-            });
-
-            res.nodes.set_len(size);
         }
-
-        res
     }
 
     pub fn reserve_nodes_thread(&self, num: usize, thread: usize) -> Option<NodePtr> {
@@ -1,53 +1,101 @@
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::{
+    io::{self, Read, Write},
+    sync::atomic::{AtomicU64, AtomicU8, Ordering},
+};
+
+use crate::memory::LargeBuffer;
+
+const BUCKET_SIZE: usize = 4;
+const MAX_VISITS: u64 = (1 << 23) - 1;
+
+/// Set in every stored entry, so that an empty entry is all zeroes.
+const USED: u64 = 1 << 55;
+
+/// The number of entries sampled to estimate `hashfull`.
+const HASHFULL_SAMPLE: usize = 1000;
 
 #[derive(Clone, Copy, Debug, Default)]
 pub struct HashEntry {
-    hash: u32,
-    q: u32,
-    d: u32,
-    #[allow(dead_code)]
-    visits: u32,
+    data: u64,
 }
 
 impl HashEntry {
     pub fn q(&self) -> f32 {
-        self.q as f32 / u32::MAX as f32
+        f32::from(self.data as u16) / f32::from(u16::MAX)
     }
 
     pub fn d(&self) -> f32 {
-        self.d as f32 / u32::MAX as f32
+        f32::from((self.data >> 16) as u16) / f32::from(u16::MAX)
+    }
+
+    fn visits(&self) -> u64 {
+        (self.data >> 32) & MAX_VISITS
+    }
+
+    fn generation(&self) -> u8 {
+        (self.data >> 56) as u8
+    }
+
+    fn new(q: f32, draw: f32, visits: u64, generation: u8) -> Self {
+        let quantise = |x: f32| (x.clamp(0.0, 1.0) * f32::from(u16::MAX)) as u64;
+
+        Self {
+            data: quantise(q)
+                | quantise(draw) << 16
+                | visits.min(MAX_VISITS) << 32
+                | USED
+                | u64::from(generation) << 56,
+        }
     }
 }
 
+/// An entry stores the full hash xor its data, so that both hash
+/// collisions in the same bucket and torn writes from other threads
+/// are detected when probing.
 #[derive(Default)]
 struct HashEntryInternal {
-    hash: AtomicU32,
-    q: AtomicU32,
-    d: AtomicU32,
-    visits: AtomicU32,
+    check: AtomicU64,
+    data: AtomicU64,
 }
 
-impl Clone for HashEntryInternal {
-    fn clone(&self) -> Self {
-        Self {
-            hash: AtomicU32::new(self.hash.load(Ordering::Relaxed)),
-            q: AtomicU32::new(self.q.load(Ordering::Relaxed)),
-            d: AtomicU32::new(self.d.load(Ordering::Relaxed)),
-            visits: AtomicU32::new(self.visits.load(Ordering::Relaxed)),
-        }
+impl HashEntryInternal {
+    fn load(&self) -> (u64, HashEntry) {
+        let data = self.data.load(Ordering::Relaxed);
+        let key = self.check.load(Ordering::Relaxed) ^ data;
+
+        (key, HashEntry { data })
+    }
+
+    fn store(&self, hash: u64, entry: HashEntry) {
+        self.data.store(entry.data, Ordering::Relaxed);
+        self.check.store(hash ^ entry.data, Ordering::Relaxed);
     }
+
+    fn is_empty(&self) -> bool {
+        self.data.load(Ordering::Relaxed) == 0
+    }
+}
+
+#[derive(Default)]
+#[repr(align(64))]
+struct Bucket {
+    entries: [HashEntryInternal; BUCKET_SIZE],
 }
 
 pub struct HashTable {
-    table: Vec<HashEntryInternal>,
+    table: LargeBuffer<Bucket>,
+    generation: AtomicU8,
 }
 
 impl HashTable {
-    pub fn new(size: usize, _threads: usize) -> Self {
-        let mut table = HashTable { table: Vec::new() };
-        table.table.resize_with(size, HashEntryInternal::default);
+    /// A table with room for `size` entries, in buckets sharing a cache line.
+    pub fn new(size: usize, threads: usize) -> Self {
+        let buckets = size.div_ceil(BUCKET_SIZE).max(1);
 
-        table
+        HashTable {
+            table: LargeBuffer::new(buckets, threads, Bucket::default),
+            generation: AtomicU8::new(0),
+        }
     }
 
     pub fn clear(&mut self, threads: usize) {
@@ -56,60 +104,148 @@ impl HashTable {
         std::thread::scope(|s| {
             for chunk in self.table.chunks_mut(chunk_size) {
                 s.spawn(|| {
-                    for entry in chunk.iter_mut() {
-                        *entry = HashEntryInternal::default();
+                    for bucket in chunk.iter_mut() {
+                        *bucket = Bucket::default();
                     }
                 });
             }
         });
+
+        self.generation.store(0, Ordering::Relaxed);
     }
 
-    pub fn fetch(&self, hash: u64) -> HashEntry {
-        let idx = hash % (self.table.len() as u64);
-        let entry = &self.table[idx as usize];
+    /// Start a new search, so that entries from previous
+    /// searches are preferred for replacement.
+    pub fn age(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of entries used by the current search, per mille,
+    /// estimated from the first few buckets.
+    pub fn hashfull(&self) -> usize {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sampled = self
+            .table
+            .iter()
+            .flat_map(|bucket| &bucket.entries)
+            .take(HASHFULL_SAMPLE);
+
+        let mut total = 0;
+        let mut used = 0;
 
-        HashEntry {
-            hash: entry.hash.load(Ordering::Relaxed),
-            q: entry.q.load(Ordering::Relaxed),
-            d: entry.d.load(Ordering::Relaxed),
-            visits: entry.visits.load(Ordering::Relaxed),
+        for entry in sampled {
+            total += 1;
+
+            if !entry.is_empty() && entry.load().1.generation() == generation {
+                used += 1;
+            }
         }
+
+        used * 1000 / total
     }
 
-    fn key(hash: u64) -> u32 {
-        (hash >> 32) as u32
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&(self.table.len() as u64).to_le_bytes())?;
+        out.write_all(&[self.generation.load(Ordering::Relaxed)])?;
+
+        for entry in self.table.iter().flat_map(|bucket| &bucket.entries) {
+            for field in [&entry.check, &entry.data] {
+                out.write_all(&field.load(Ordering::Relaxed).to_le_bytes())?;
+            }
+        }
+
+        Ok(())
     }
 
-    pub fn get(&self, hash: u64) -> Option<HashEntry> {
-        let entry = self.fetch(hash);
+    /// Restore entries written by `write_to`. Buckets are indexed by the
+    /// hash, so entries can only be restored into a table of the same size;
+    /// otherwise they are skipped and `false` is returned.
+    pub fn read_from(&self, inp: &mut impl Read) -> io::Result<bool> {
+        let mut len = [0; 8];
+        inp.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len);
+
+        let mut generation = [0];
+        inp.read_exact(&mut generation)?;
+
+        let restore = len == self.table.len() as u64;
+        let mut buf = [0; 16];
+
+        for idx in 0..len as usize * BUCKET_SIZE {
+            inp.read_exact(&mut buf)?;
+
+            if restore {
+                let entry = &self.table[idx / BUCKET_SIZE].entries[idx % BUCKET_SIZE];
+                let fields = [&entry.check, &entry.data];
+
+                for (field, bytes) in fields.into_iter().zip(buf.chunks_exact(8)) {
+                    field.store(
+                        u64::from_le_bytes(bytes.try_into().unwrap()),
+                        Ordering::Relaxed,
+                    );
+                }
+            }
+        }
 
-        if entry.hash == Self::key(hash) {
-            Some(entry)
-        } else {
-            None
+        if restore {
+            self.generation.store(generation[0], Ordering::Relaxed);
         }
+
+        Ok(restore)
     }
 
+    fn bucket(&self, hash: u64) -> &Bucket {
+        let idx = (u128::from(hash) * self.table.len() as u128) >> 64;
+        &self.table[idx as usize]
+    }
+
+    pub fn get(&self, hash: u64) -> Option<HashEntry> {
+        self.bucket(hash)
+            .entries
+            .iter()
+            .map(HashEntryInternal::load)
+            .find(|&(key, entry)| key == hash && entry.data != 0)
+            .map(|(_, entry)| entry)
+    }
+
+    /// Store an entry for `hash`, replacing an existing one for the same
+    /// position if it has no more visits, or otherwise the least valuable
+    /// entry in the bucket, those from older searches being worth less.
     pub fn push(&self, hash: u64, q: f32, draw: f32, visits: u64) {
-        let idx = hash % (self.table.len() as u64);
-        let key = Self::key(hash);
-        let q_u32 = (q.clamp(0.0, 1.0) * u32::MAX as f32) as u32;
-        let d_u32 = (draw.clamp(0.0, 1.0) * u32::MAX as f32) as u32;
-        let visits_u32 = visits.min(u32::MAX as u64) as u32;
-
-        let entry = &self.table[idx as usize];
-        let existing_hash = entry.hash.load(Ordering::Relaxed);
-        let replace = if existing_hash != key {
-            true
-        } else {
-            visits_u32 >= entry.visits.load(Ordering::Relaxed)
-        };
-
-        if replace {
-            entry.q.store(q_u32, Ordering::Relaxed);
-            entry.d.store(d_u32, Ordering::Relaxed);
-            entry.visits.store(visits_u32, Ordering::Relaxed);
-            entry.hash.store(key, Ordering::Relaxed);
+        let generation = self.generation.load(Ordering::Relaxed);
+        let new = HashEntry::new(q, draw, visits, generation);
+        let bucket = self.bucket(hash);
+
+        let mut replace = &bucket.entries[0];
+        let mut lowest_worth = i32::MAX;
+
+        for slot in &bucket.entries {
+            if slot.is_empty() {
+                replace = slot;
+                break;
+            }
+
+            let (key, entry) = slot.load();
+
+            if key == hash {
+                if new.visits() >= entry.visits() || entry.generation() != generation {
+                    slot.store(hash, new);
+                }
+
+                return;
+            }
+
+            // each search since the entry was stored costs it as
+            // much as eight doublings of its visits are worth
+            let age = i32::from(generation.wrapping_sub(entry.generation()));
+            let worth = (u64::BITS - entry.visits().leading_zeros()) as i32 - 8 * age;
+
+            if worth < lowest_worth {
+                lowest_worth = worth;
+                replace = slot;
+            }
         }
+
+        replace.store(hash, new);
     }
 }
@@ -1,5 +1,6 @@
 use std::{
     convert::TryFrom,
+    io::{self, Read, Write},
     ops::{Add, AddAssign},
     sync::atomic::{AtomicU16, AtomicU64, AtomicU8, Ordering},
 };
@@ -8,7 +9,7 @@ use crate::chess::{GameState, Move};
 
 use super::lock::{CustomLock, WriteGuard};
 
-const QUANT: i32 = 16384 * 4;
+pub(super) const QUANT: i32 = 16384 * 4;
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct NodePtr(u64);
@@ -55,6 +56,71 @@ impl Add<usize> for NodePtr {
     }
 }
 
+/// A game result for the side to move, ordered from worst to best.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    Loss = 0,
+    Draw = 1,
+    Win = 2,
+}
+
+impl Outcome {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 3 {
+            0 => Self::Loss,
+            1 => Self::Draw,
+            _ => Self::Win,
+        }
+    }
+
+    /// The same result from the point of view of the other side.
+    pub fn flip(self) -> Self {
+        match self {
+            Self::Loss => Self::Win,
+            Self::Draw => Self::Draw,
+            Self::Win => Self::Loss,
+        }
+    }
+}
+
+/// Bounds on the result of a node for its side to move, which are
+/// tightened as the results of its children are proven, as in
+/// MCTS-Solver with score bounds (Cazenave & Saffidine 2010).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bounds {
+    pub lower: Outcome,
+    pub upper: Outcome,
+}
+
+impl Bounds {
+    pub const UNKNOWN: Self = Self::new(Outcome::Loss, Outcome::Win);
+
+    pub const fn new(lower: Outcome, upper: Outcome) -> Self {
+        Self { lower, upper }
+    }
+
+    pub fn exact(outcome: Outcome) -> Self {
+        Self::new(outcome, outcome)
+    }
+
+    /// The bounds from the point of view of the other side.
+    pub fn flip(self) -> Self {
+        Self::new(self.upper.flip(), self.lower.flip())
+    }
+
+    pub fn is_proven(self) -> bool {
+        self.lower == self.upper
+    }
+
+    fn to_bits(self) -> u8 {
+        self.lower as u8 | (self.upper as u8) << 2
+    }
+
+    fn from_bits(bits: u8) -> Self {
+        Self::new(Outcome::from_bits(bits), Outcome::from_bits(bits >> 2))
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default)]
 #[repr(align(64))]
 pub struct NodeStatsDelta {
@@ -104,6 +170,7 @@ pub struct Node {
     sum_sq_q: AtomicU64,
     draws: AtomicU64,
     gini_impurity: AtomicU8,
+    bounds: AtomicU8,
 }
 
 impl Node {
@@ -120,6 +187,7 @@ impl Node {
             sum_sq_q: AtomicU64::new(0),
             draws: AtomicU64::new(0),
             gini_impurity: AtomicU8::new(0),
+            bounds: AtomicU8::new(Bounds::UNKNOWN.to_bits()),
         }
     }
 
@@ -211,6 +279,21 @@ impl Node {
         self.state.store(u16::from(state), Ordering::Relaxed);
     }
 
+    /// The proven bounds on the result for the side to move,
+    /// which are exact once the node has a terminal state.
+    pub fn bounds(&self) -> Bounds {
+        match self.state() {
+            GameState::Ongoing => Bounds::from_bits(self.bounds.load(Ordering::Relaxed)),
+            GameState::Draw => Bounds::exact(Outcome::Draw),
+            GameState::Lost(_) => Bounds::exact(Outcome::Loss),
+            GameState::Won(_) => Bounds::exact(Outcome::Win),
+        }
+    }
+
+    pub fn set_bounds(&self, bounds: Bounds) {
+        self.bounds.store(bounds.to_bits(), Ordering::Relaxed);
+    }
+
     pub fn policy(&self) -> f32 {
         f32::from(self.policy.load(Ordering::Relaxed)) / f32::from(u16::MAX)
     }
@@ -257,15 +340,61 @@ impl Node {
         self.state.store(other.state.load(Relaxed), Relaxed);
         self.gini_impurity
             .store(other.gini_impurity.load(Relaxed), Relaxed);
+        self.bounds.store(other.bounds.load(Relaxed), Relaxed);
         self.visits.store(other.visits.load(Relaxed), Relaxed);
         self.sum_q.store(other.sum_q.load(Relaxed), Relaxed);
         self.sum_sq_q.store(other.sum_sq_q.load(Relaxed), Relaxed);
         self.draws.store(other.draws.load(Relaxed), Relaxed);
     }
 
+    /// Serialise the node, with `actions` being the index of its first
+    /// child in the saved tree (`u64::MAX` if there are none).
+    pub fn write_to(&self, out: &mut impl Write, actions: u64) -> io::Result<()> {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        out.write_all(&actions.to_le_bytes())?;
+        out.write_all(&[self.num_actions.load(Relaxed)])?;
+        out.write_all(&self.state.load(Relaxed).to_le_bytes())?;
+        out.write_all(&self.mov.load(Relaxed).to_le_bytes())?;
+        out.write_all(&self.policy.load(Relaxed).to_le_bytes())?;
+        out.write_all(&self.visits.load(Relaxed).to_le_bytes())?;
+        out.write_all(&self.sum_q.load(Relaxed).to_le_bytes())?;
+        out.write_all(&self.sum_sq_q.load(Relaxed).to_le_bytes())?;
+        out.write_all(&self.draws.load(Relaxed).to_le_bytes())?;
+        out.write_all(&[self.gini_impurity.load(Relaxed)])
+    }
+
+    /// Restore a node written by `write_to`, returning the saved index of
+    /// its first child. The actions pointer itself is left to the caller.
+    pub fn read_from(&self, inp: &mut impl Read) -> io::Result<u64> {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        let mut buf = [0; 48];
+        inp.read_exact(&mut buf)?;
+
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        let u64_at = |i: usize| u64::from_le_bytes(buf[i..i + 8].try_into().unwrap());
+
+        self.threads.store(0, Relaxed);
+        self.num_actions.store(buf[8], Relaxed);
+        self.state.store(u16_at(9), Relaxed);
+        self.mov.store(u16_at(11), Relaxed);
+        self.policy.store(u16_at(13), Relaxed);
+        self.visits.store(u64_at(15), Relaxed);
+        self.sum_q.store(u64_at(23), Relaxed);
+        self.sum_sq_q.store(u64_at(31), Relaxed);
+        self.draws.store(u64_at(39), Relaxed);
+        self.gini_impurity.store(buf[47], Relaxed);
+        // bounds short of a proven result are not saved
+        self.set_bounds(Bounds::UNKNOWN);
+
+        Ok(u64_at(0))
+    }
+
     pub fn clear(&self) {
         self.clear_actions();
         self.set_state(GameState::Ongoing);
+        self.set_bounds(Bounds::UNKNOWN);
         self.set_gini_impurity(0.0);
         self.visits.store(0, Ordering::Relaxed);
         self.sum_q.store(0, Ordering::Relaxed);
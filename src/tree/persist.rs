@@ -0,0 +1,247 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    sync::atomic::Ordering,
+};
+
+use super::{NodePtr, Tree};
+use crate::chess::ChessState;
+
+const MAGIC: &[u8; 8] = b"MONTYTRE";
+const VERSION: u32 = 2;
+
+const MAX_FEN_LEN: u32 = 128;
+
+/// Positions since the last irreversible move, which can't
+/// exceed the 100 plies after which the game is drawn.
+const MAX_STACK_LEN: u32 = 100;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Check that `fen` is well formed, as `ChessState::from_fen` assumes.
+fn check_fen(fen: &str) -> io::Result<()> {
+    let fields = fen.split_whitespace().collect::<Vec<_>>();
+
+    if !(4..=6).contains(&fields.len()) {
+        return Err(invalid("root fen does not have 4 to 6 fields"));
+    }
+
+    let ranks = fields[0].split('/').collect::<Vec<_>>();
+    let rank_len = |rank: &str| {
+        rank.chars()
+            .map(|ch| ch.to_digit(10).filter(|n| (1..=8).contains(n)).unwrap_or(1))
+            .sum::<u32>()
+    };
+
+    if ranks.len() != 8
+        || ranks.iter().any(|&rank| rank_len(rank) != 8)
+        || !fields[0]
+            .chars()
+            .all(|ch| "PNBRQKpnbrqk12345678/".contains(ch))
+    {
+        return Err(invalid("root fen has an invalid board"));
+    }
+
+    if fields[0].matches('K').count() != 1 || fields[0].matches('k').count() != 1 {
+        return Err(invalid("root fen does not have one king per side"));
+    }
+
+    if !["w", "b"].contains(&fields[1]) {
+        return Err(invalid("root fen has an invalid side to move"));
+    }
+
+    if fields[2] != "-"
+        && !fields[2]
+            .chars()
+            .all(|ch| "KQkqABCDEFGHabcdefgh".contains(ch))
+    {
+        return Err(invalid("root fen has invalid castling rights"));
+    }
+
+    let enp = fields[3].as_bytes();
+    if fields[3] != "-"
+        && !(enp.len() == 2 && (b'a'..=b'h').contains(&enp[0]) && [b'3', b'6'].contains(&enp[1]))
+    {
+        return Err(invalid("root fen has an invalid en passant square"));
+    }
+
+    Ok(())
+}
+
+fn read_u32(inp: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    inp.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(inp: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    inp.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+impl Tree {
+    /// Write the root position, every node reachable from the root and the
+    /// hash table to `path`, so that a later `load` can carry on searching
+    /// from the same visits. Must not be called whilst a search is running.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        self.flush_root_accumulator();
+
+        // lay the reachable nodes out breadth first, so that the children
+        // of each node remain contiguous once compacted into a single half
+        let mut order = vec![self.root_node()];
+        let mut actions = Vec::new();
+        let mut idx = 0;
+
+        while idx < order.len() {
+            let node = &self[order[idx]];
+            let first_child_ptr = node.actions();
+
+            if first_child_ptr.is_null() || !node.has_children() {
+                actions.push(u64::MAX);
+            } else {
+                actions.push(order.len() as u64);
+                order.extend((0..node.num_actions()).map(|action| first_child_ptr + action));
+            }
+
+            idx += 1;
+        }
+
+        let mut out = BufWriter::new(File::create(path)?);
+
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+
+        let fen = self.root.to_fen();
+        out.write_all(&(fen.len() as u32).to_le_bytes())?;
+        out.write_all(fen.as_bytes())?;
+
+        let stack = self.root.stack();
+        out.write_all(&(stack.len() as u32).to_le_bytes())?;
+        for hash in stack {
+            out.write_all(&hash.to_le_bytes())?;
+        }
+
+        out.write_all(&(order.len() as u64).to_le_bytes())?;
+        for (&ptr, &first_child) in order.iter().zip(&actions) {
+            self[ptr].write_to(&mut out, first_child)?;
+        }
+
+        self.hash.write_to(&mut out)?;
+
+        out.flush()
+    }
+
+    /// Replace the tree with one written by `save`, returning the number of
+    /// nodes restored. The hash table entries are only restored if the hash
+    /// table is the same size as when the tree was saved.
+    pub fn load(&mut self, path: &str, threads: usize) -> io::Result<usize> {
+        let mut inp = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 8];
+        inp.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a saved tree"));
+        }
+
+        let version = read_u32(&mut inp)?;
+        if version != VERSION {
+            return Err(invalid(&format!(
+                "unsupported tree version {version}, expected {VERSION}"
+            )));
+        }
+
+        let fen_len = read_u32(&mut inp)?;
+        if fen_len > MAX_FEN_LEN {
+            return Err(invalid("root fen is too long"));
+        }
+
+        let mut fen = vec![0; fen_len as usize];
+        inp.read_exact(&mut fen)?;
+        let fen = String::from_utf8(fen).map_err(|_| invalid("root fen is not valid utf-8"))?;
+        check_fen(&fen)?;
+
+        let stack_len = read_u32(&mut inp)?;
+        if stack_len > MAX_STACK_LEN {
+            return Err(invalid("root position history is too long"));
+        }
+
+        let stack = (0..stack_len)
+            .map(|_| read_u64(&mut inp))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let num_nodes = read_u64(&mut inp)? as usize;
+        if num_nodes == 0 {
+            return Err(invalid("saved tree has no root"));
+        }
+
+        if num_nodes > self.tree[0].nodes.len() {
+            return Err(invalid(&format!(
+                "saved tree has {num_nodes} nodes, but only {} fit in the current hash size",
+                self.tree[0].nodes.len()
+            )));
+        }
+
+        self.clear(threads);
+
+        let mut root = ChessState::from_fen(&fen);
+        root.set_stack(stack);
+        self.root = root;
+
+        self.half.store(false, Ordering::Relaxed);
+
+        if let Err(err) = self.load_nodes(&mut inp, num_nodes) {
+            self.clear(threads);
+            return Err(err);
+        }
+
+        match self.hash.read_from(&mut inp) {
+            Ok(true) => {}
+            Ok(false) => {
+                println!("info string hash size differs from saved tree, hash entries not restored")
+            }
+            Err(err) => {
+                self.clear(threads);
+                return Err(err);
+            }
+        }
+
+        self.reset_root_accumulator();
+
+        Ok(num_nodes)
+    }
+
+    fn load_nodes(&self, inp: &mut impl Read, num_nodes: usize) -> io::Result<()> {
+        let start = self.tree[0].reserve_nodes_thread(num_nodes, 0).unwrap();
+
+        for idx in 0..num_nodes {
+            let node = &self[start + idx];
+            let first_child = node.read_from(inp)?;
+
+            let actions = if first_child == u64::MAX {
+                node.set_num_actions(0);
+                NodePtr::NULL
+            } else {
+                let first_child = first_child as usize;
+
+                // nodes are saved breadth first, so children always follow
+                // their parent, and anything else would make a cycle
+                if first_child <= idx {
+                    return Err(invalid("saved tree has a child before its parent"));
+                }
+
+                if first_child.saturating_add(node.num_actions()) > num_nodes {
+                    return Err(invalid("saved tree has a child out of range"));
+                }
+
+                NodePtr::new(false, start.idx() + first_child)
+            };
+
+            node.actions_mut().store(actions);
+        }
+
+        Ok(())
+    }
+}
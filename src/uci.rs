@@ -1,13 +1,26 @@
 use crate::{
+    book::PolyglotBook,
     chess::{ChessState, Move},
-    mcts::{Limits, MctsParams, SearchHelpers, Searcher, REPORT_ITERS},
-    networks::{PolicyNetwork, ValueNetwork},
+    mcts::{
+        EvalBatch, Limits, MctsParams, SearchHelpers, Searcher, Selection, ThreadPool, INFO_JSON,
+        REPORT_ITERS, VERBOSE_MOVE_STATS,
+    },
+    memory::{self, Placed},
+    networks::{file::NetworkArch, NetworkFile, PolicyNetwork, ValueAccumulators, ValueNetwork},
+    syzygy::Tablebases,
     tree::Tree,
 };
 
+use montyformat::chess::perft::{check_epd, EpdEntry, PerftTable};
+
 use std::{
-    io, process,
-    sync::atomic::{AtomicBool, Ordering},
+    fs::File,
+    io::{self, BufRead, BufReader},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex,
+    },
     time::Instant,
 };
 
@@ -26,10 +39,34 @@ pub fn run(policy: &PolicyNetwork, value: &ValueNetwork, tcec_mode: bool) {
     let mut uci_rating_adv: Option<i32> = None;
     let mut contempt_override: Option<i32> = None;
     let mut contempt_analysis = false;
+    let mut tablebases: Option<Tablebases> = None;
+    let mut own_book = false;
+    let mut book: Option<PolyglotBook> = None;
+    let mut chess960 = false;
+    let mut selection = Selection::default();
+    let mut eval_batch = 1;
+    let mut perft_table = None;
+    let mut value_file: Option<NetworkFile<ValueNetwork>> = None;
+    let mut policy_file: Option<NetworkFile<PolicyNetwork>> = None;
+
+    // search threads are kept parked between searches
+    let pool = ThreadPool::default();
+
+    let mut placed_policy = Placed::default();
+    let mut placed_value = Placed::default();
 
     let mut stored_message: Option<String> = None;
 
     loop {
+        // networks loaded with `EvalFile` and `PolicyFile` replace the defaults
+        let policy = policy_file.as_ref().map_or(policy, NetworkFile::get);
+        let value = value_file.as_ref().map_or(value, NetworkFile::get);
+
+        // and are copied across the NUMA nodes with `NumaInterleave`
+        // SAFETY: the networks are made only of integers and floats
+        let policy = unsafe { placed_policy.get(policy) };
+        let value = unsafe { placed_value.get(value) };
+
         let input = if let Some(msg) = stored_message {
             msg.clone()
         } else {
@@ -64,8 +101,17 @@ pub fn run(policy: &PolicyNetwork, value: &ValueNetwork, tcec_mode: bool) {
                 &mut uci_rating_adv,
                 &mut contempt_override,
                 &mut contempt_analysis,
+                &mut tablebases,
+                &mut own_book,
+                &mut book,
+                &mut chess960,
+                &mut selection,
+                &mut eval_batch,
+                &mut value_file,
+                &mut policy_file,
+                &pool,
             ),
-            "position" => position(commands, &mut pos),
+            "position" => position(commands, &mut pos, chess960),
             "go" => {
                 // increment game ply every time `go` is called
                 root_game_ply += 2;
@@ -84,6 +130,11 @@ pub fn run(policy: &PolicyNetwork, value: &ValueNetwork, tcec_mode: bool) {
                     move_overhead,
                     gui_compatibility,
                     contempt_analysis,
+                    tablebases.as_ref(),
+                    book.as_ref().filter(|_| own_book),
+                    selection,
+                    eval_batch,
+                    &pool,
                     &mut stored_message,
                     #[cfg(feature = "datagen")]
                     1.0,
@@ -98,7 +149,16 @@ pub fn run(policy: &PolicyNetwork, value: &ValueNetwork, tcec_mode: bool) {
 
                 bench(depth, policy, value, &params);
             }
-            "perft" => run_perft(&commands, &pos),
+            "benchbatch" => {
+                let threads = commands
+                    .get(1)
+                    .and_then(|t| t.parse().ok())
+                    .unwrap_or(threads.max(2));
+
+                bench_batching(threads, policy, value, &params);
+            }
+            "benchvalue" => bench_value(value),
+            "perft" => run_perft(&commands, &pos, threads, hash_mb, &mut perft_table),
             "quit" => std::process::exit(0),
             "eval" => {
                 let breakdown = pos.eval_with_contempt(value, &params, pos.stm());
@@ -157,101 +217,240 @@ pub fn run(policy: &PolicyNetwork, value: &ValueNetwork, tcec_mode: bool) {
                 root_game_ply = 0;
                 tree.clear(threads);
             }
+            "savetree" => {
+                let path = commands[1..].join(" ");
+
+                match tree.save(&path) {
+                    Ok(()) => println!("info string saved tree to {path}"),
+                    Err(err) => println!("info string failed to save tree: {err}"),
+                }
+            }
+            "loadtree" => {
+                let path = commands[1..].join(" ");
+
+                match tree.load(&path, threads) {
+                    Ok(nodes) => {
+                        pos = tree.root_position().clone();
+                        println!("info string loaded tree with {nodes} nodes from {path}");
+                    }
+                    Err(err) => println!("info string failed to load tree: {err}"),
+                }
+            }
             _ => {}
         }
     }
 }
 
+const BENCH_FENS: [&str; 54] = [
+    "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq a6 0 14",
+    "4rrk1/2p1b1p1/p1p3q1/4p3/2P2n1p/1P1NR2P/PB3PP1/3R1QK1 b - - 2 24",
+    "r3qbrk/6p1/2b2pPp/p3pP1Q/PpPpP2P/3P1B2/2PB3K/R5R1 w - - 16 42",
+    "6k1/1R3p2/6p1/2Bp3p/3P2q1/P7/1P2rQ1K/5R2 b - - 4 44",
+    "8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54",
+    "7r/2p3k1/1p1p1qp1/1P1Bp3/p1P2r1P/P7/4R3/Q4RK1 w - - 0 36",
+    "r1bq1rk1/pp2b1pp/n1pp1n2/3P1p2/2P1p3/2N1P2N/PP2BPPP/R1BQ1RK1 b - - 2 10",
+    "3r3k/2r4p/1p1b3q/p4P2/P2Pp3/1B2P3/3BQ1RP/6K1 w - - 3 87",
+    "2r4r/1p4k1/1Pnp4/3Qb1pq/8/4BpPp/5P2/2RR1BK1 w - - 0 42",
+    "4q1bk/6b1/7p/p1p4p/PNPpP2P/KN4P1/3Q4/4R3 b - - 0 37",
+    "2q3r1/1r2pk2/pp3pp1/2pP3p/P1Pb1BbP/1P4Q1/R3NPP1/4R1K1 w - - 2 34",
+    "1r2r2k/1b4q1/pp5p/2pPp1p1/P3Pn2/1P1B1Q1P/2R3P1/4BR1K b - - 1 37",
+    "r3kbbr/pp1n1p1P/3ppnp1/q5N1/1P1pP3/P1N1B3/2P1QP2/R3KB1R b KQkq b3 0 17",
+    "8/6pk/2b1Rp2/3r4/1R1B2PP/P5K1/8/2r5 b - - 16 42",
+    "1r4k1/4ppb1/2n1b1qp/pB4p1/1n1BP1P1/7P/2PNQPK1/3RN3 w - - 8 29",
+    "8/p2B4/PkP5/4p1pK/4Pb1p/5P2/8/8 w - - 29 68",
+    "3r4/ppq1ppkp/4bnp1/2pN4/2P1P3/1P4P1/PQ3PBP/R4K2 b - - 2 20",
+    "5rr1/4n2k/4q2P/P1P2n2/3B1p2/4pP2/2N1P3/1RR1K2Q w - - 1 49",
+    "1r5k/2pq2p1/3p3p/p1pP4/4QP2/PP1R3P/6PK/8 w - - 1 51",
+    "q5k1/5ppp/1r3bn1/1B6/P1N2P2/BQ2P1P1/5K1P/8 b - - 2 34",
+    "r1b2k1r/5n2/p4q2/1ppn1Pp1/3pp1p1/NP2P3/P1PPBK2/1RQN2R1 w - - 0 22",
+    "r1bqk2r/pppp1ppp/5n2/4b3/4P3/P1N5/1PP2PPP/R1BQKB1R w KQkq - 0 5",
+    "r1bqr1k1/pp1p1ppp/2p5/8/3N1Q2/P2BB3/1PP2PPP/R3K2n b Q - 1 12",
+    "r1bq2k1/p4r1p/1pp2pp1/3p4/1P1B3Q/P2B1N2/2P3PP/4R1K1 b - - 2 19",
+    "r4qk1/6r1/1p4p1/2ppBbN1/1p5Q/P7/2P3PP/5RK1 w - - 2 25",
+    "r7/6k1/1p6/2pp1p2/7Q/8/p1P2K1P/8 w - - 0 32",
+    "r3k2r/ppp1pp1p/2nqb1pn/3p4/4P3/2PP4/PP1NBPPP/R2QK1NR w KQkq - 1 5",
+    "3r1rk1/1pp1pn1p/p1n1q1p1/3p4/Q3P3/2P5/PP1NBPPP/4RRK1 w - - 0 12",
+    "5rk1/1pp1pn1p/p3Brp1/8/1n6/5N2/PP3PPP/2R2RK1 w - - 2 20",
+    "8/1p2pk1p/p1p1r1p1/3n4/8/5R2/PP3PPP/4R1K1 b - - 3 27",
+    "8/4pk2/1p1r2p1/p1p4p/Pn5P/3R4/1P3PP1/4RK2 w - - 1 33",
+    "8/5k2/1pnrp1p1/p1p4p/P6P/4R1PK/1P3P2/4R3 b - - 1 38",
+    "8/8/1p1kp1p1/p1pr1n1p/P6P/1R4P1/1P3PK1/1R6 b - - 15 45",
+    "8/8/1p1k2p1/p1prp2p/P2n3P/6P1/1P1R1PK1/4R3 b - - 5 49",
+    "8/8/1p4p1/p1p2k1p/P2npP1P/4K1P1/1P6/3R4 w - - 6 54",
+    "8/8/1p4p1/p1p2k1p/P2n1P1P/4K1P1/1P6/6R1 b - - 6 59",
+    "8/5k2/1p4p1/p1pK3p/P2n1P1P/6P1/1P6/4R3 b - - 14 63",
+    "8/1R6/1p1K1kp1/p6p/P1p2P1P/6P1/1Pn5/8 w - - 0 67",
+    "1rb1rn1k/p3q1bp/2p3p1/2p1p3/2P1P2N/PP1RQNP1/1B3P2/4R1K1 b - - 4 23",
+    "4rrk1/pp1n1pp1/q5p1/P1pP4/2n3P1/7P/1P3PB1/R1BQ1RK1 w - - 3 22",
+    "r2qr1k1/pb1nbppp/1pn1p3/2ppP3/3P4/2PB1NN1/PP3PPP/R1BQR1K1 w - - 4 12",
+    "2r2k2/8/4P1R1/1p6/8/P4K1N/7b/2B5 b - - 0 55",
+    "6k1/5pp1/8/2bKP2P/2P5/p4PNb/B7/8 b - - 1 44",
+    "2rqr1k1/1p3p1p/p2p2p1/P1nPb3/2B1P3/5P2/1PQ2NPP/R1R4K w - - 3 25",
+    "r1b2rk1/p1q1ppbp/6p1/2Q5/8/4BP2/PPP3PP/2KR1B1R b - - 2 14",
+    "6r1/5k2/p1b1r2p/1pB1p1p1/1Pp3PP/2P1R1K1/2P2P2/3R4 w - - 1 36",
+    "rnbqkb1r/pppppppp/5n2/8/2PP4/8/PP2PPPP/RNBQKBNR b KQkq c3 0 2",
+    "2rr2k1/1p4bp/p1q1p1p1/4Pp1n/2PB4/1PN3P1/P3Q2P/2RR2K1 w - f6 0 20",
+    "3br1k1/p1pn3p/1p3n2/5pNq/2P1p3/1PN3PP/P2Q1PB1/4R1K1 w - - 0 23",
+    "2r2b2/5p2/5k2/p1r1pP2/P2pB3/1P3P2/K1P3R1/7R w - - 23 93",
+    "5k2/4q1p1/3P1pQb/1p1B4/pP5p/P1PR4/5PP1/1K6 b - - 0 38",
+    "5rk1/1rP3pp/p4n2/3Pp3/1P2Pq2/2Q4P/P5P1/R3R1K1 b - - 0 32",
+    "4r1k1/4r1p1/8/p2R1P1K/5P1P/1QP3q1/1P6/3R4 b - - 0 1",
+    "3qk1b1/1p4r1/1n4r1/2P1b2B/p3N2p/P2Q3P/8/1R3R1K w - - 2 39",
+];
+
 pub fn bench(depth: usize, policy: &PolicyNetwork, value: &ValueNetwork, params: &MctsParams) {
+    let (total_nodes, time) = bench_positions(depth, 1, None, policy, value, params);
+
+    println!(
+        "Bench: {total_nodes} nodes {:.0} nps",
+        total_nodes as f32 / time
+    );
+}
+
+/// Compare the speed of searching the bench positions on `threads` threads
+/// with and without batched evaluation, for each batch size up to `threads`.
+pub fn bench_batching(
+    threads: usize,
+    policy: &PolicyNetwork,
+    value: &ValueNetwork,
+    params: &MctsParams,
+) {
+    let mut size = 1;
+
+    while size <= threads {
+        let batch = (size > 1).then(|| EvalBatch::new(size));
+        let (total_nodes, time) = bench_positions(
+            ChessState::BENCH_DEPTH,
+            threads,
+            batch.as_ref(),
+            policy,
+            value,
+            params,
+        );
+
+        println!(
+            "Threads: {threads} Batch: {size} {total_nodes} nodes {:.0} nps",
+            total_nodes as f32 / time
+        );
+
+        size *= 2;
+    }
+}
+
+/// Compare the speed of evaluating positions with the value network from
+/// scratch against updating its accumulators along the line from the root,
+/// each step of which visits a new position branching from the last line
+/// as in the search.
+pub fn bench_value(value: &ValueNetwork) {
+    const LINES: usize = 1000;
+    const MAX_PLY: usize = 24;
+
+    let mut seed = 0x9e37_79b9_7f4a_7c15_u64;
+    let mut rand = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed as usize
+    };
+
+    let mut accs = ValueAccumulators::default();
+    let (mut evals, mut full, mut incremental) = (0, 0.0, 0.0);
+
+    for fen in BENCH_FENS {
+        let mut line = vec![ChessState::from_fen(fen)];
+
+        for _ in 0..LINES {
+            // step to a new position from near the end of the last line,
+            // starting again from the root once the line gets too long
+            let len = if line.len() < MAX_PLY {
+                line.len() - rand() % line.len().min(3)
+            } else {
+                1
+            };
+            line.truncate(len);
+
+            let pos = line.last().unwrap();
+            let mut moves = Vec::new();
+            pos.map_legal_moves(|mov| moves.push(mov));
+
+            if !moves.is_empty() {
+                let mut next = pos.clone();
+                next.make_move(moves[rand() % moves.len()]);
+                line.push(next);
+            }
+
+            let board = line.last().unwrap().board();
+
+            let timer = Instant::now();
+            std::hint::black_box(value.eval(&board));
+            full += timer.elapsed().as_secs_f32();
+
+            let timer = Instant::now();
+            for (ply, pos) in line.iter().enumerate() {
+                accs.push(ply, &pos.board());
+            }
+            std::hint::black_box(value.eval_incremental(&mut accs));
+            incremental += timer.elapsed().as_secs_f32();
+
+            evals += 1;
+        }
+    }
+
+    println!("Value: {evals} evals");
+    println!("Full: {:.0} nps", evals as f32 / full);
+    println!("Incremental: {:.0} nps", evals as f32 / incremental);
+}
+
+fn bench_positions(
+    depth: usize,
+    threads: usize,
+    batch: Option<&EvalBatch>,
+    policy: &PolicyNetwork,
+    value: &ValueNetwork,
+    params: &MctsParams,
+) -> (usize, f32) {
     let mut total_nodes = 0;
     let mut time = 0.0;
 
-    let bench_fens = [
-        "r3k2r/2pb1ppp/2pp1q2/p7/1nP1B3/1P2P3/P2N1PPP/R2QK2R w KQkq a6 0 14",
-        "4rrk1/2p1b1p1/p1p3q1/4p3/2P2n1p/1P1NR2P/PB3PP1/3R1QK1 b - - 2 24",
-        "r3qbrk/6p1/2b2pPp/p3pP1Q/PpPpP2P/3P1B2/2PB3K/R5R1 w - - 16 42",
-        "6k1/1R3p2/6p1/2Bp3p/3P2q1/P7/1P2rQ1K/5R2 b - - 4 44",
-        "8/8/1p2k1p1/3p3p/1p1P1P1P/1P2PK2/8/8 w - - 3 54",
-        "7r/2p3k1/1p1p1qp1/1P1Bp3/p1P2r1P/P7/4R3/Q4RK1 w - - 0 36",
-        "r1bq1rk1/pp2b1pp/n1pp1n2/3P1p2/2P1p3/2N1P2N/PP2BPPP/R1BQ1RK1 b - - 2 10",
-        "3r3k/2r4p/1p1b3q/p4P2/P2Pp3/1B2P3/3BQ1RP/6K1 w - - 3 87",
-        "2r4r/1p4k1/1Pnp4/3Qb1pq/8/4BpPp/5P2/2RR1BK1 w - - 0 42",
-        "4q1bk/6b1/7p/p1p4p/PNPpP2P/KN4P1/3Q4/4R3 b - - 0 37",
-        "2q3r1/1r2pk2/pp3pp1/2pP3p/P1Pb1BbP/1P4Q1/R3NPP1/4R1K1 w - - 2 34",
-        "1r2r2k/1b4q1/pp5p/2pPp1p1/P3Pn2/1P1B1Q1P/2R3P1/4BR1K b - - 1 37",
-        "r3kbbr/pp1n1p1P/3ppnp1/q5N1/1P1pP3/P1N1B3/2P1QP2/R3KB1R b KQkq b3 0 17",
-        "8/6pk/2b1Rp2/3r4/1R1B2PP/P5K1/8/2r5 b - - 16 42",
-        "1r4k1/4ppb1/2n1b1qp/pB4p1/1n1BP1P1/7P/2PNQPK1/3RN3 w - - 8 29",
-        "8/p2B4/PkP5/4p1pK/4Pb1p/5P2/8/8 w - - 29 68",
-        "3r4/ppq1ppkp/4bnp1/2pN4/2P1P3/1P4P1/PQ3PBP/R4K2 b - - 2 20",
-        "5rr1/4n2k/4q2P/P1P2n2/3B1p2/4pP2/2N1P3/1RR1K2Q w - - 1 49",
-        "1r5k/2pq2p1/3p3p/p1pP4/4QP2/PP1R3P/6PK/8 w - - 1 51",
-        "q5k1/5ppp/1r3bn1/1B6/P1N2P2/BQ2P1P1/5K1P/8 b - - 2 34",
-        "r1b2k1r/5n2/p4q2/1ppn1Pp1/3pp1p1/NP2P3/P1PPBK2/1RQN2R1 w - - 0 22",
-        "r1bqk2r/pppp1ppp/5n2/4b3/4P3/P1N5/1PP2PPP/R1BQKB1R w KQkq - 0 5",
-        "r1bqr1k1/pp1p1ppp/2p5/8/3N1Q2/P2BB3/1PP2PPP/R3K2n b Q - 1 12",
-        "r1bq2k1/p4r1p/1pp2pp1/3p4/1P1B3Q/P2B1N2/2P3PP/4R1K1 b - - 2 19",
-        "r4qk1/6r1/1p4p1/2ppBbN1/1p5Q/P7/2P3PP/5RK1 w - - 2 25",
-        "r7/6k1/1p6/2pp1p2/7Q/8/p1P2K1P/8 w - - 0 32",
-        "r3k2r/ppp1pp1p/2nqb1pn/3p4/4P3/2PP4/PP1NBPPP/R2QK1NR w KQkq - 1 5",
-        "3r1rk1/1pp1pn1p/p1n1q1p1/3p4/Q3P3/2P5/PP1NBPPP/4RRK1 w - - 0 12",
-        "5rk1/1pp1pn1p/p3Brp1/8/1n6/5N2/PP3PPP/2R2RK1 w - - 2 20",
-        "8/1p2pk1p/p1p1r1p1/3n4/8/5R2/PP3PPP/4R1K1 b - - 3 27",
-        "8/4pk2/1p1r2p1/p1p4p/Pn5P/3R4/1P3PP1/4RK2 w - - 1 33",
-        "8/5k2/1pnrp1p1/p1p4p/P6P/4R1PK/1P3P2/4R3 b - - 1 38",
-        "8/8/1p1kp1p1/p1pr1n1p/P6P/1R4P1/1P3PK1/1R6 b - - 15 45",
-        "8/8/1p1k2p1/p1prp2p/P2n3P/6P1/1P1R1PK1/4R3 b - - 5 49",
-        "8/8/1p4p1/p1p2k1p/P2npP1P/4K1P1/1P6/3R4 w - - 6 54",
-        "8/8/1p4p1/p1p2k1p/P2n1P1P/4K1P1/1P6/6R1 b - - 6 59",
-        "8/5k2/1p4p1/p1pK3p/P2n1P1P/6P1/1P6/4R3 b - - 14 63",
-        "8/1R6/1p1K1kp1/p6p/P1p2P1P/6P1/1Pn5/8 w - - 0 67",
-        "1rb1rn1k/p3q1bp/2p3p1/2p1p3/2P1P2N/PP1RQNP1/1B3P2/4R1K1 b - - 4 23",
-        "4rrk1/pp1n1pp1/q5p1/P1pP4/2n3P1/7P/1P3PB1/R1BQ1RK1 w - - 3 22",
-        "r2qr1k1/pb1nbppp/1pn1p3/2ppP3/3P4/2PB1NN1/PP3PPP/R1BQR1K1 w - - 4 12",
-        "2r2k2/8/4P1R1/1p6/8/P4K1N/7b/2B5 b - - 0 55",
-        "6k1/5pp1/8/2bKP2P/2P5/p4PNb/B7/8 b - - 1 44",
-        "2rqr1k1/1p3p1p/p2p2p1/P1nPb3/2B1P3/5P2/1PQ2NPP/R1R4K w - - 3 25",
-        "r1b2rk1/p1q1ppbp/6p1/2Q5/8/4BP2/PPP3PP/2KR1B1R b - - 2 14",
-        "6r1/5k2/p1b1r2p/1pB1p1p1/1Pp3PP/2P1R1K1/2P2P2/3R4 w - - 1 36",
-        "rnbqkb1r/pppppppp/5n2/8/2PP4/8/PP2PPPP/RNBQKBNR b KQkq c3 0 2",
-        "2rr2k1/1p4bp/p1q1p1p1/4Pp1n/2PB4/1PN3P1/P3Q2P/2RR2K1 w - f6 0 20",
-        "3br1k1/p1pn3p/1p3n2/5pNq/2P1p3/1PN3PP/P2Q1PB1/4R1K1 w - - 0 23",
-        "2r2b2/5p2/5k2/p1r1pP2/P2pB3/1P3P2/K1P3R1/7R w - - 23 93",
-        "5k2/4q1p1/3P1pQb/1p1B4/pP5p/P1PR4/5PP1/1K6 b - - 0 38",
-        "5rk1/1rP3pp/p4n2/3Pp3/1P2Pq2/2Q4P/P5P1/R3R1K1 b - - 0 32",
-        "4r1k1/4r1p1/8/p2R1P1K/5P1P/1QP3q1/1P6/3R4 b - - 0 1",
-        "3qk1b1/1p4r1/1n4r1/2P1b2B/p3N2p/P2Q3P/8/1R3R1K w - - 2 39",
-    ];
-
     let limits = Limits {
         max_time: None,
         opt_time: None,
         max_depth: depth,
         max_nodes: 1_000_000,
+        mate: None,
         #[cfg(feature = "datagen")]
         kld_min_gain: None,
     };
 
-    let mut tree = Tree::new_mb(32, 1);
+    let mut tree = Tree::new_mb(32, threads);
 
-    for fen in bench_fens {
+    for fen in BENCH_FENS {
         let abort = AtomicBool::new(false);
         let pos = ChessState::from_fen(fen);
         tree.set_root_position(&pos);
-        let searcher = Searcher::new(&tree, params, policy, value, &abort);
+        let mut searcher = Searcher::new(&tree, params, policy, value, &abort);
+        if let Some(batch) = batch {
+            searcher = searcher.with_batching(batch);
+        }
         let timer = Instant::now();
         #[cfg(not(feature = "datagen"))]
-        searcher.search(1, limits, false, 1, false, &mut total_nodes);
+        searcher.search(threads, limits, false, 1, false, &mut total_nodes);
         #[cfg(feature = "datagen")]
-        searcher.search(1, limits, false, 1, false, &mut total_nodes, false, 1.0);
+        searcher.search(
+            threads,
+            limits,
+            false,
+            1,
+            false,
+            &mut total_nodes,
+            false,
+            1.0,
+        );
         time += timer.elapsed().as_secs_f32();
-        tree.clear(1);
+        tree.clear(threads);
     }
 
-    println!(
-        "Bench: {total_nodes} nodes {:.0} nps",
-        total_nodes as f32 / time
-    );
+    (total_nodes, time)
 }
 
 fn preamble(tcec_mode: bool) {
@@ -260,10 +459,30 @@ fn preamble(tcec_mode: bool) {
     println!("option name Hash type spin default 64 min 1 max 524288");
     println!("option name Threads type spin default 1 min 1 max 512");
     println!("option name UCI_Chess960 type check default false");
+    println!("option name Ponder type check default false");
+    println!("option name SyzygyPath type string default <empty>");
+    println!("option name OwnBook type check default false");
+    println!("option name BookFile type string default <empty>");
+    println!("option name EvalFile type string default <empty>");
+    println!("option name PolicyFile type string default <empty>");
     println!("option name Contempt_Analysis type check default false");
     println!("option name MoveOverhead type spin default 400 min 0 max 5000");
     println!("option name MultiPV type spin default 1 min 1 max 10");
     println!("option name GUI_Compatibility type check default true");
+    let selections = Selection::ALL.map(|selection| format!(" var {}", selection.name()));
+    println!(
+        "option name SelectionPolicy type combo default {}{}",
+        Selection::default().name(),
+        selections.concat()
+    );
+    println!("option name InfoFormat type combo default uci var uci var json");
+    println!("option name VerboseMoveStats type check default false");
+    println!("option name EvalBatch type spin default 1 min 1 max 64");
+    println!("option name MCGS type check default false");
+    println!("option name LargePages type check default true");
+    println!("option name NumaInterleave type check default false");
+    #[cfg(target_os = "linux")]
+    println!("option name ThreadAffinity type check default false");
     println!("option name report_moves type button");
     println!("option name report_iters type button");
     if tcec_mode {
@@ -278,6 +497,43 @@ fn preamble(tcec_mode: bool) {
     println!("uciok");
 }
 
+/// Replace the network in `slot` with the one at `path`, or revert to the
+/// default network if `path` is empty. A file that fails to load is reported
+/// and leaves the current network in place.
+fn load_network<T: NetworkArch>(
+    slot: &mut Option<NetworkFile<T>>,
+    path: &str,
+    tree: &mut Tree,
+    threads: usize,
+) {
+    if path.is_empty() || path == "<empty>" {
+        if slot.take().is_some() {
+            println!("info string using default {} network", T::NAME);
+            tree.clear(threads);
+        }
+
+        return;
+    }
+
+    match NetworkFile::open(path) {
+        Ok(loaded) => {
+            println!(
+                "info string loaded {} network {path} (sha {})",
+                T::NAME,
+                loaded.sha_prefix()
+            );
+            *slot = Some(loaded);
+
+            // the tree holds evaluations from the previous network
+            tree.clear(threads);
+        }
+        Err(err) => println!(
+            "info string failed to load {} network {path}: {err}",
+            T::NAME
+        ),
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 fn setoption(
     commands: &[&str],
@@ -293,6 +549,15 @@ fn setoption(
     uci_rating_adv: &mut Option<i32>,
     contempt_override: &mut Option<i32>,
     disable_tree_reuse: &mut bool,
+    tablebases: &mut Option<Tablebases>,
+    own_book: &mut bool,
+    book: &mut Option<PolyglotBook>,
+    chess960: &mut bool,
+    selection: &mut Selection,
+    eval_batch: &mut usize,
+    value_file: &mut Option<NetworkFile<ValueNetwork>>,
+    policy_file: &mut Option<NetworkFile<PolicyNetwork>>,
+    pool: &ThreadPool,
 ) {
     let Some((name, value)) = parse_name_value(commands) else {
         return;
@@ -305,12 +570,121 @@ fn setoption(
         "report_iters" => {
             REPORT_ITERS.fetch_xor(true, Ordering::Relaxed);
         }
-        "UCI_Chess960" => {}
+        "Ponder" => {}
+        "UCI_Chess960" => {
+            if let Some(v) = value {
+                *chess960 = v.eq_ignore_ascii_case("true");
+            }
+        }
+        "VerboseMoveStats" => {
+            if let Some(v) = value {
+                VERBOSE_MOVE_STATS.store(v.eq_ignore_ascii_case("true"), Ordering::Relaxed);
+            }
+        }
+        "SelectionPolicy" => {
+            if let Some(v) = value {
+                if let Some(parsed) = Selection::parse(&v) {
+                    *selection = parsed;
+                }
+            }
+        }
+        "EvalBatch" => {
+            if let Some(v) = value.and_then(|v| v.parse::<usize>().ok()) {
+                *eval_batch = v.clamp(1, 64);
+            }
+        }
+        "MCGS" => {
+            if let Some(v) = value {
+                tree.set_graph(v.eq_ignore_ascii_case("true"), *threads);
+            }
+        }
+        "LargePages" | "NumaInterleave" => {
+            if let Some(v) = value {
+                let enabled = v.eq_ignore_ascii_case("true");
+
+                if name == "LargePages" {
+                    memory::set_large_pages(enabled);
+                } else {
+                    memory::set_numa_interleave(enabled);
+                }
+
+                // reallocate so that the new setting takes effect
+                let root = tree.root_position().clone();
+                tree.rebuild(*hash_mb, *threads, root);
+                println!("info string hash using {}", tree.placement());
+            }
+        }
+        "ThreadAffinity" => {
+            if let Some(v) = value {
+                let pinned = v.eq_ignore_ascii_case("true");
+
+                if !pool.set_affinity(pinned) && pinned {
+                    println!("info string thread affinity is not supported");
+                }
+            }
+        }
+        "InfoFormat" => {
+            if let Some(v) = value {
+                INFO_JSON.store(v.eq_ignore_ascii_case("json"), Ordering::Relaxed);
+            }
+        }
         "Contempt_Analysis" => {
             if let Some(v) = value {
                 *disable_tree_reuse = v.eq_ignore_ascii_case("true");
             }
         }
+        "SyzygyPath" => {
+            if let Some(v) = value {
+                *tablebases = None;
+
+                if !v.is_empty() && v != "<empty>" {
+                    let loaded = Tablebases::new(&v);
+                    println!(
+                        "info string found {} tablebases (up to {} pieces)",
+                        loaded.num_tables(),
+                        loaded.max_pieces()
+                    );
+
+                    if loaded.num_tables() > 0 {
+                        *tablebases = Some(loaded);
+                    }
+                }
+            }
+        }
+        "OwnBook" => {
+            if let Some(v) = value {
+                *own_book = v.eq_ignore_ascii_case("true");
+            }
+        }
+        "BookFile" => {
+            if let Some(v) = value {
+                *book = None;
+
+                if !v.is_empty() && v != "<empty>" {
+                    match PolyglotBook::open(&v) {
+                        Ok(loaded) => {
+                            println!(
+                                "info string loaded book with {} entries",
+                                loaded.num_entries()
+                            );
+                            *book = Some(loaded);
+                        }
+                        Err(err) => println!("info string failed to load book: {err}"),
+                    }
+                }
+            }
+        }
+        // `ValueNet` and `PolicyNet` are accepted as aliases
+        "EvalFile" | "ValueNet" => {
+            if let Some(v) = value {
+                load_network(value_file, &v, tree, *threads);
+            }
+        }
+        "PolicyFile" | "PolicyNet" => {
+            if let Some(v) = value {
+                load_network(policy_file, &v, tree, *threads);
+            }
+        }
         "Threads" => {
             if let Some(v) = value {
                 if let Ok(parsed) = v.parse::<usize>() {
@@ -454,7 +828,7 @@ fn apply_uci_contempt(
     }
 }
 
-fn position(commands: Vec<&str>, pos: &mut ChessState) {
+fn position(commands: Vec<&str>, pos: &mut ChessState, chess960: bool) {
     let mut fen = String::new();
     let mut move_list = Vec::new();
     let mut moves = false;
@@ -475,6 +849,7 @@ fn position(commands: Vec<&str>, pos: &mut ChessState) {
     }
 
     *pos = ChessState::from_fen(&fen);
+    pos.set_chess960(chess960);
 
     for &m in move_list.iter() {
         let mut this_mov = Move::default();
@@ -504,6 +879,11 @@ fn go(
     move_overhead: usize,
     gui_compatibility: bool,
     disable_tree_reuse: bool,
+    tablebases: Option<&Tablebases>,
+    book: Option<&PolyglotBook>,
+    selection: Selection,
+    eval_batch: usize,
+    pool: &ThreadPool,
     stored_message: &mut Option<String>,
     #[cfg(feature = "datagen")] temp: f32,
 ) {
@@ -515,6 +895,10 @@ fn go(
     let mut incs = [None; 2];
     let mut movestogo = None;
     let mut opt_time = None;
+    let mut infinite = false;
+    let mut ponder = false;
+    let mut search_moves = Vec::new();
+    let mut mate = None;
 
     let mut mode = "";
 
@@ -530,6 +914,10 @@ fn go(
             "winc" => mode = "winc",
             "binc" => mode = "binc",
             "movestogo" => mode = "movestogo",
+            "infinite" => infinite = true,
+            "ponder" => ponder = true,
+            "searchmoves" => mode = "searchmoves",
+            "mate" => mode = "mate",
             _ => match mode {
                 "nodes" => max_nodes = cmd.parse().unwrap_or(max_nodes),
                 "movetime" => max_time = cmd.parse().ok(),
@@ -539,6 +927,12 @@ fn go(
                 "winc" => incs[0] = saturating_parse(cmd),
                 "binc" => incs[1] = saturating_parse(cmd),
                 "movestogo" => movestogo = saturating_parse(cmd),
+                "mate" => mate = cmd.parse().ok(),
+                "searchmoves" => pos.map_legal_moves(|mov| {
+                    if *cmd == pos.conv_mov_to_str(mov) {
+                        search_moves.push(mov);
+                    }
+                }),
                 _ => mode = "none",
             },
         }
@@ -562,26 +956,73 @@ fn go(
         max_time = Some(max_time.unwrap_or(u128::MAX).min(max));
     }
 
+    // `bestmove` can only be sent straight away outside of infinite and ponder mode
+    if !infinite && !ponder {
+        if let Some(mov) = book.and_then(|book| book.probe(pos)) {
+            if search_moves.is_empty() || search_moves.contains(&mov) {
+                println!("info string book move");
+                println!("bestmove {}", pos.conv_mov_to_str(mov));
+                return;
+            }
+        }
+    }
+
     let abort = AtomicBool::new(false);
+    let stopped = AtomicBool::new(false);
+    let pondering = AtomicBool::new(ponder);
+    // signalled when `stop` or `ponderhit` may release `bestmove`
+    let release = (Mutex::new(()), Condvar::new());
 
     if disable_tree_reuse {
         tree.clear(threads);
     }
 
-    tree.set_root_position(pos);
+    // only search the moves keeping the best result in the tablebases
+    if let Some(moves) = tablebases.and_then(|tb| tb.root_moves(pos, &search_moves)) {
+        search_moves = moves;
+    }
+
+    if !tree.is_empty() {
+        println!("info string searching for subtree");
+
+        if tree.set_root_position(pos) {
+            println!("info string found subtree");
+        } else {
+            println!("info string no subtree found");
+        }
+    } else {
+        tree.set_root_position(pos);
+    }
+
+    tree.set_search_moves(&search_moves);
 
     let limits = Limits {
         max_time,
         opt_time,
         max_depth,
         max_nodes,
+        mate,
         #[cfg(feature = "datagen")]
         kld_min_gain: None,
     };
 
-    std::thread::scope(|s| {
+    let batch = (eval_batch > 1).then(|| EvalBatch::new(eval_batch));
+
+    pool.scope(|s| {
         s.spawn(|| {
-            let searcher = Searcher::new(tree, params, policy, value, &abort);
+            let mut searcher = Searcher::new(tree, params, policy, value, &abort)
+                .with_ponder(&pondering)
+                .with_selection(selection.policy())
+                .with_pool(pool);
+
+            if let Some(tablebases) = tablebases {
+                searcher = searcher.with_tablebases(tablebases);
+            }
+
+            if let Some(batch) = &batch {
+                searcher = searcher.with_batching(batch);
+            }
+
             let mov = searcher
                 .search(
                     threads,
@@ -596,31 +1037,177 @@ fn go(
                     temp,
                 )
                 .0;
-            println!("bestmove {}", pos.conv_mov_to_str(mov));
+
+            if let Some(moves) = mate {
+                if !searcher
+                    .proven_mate()
+                    .is_some_and(|found| found > 0 && found as usize <= moves)
+                {
+                    println!("info string no mate in {moves} found");
+                }
+            }
+
+            // in infinite and ponder mode `bestmove` may only be
+            // sent after `stop` or `ponderhit`, even if the search
+            // has ended early (e.g. the root is a proven mate)
+            let (lock, released) = &release;
+            let mut guard = lock.lock().unwrap();
+            while !stopped.load(Ordering::Relaxed)
+                && (infinite || pondering.load(Ordering::Relaxed))
+            {
+                guard = released.wait(guard).unwrap();
+            }
+            drop(guard);
+
+            print!("bestmove {}", pos.conv_mov_to_str(mov));
+
+            if let Some(ponder_mov) = searcher.ponder_move() {
+                let mut child = pos.clone();
+                child.make_move(mov);
+                print!(" ponder {}", child.conv_mov_to_str(ponder_mov));
+            }
+
+            println!();
 
             if report_moves {
                 searcher.display_moves();
             }
         });
 
-        *stored_message = handle_search_input(&abort);
+        *stored_message = handle_search_input(&abort, &stopped, &pondering, &release, infinite);
     });
 }
 
-fn run_perft(commands: &[&str], pos: &ChessState) {
-    let depth = commands[1].parse().unwrap();
-    let root_pos = pos.clone();
+/// `perft <depth> [expected]` or `perft epd <file> [max depth]`, using the
+/// `Threads` and `Hash` options. Exits with a non-zero status on a mismatch,
+/// so that suites can be checked by piping commands in.
+/// `table` is kept for the session, with its size in MB, as allocating it
+/// can take longer than the perft itself. Its entries are keyed on the
+/// castling rules too, so it is never cleared.
+fn run_perft(
+    commands: &[&str],
+    pos: &ChessState,
+    threads: usize,
+    hash_mb: usize,
+    table: &mut Option<(usize, PerftTable)>,
+) {
+    // the tree already holds `Hash` worth of memory, so don't go overboard
+    let mb = hash_mb.min(1024);
+
+    let table = match table {
+        Some((size, table)) if *size == mb => &*table,
+        _ => &table.insert((mb, PerftTable::new(mb))).1,
+    };
+
+    if commands.get(1) == Some(&"epd") {
+        let Some(path) = commands.get(2) else {
+            println!("info string perft epd needs a file");
+            return;
+        };
+
+        let max_depth = commands
+            .get(3)
+            .and_then(|d| d.parse().ok())
+            .unwrap_or(u8::MAX);
+        run_perft_suite(path, max_depth, threads, table);
+        return;
+    }
+
+    let Some(depth) = commands.get(1).and_then(|d| d.parse::<usize>().ok()) else {
+        println!("info string perft needs a depth");
+        return;
+    };
+
     let now = Instant::now();
-    let count = root_pos.perft(depth);
-    let time = now.elapsed().as_micros();
+    let divide = pos.perft_divide(depth.max(1), threads, Some(table));
+    let time = now.elapsed().as_micros().max(1);
+
+    for &(mov, count) in &divide {
+        println!("{}: {count}", pos.conv_mov_to_str(mov));
+    }
+
+    let count = divide.iter().map(|&(_, count)| count).sum::<u64>();
     println!(
         "perft {depth} time {} nodes {count} ({:.2} Mnps)",
         time / 1000,
         count as f32 / time as f32
     );
+
+    if let Some(expected) = commands.get(2).and_then(|e| e.parse::<u64>().ok()) {
+        if count != expected {
+            println!("info string perft mismatch, expected {expected} found {count}");
+            process::exit(1);
+        }
+    }
+}
+
+fn run_perft_suite(path: &str, max_depth: u8, threads: usize, table: &PerftTable) {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            println!("info string failed to open {path}: {err}");
+            return;
+        }
+    };
+
+    let mut positions = 0;
+    let mut failed = 0;
+
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        let Some(entry) = EpdEntry::parse(&line) else {
+            continue;
+        };
+
+        positions += 1;
+
+        let mismatches = match check_epd(&entry, max_depth, threads, Some(table)) {
+            Ok(mismatches) => mismatches,
+            Err(err) => {
+                println!("info string bad entry {}: {err}", entry.fen);
+                failed += 1;
+                continue;
+            }
+        };
+
+        if !mismatches.is_empty() {
+            failed += 1;
+        }
+
+        for mismatch in mismatches {
+            println!(
+                "info string mismatch {} depth {} expected {} found {}",
+                entry.fen, mismatch.depth, mismatch.expected, mismatch.found
+            );
+        }
+    }
+
+    println!("info string perft suite {positions} positions {failed} failed");
+
+    if failed > 0 {
+        process::exit(1);
+    }
 }
 
-fn handle_search_input(abort: &AtomicBool) -> Option<String> {
+fn handle_search_input(
+    abort: &AtomicBool,
+    stopped: &AtomicBool,
+    pondering: &AtomicBool,
+    release: &(Mutex<()>, Condvar),
+    infinite: bool,
+) -> Option<String> {
+    // taking the lock ensures a waiting `bestmove` has either seen
+    // the new state already or is waiting and will be woken
+    let notify = || {
+        let _guard = release.0.lock().unwrap();
+        release.1.notify_all();
+    };
+
+    let stop = || {
+        stopped.store(true, Ordering::Relaxed);
+        abort.store(true, Ordering::Relaxed);
+        notify();
+    };
+
     loop {
         let mut input = String::new();
         let bytes_read = io::stdin().read_line(&mut input).unwrap();
@@ -633,10 +1220,22 @@ fn handle_search_input(abort: &AtomicBool) -> Option<String> {
             "isready" => println!("readyok"),
             "quit" => std::process::exit(0),
             "stop" => {
-                abort.store(true, Ordering::Relaxed);
+                stop();
                 return None;
             }
-            _ => return Some(input),
+            "ponderhit" => {
+                pondering.store(false, Ordering::Relaxed);
+                notify();
+            }
+            _ => {
+                // a search that cannot finish by itself would never
+                // return, so treat any other command as an implicit stop
+                if infinite || pondering.load(Ordering::Relaxed) {
+                    stop();
+                }
+
+                return Some(input);
+            }
         };
     }
 }
@@ -0,0 +1,52 @@
+mod common;
+
+use common::Rand;
+use monty::networks::Accumulator;
+
+const N: usize = 256;
+const INPUTS: usize = 64;
+
+fn weights() -> Vec<Accumulator<i8, N>> {
+    let mut rand = Rand(0x9E37_79B9_7F4A_7C15);
+
+    (0..INPUTS)
+        .map(|_| Accumulator(std::array::from_fn(|_| rand.below(17) as i8 - 8)))
+        .collect()
+}
+
+#[test]
+fn batched_first_layer_matches_single() {
+    let weights = weights();
+    let biases = Accumulator(std::array::from_fn(|i| (i % 7) as i16 - 3));
+
+    // overlapping and disjoint feature sets, including an empty one
+    let features: [&[usize]; 4] = [&[0, 5, 9, 63], &[5, 9, 10, 11, 12], &[], &[1, 2, 3, 63]];
+
+    let mut batched = vec![biases; features.len()];
+    Accumulator::add_multi_i8_batch(&mut batched, &features, &weights);
+
+    for (acc, feats) in batched.iter().zip(features) {
+        let mut single = biases;
+        single.add_multi_i8(feats, &weights);
+
+        assert_eq!(acc.0, single.0);
+    }
+}
+
+#[test]
+fn incremental_update_matches_refresh() {
+    let weights = weights();
+    let biases = Accumulator(std::array::from_fn(|i| (i % 5) as i16 - 2));
+
+    let old: &[usize] = &[3, 8, 13, 21, 34, 55];
+    let new: &[usize] = &[3, 8, 14, 21, 40, 55, 60];
+
+    let mut incremental = biases;
+    incremental.add_multi_i8(old, &weights);
+    incremental.add_sub_i8(&[14, 40, 60], &[13, 34], &weights);
+
+    let mut refreshed = biases;
+    refreshed.add_multi_i8(new, &weights);
+
+    assert_eq!(incremental.0, refreshed.0);
+}
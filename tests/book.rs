@@ -0,0 +1,114 @@
+use std::{env, fs};
+
+use monty::{book::PolyglotBook, chess::ChessState};
+
+/// Keys from the Polyglot book format specification.
+const KEYS: [(&str, u64); 9] = [
+    (
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        0x463b_9618_1691_fc9c,
+    ),
+    (
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+        0x823c_9b50_fd11_4196,
+    ),
+    (
+        "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2",
+        0x0756_b944_61c5_0fb0,
+    ),
+    (
+        "rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+        0x662f_afb9_65db_29d4,
+    ),
+    (
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        0x22a4_8b5a_8e47_ff78,
+    ),
+    (
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR b kq - 0 3",
+        0x652a_607c_a3f2_42c1,
+    ),
+    (
+        "rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/RNBQ1BNR w - - 0 4",
+        0x00fd_d303_c946_bdd9,
+    ),
+    (
+        "rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/RNBQKBNR b KQkq c3 0 3",
+        0x3c81_23ea_7b06_7637,
+    ),
+    (
+        "rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/1NBQKBNR b Kkq - 0 4",
+        0x5c3f_9b82_9b27_9560,
+    ),
+];
+
+#[test]
+fn matches_published_keys() {
+    for (fen, key) in KEYS {
+        let pos = ChessState::from_fen(fen);
+        assert_eq!(PolyglotBook::key(&pos.board()), key, "{fen}");
+    }
+}
+
+#[test]
+fn keys_follow_moves() {
+    let mut pos = ChessState::from_fen(KEYS[0].0);
+
+    for (mov, (_, key)) in ["e2e4", "d7d5", "e4e5", "f7f5", "e1e2", "e8f7"]
+        .into_iter()
+        .zip(&KEYS[1..])
+    {
+        let mut found = None;
+        pos.map_legal_moves(|m| {
+            if pos.conv_mov_to_str(m) == mov {
+                found = Some(m);
+            }
+        });
+
+        pos.make_move(found.unwrap());
+        assert_eq!(PolyglotBook::key(&pos.board()), *key, "{mov}");
+    }
+}
+
+#[test]
+fn finds_moves_by_key() {
+    let pos = ChessState::from_fen(KEYS[0].0);
+    let after = ChessState::from_fen(KEYS[1].0);
+
+    // e2e4 and d2d4 from the start, then d7d5 after e2e4
+    let mut book = Vec::new();
+    for (key, mov, weight) in [
+        (KEYS[0].1, (12u16 << 6) | 28, 3u16),
+        (KEYS[0].1, (11 << 6) | 27, 1),
+        (KEYS[1].1, (51 << 6) | 35, 1),
+    ] {
+        book.extend(key.to_be_bytes());
+        book.extend(mov.to_be_bytes());
+        book.extend(weight.to_be_bytes());
+        book.extend(0u32.to_be_bytes());
+    }
+
+    let path = env::temp_dir().join(format!("monty-book-{}.bin", std::process::id()));
+    fs::write(&path, book).unwrap();
+
+    let book = PolyglotBook::open(path.to_str().unwrap()).unwrap();
+
+    let name = |pos: &ChessState| {
+        let mut moves = book
+            .moves(pos)
+            .into_iter()
+            .map(|(mov, weight)| (pos.conv_mov_to_str(mov), weight))
+            .collect::<Vec<_>>();
+        moves.sort();
+        moves
+    };
+
+    assert_eq!(
+        name(&pos),
+        [("d2d4".to_string(), 1), ("e2e4".to_string(), 3)]
+    );
+    assert_eq!(name(&after), [("d7d5".to_string(), 1)]);
+
+    drop(book);
+    fs::remove_file(&path).unwrap();
+}
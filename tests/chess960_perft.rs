@@ -0,0 +1,75 @@
+use monty::chess::ChessState;
+
+/// Positions from the standard Chess960 perft suite, with
+/// the node counts at depths 1 to 4.
+const SUITE: [(&str, [u64; 4]); 6] = [
+    (
+        "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        [21, 528, 12189, 326672],
+    ),
+    (
+        "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+        [21, 807, 18002, 667366],
+    ),
+    (
+        "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+        [20, 479, 10471, 273318],
+    ),
+    (
+        "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+        [22, 593, 13440, 382958],
+    ),
+    (
+        "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+        [28, 1120, 31058, 1171749],
+    ),
+    (
+        "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9",
+        [29, 899, 26578, 824055],
+    ),
+];
+
+#[test]
+fn chess960_perft() {
+    for (fen, counts) in SUITE {
+        let pos = ChessState::from_fen(fen);
+
+        for (depth, &count) in counts.iter().enumerate() {
+            assert_eq!(pos.perft(depth + 1), count, "{fen} depth {}", depth + 1);
+        }
+    }
+}
+
+#[test]
+fn xfen_matches_shredder_fen() {
+    // in each suite position the castling rooks are the outermost ones
+    const XFEN_RIGHTS: [&str; 6] = ["KQkq", "KQkq", "KQ", "kq", "KQkq", "KQkq"];
+
+    for ((fen, counts), rights) in SUITE.into_iter().zip(XFEN_RIGHTS) {
+        let mut fields = fen.split_whitespace().collect::<Vec<_>>();
+        fields[2] = rights;
+
+        let pos = ChessState::from_fen(&fields.join(" "));
+
+        assert_eq!(pos.perft(3), counts[2], "{fen} as X-FEN");
+    }
+}
+
+#[test]
+fn chess960_castling_notation() {
+    let mut pos = ChessState::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+
+    let moves = |pos: &ChessState| {
+        let mut moves = Vec::new();
+        pos.map_legal_moves(|mov| moves.push(pos.conv_mov_to_str(mov)));
+        moves
+    };
+
+    assert!(moves(&pos).contains(&"e1g1".to_string()));
+    assert!(moves(&pos).contains(&"e1c1".to_string()));
+
+    pos.set_chess960(true);
+
+    assert!(moves(&pos).contains(&"e1h1".to_string()));
+    assert!(moves(&pos).contains(&"e1a1".to_string()));
+}
@@ -0,0 +1,125 @@
+use std::sync::{atomic::AtomicBool, Mutex};
+
+use monty::{
+    boxed_and_zeroed,
+    chess::ChessState,
+    mcts::{Limits, MctsParams, Score, SearchInfo, Searcher},
+    networks::{PolicyNetwork, ValueNetwork},
+    tree::Tree,
+};
+
+/// Hashes differing only in their lowest bits, which share a bucket.
+const BASE: u64 = 0x1234_5678_9ABC_DE00;
+
+fn assert_close(a: f32, b: f32) {
+    assert!((a - b).abs() < 1e-4, "{a} != {b}");
+}
+
+#[test]
+fn accumulates_visits_to_a_position() {
+    let mut tree = Tree::new_mb(1, 1);
+
+    // nothing is stored without graph search
+    tree.push_graph(BASE, 0.75, 0.25);
+    assert!(tree.probe_graph(BASE).is_none());
+
+    tree.set_graph(true, 1);
+    assert!(tree.probe_graph(BASE).is_none());
+
+    tree.push_graph(BASE, 0.75, 0.25);
+    tree.push_graph(BASE, 0.25, 0.75);
+
+    let entry = tree.probe_graph(BASE).unwrap();
+    assert_eq!(entry.visits(), 2);
+    assert_close(entry.q(), 0.5);
+    assert_close(entry.d(), 0.5);
+
+    tree.clear(1);
+    assert!(tree.probe_graph(BASE).is_none());
+}
+
+#[test]
+fn keeps_the_most_visited_positions() {
+    let mut tree = Tree::new_mb(1, 1);
+    tree.set_graph(true, 1);
+
+    tree.push_graph(BASE, 0.75, 0.25);
+    tree.push_graph(BASE, 0.75, 0.25);
+
+    // fill the rest of the bucket, then replace one of the single visits
+    for i in 1..=4 {
+        tree.push_graph(BASE ^ i, 0.25, 0.0);
+    }
+
+    let entry = tree.probe_graph(BASE).unwrap();
+    assert_eq!(entry.visits(), 2);
+    assert_close(entry.q(), 0.75);
+    assert_close(entry.d(), 0.25);
+
+    let entry = tree.probe_graph(BASE ^ 4).unwrap();
+    assert_eq!(entry.visits(), 1);
+    assert_close(entry.q(), 0.25);
+    assert_close(entry.d(), 0.0);
+
+    assert_eq!(
+        (1..4)
+            .filter(|&i| tree.probe_graph(BASE ^ i).is_some())
+            .count(),
+        2
+    );
+
+    // the table starts empty when graph search is enabled again
+    tree.set_graph(false, 1);
+    tree.set_graph(true, 1);
+    assert!(tree.probe_graph(BASE).is_none());
+}
+
+fn search(fen: &str, graph: bool, nodes: usize) -> SearchInfo {
+    // SAFETY: the networks are made only of integers and floats
+    let policy: Box<PolicyNetwork> = unsafe { boxed_and_zeroed() };
+    let value: Box<ValueNetwork> = unsafe { boxed_and_zeroed() };
+
+    let mut tree = Tree::new_mb(8, 1);
+    tree.set_graph(graph, 1);
+    tree.set_root_position(&ChessState::from_fen(fen));
+
+    let params = MctsParams::default();
+    let abort = AtomicBool::new(false);
+    let report = Mutex::new(None);
+    let progress = |info: &SearchInfo| *report.lock().unwrap() = Some(info.clone());
+
+    let limits = Limits {
+        max_nodes: nodes,
+        ..Limits::default()
+    };
+
+    Searcher::new(&tree, &params, &policy, &value, &abort)
+        .with_progress(&progress)
+        .search(
+            1,
+            limits,
+            false,
+            1,
+            false,
+            &mut 0,
+            #[cfg(feature = "datagen")]
+            false,
+            #[cfg(feature = "datagen")]
+            1.0,
+        );
+
+    report.into_inner().unwrap().unwrap()
+}
+
+#[test]
+fn matches_the_tree_with_transpositions() {
+    // the king and rook reach the same positions by many move orders
+    let fen = "k7/8/8/1K6/8/8/8/7R w - - 0 1";
+
+    let tree = search(fen, false, 5000);
+    let graph = search(fen, true, 5000);
+
+    assert_eq!(tree.lines[0].score, Score::Mate(2));
+    assert_eq!(graph.lines[0].score, tree.lines[0].score);
+    assert_eq!(graph.lines[0].moves[0], tree.lines[0].moves[0]);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::Rand;
+use monty::tree::Tree;
+
+/// Hashes differing only in their lowest bits, which share a bucket.
+const BASE: u64 = 0x1234_5678_9ABC_DE00;
+
+#[test]
+fn stores_and_verifies_full_hash() {
+    let tree = Tree::new_mb(1, 1);
+
+    tree.push_hash(BASE, 0.75, 0.25, 10);
+
+    let entry = tree.probe_hash(BASE).unwrap();
+    assert!((entry.q() - 0.75).abs() < 1e-4);
+    assert!((entry.d() - 0.25).abs() < 1e-4);
+
+    // same bucket and upper bits, different position
+    assert!(tree.probe_hash(BASE ^ 1).is_none());
+
+    // fewer visits don't replace an entry from the same search
+    tree.push_hash(BASE, 0.5, 0.5, 5);
+    assert!((tree.probe_hash(BASE).unwrap().q() - 0.75).abs() < 1e-4);
+
+    tree.push_hash(BASE, 0.5, 0.5, 20);
+    assert!((tree.probe_hash(BASE).unwrap().q() - 0.5).abs() < 1e-4);
+}
+
+#[test]
+fn prefers_replacing_stale_entries() {
+    let tree = Tree::new_mb(1, 1);
+
+    for i in 0..3 {
+        tree.push_hash(BASE ^ i, 0.5, 0.0, 1000);
+    }
+
+    tree.age_hash();
+
+    // fills the bucket, with fewer visits than the older entries
+    tree.push_hash(BASE ^ 3, 0.5, 0.0, 2);
+    tree.push_hash(BASE ^ 4, 0.5, 0.0, 2);
+
+    assert!(tree.probe_hash(BASE ^ 3).is_some());
+    assert!(tree.probe_hash(BASE ^ 4).is_some());
+    assert_eq!(
+        (0..3)
+            .filter(|&i| tree.probe_hash(BASE ^ i).is_some())
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn reports_hashfull_of_current_search() {
+    let tree = Tree::new_mb(1, 1);
+    assert_eq!(tree.hashfull(), 0);
+
+    let mut rand = Rand(0x9E37_79B9_7F4A_7C15);
+    for _ in 0..100_000 {
+        tree.push_hash(rand.next(), 0.5, 0.0, 1);
+    }
+
+    let full = tree.hashfull();
+    assert!(full > 500 && full <= 1000, "hashfull {full}");
+
+    // entries from previous searches no longer count
+    tree.age_hash();
+    assert_eq!(tree.hashfull(), 0);
+}
@@ -0,0 +1,52 @@
+use std::{ptr, sync::Arc};
+
+use monty::memory::{self, LargeBuffer, Placed};
+
+#[test]
+fn initialises_every_element() {
+    let buffer = LargeBuffer::new(100_003, 4, || 7u32);
+
+    assert_eq!(buffer.len(), 100_003);
+    assert!(buffer.iter().all(|&x| x == 7));
+    assert_eq!(buffer.as_ptr() as usize % 4096, 0);
+    assert!(!buffer.placement().is_empty());
+}
+
+#[test]
+fn empty_buffers_are_allowed() {
+    let mut buffer = LargeBuffer::new(0, 4, || 0u64);
+
+    assert!(buffer.is_empty());
+    assert!(buffer.iter_mut().next().is_none());
+}
+
+#[test]
+fn drops_elements_with_the_buffer() {
+    let value = Arc::new(());
+
+    let buffer = LargeBuffer::new(10, 2, || value.clone());
+    assert_eq!(Arc::strong_count(&value), 11);
+
+    drop(buffer);
+    assert_eq!(Arc::strong_count(&value), 1);
+}
+
+#[test]
+fn copies_only_while_interleaving() {
+    let weights = [[1.5f32; 256]; 64];
+    let other = [[2.5f32; 256]; 64];
+    let mut placed = Placed::default();
+
+    memory::set_numa_interleave(true);
+
+    let copy = unsafe { placed.get(&weights) };
+    assert!(!ptr::eq(copy, &weights));
+    assert_eq!(copy, &weights);
+
+    // a different network is copied again
+    let copy = unsafe { placed.get(&other) };
+    assert_eq!(copy, &other);
+
+    memory::set_numa_interleave(false);
+    assert!(ptr::eq(unsafe { placed.get(&weights) }, &weights));
+}
@@ -0,0 +1,117 @@
+use std::{fs, path::PathBuf};
+
+use monty::networks::{
+    file::{write_network, NetworkArch, NetworkHeader, HEADER_SIZE},
+    NetworkError, NetworkFile,
+};
+
+#[repr(C)]
+struct TinyNetwork {
+    weights: [i16; 32],
+}
+
+impl NetworkArch for TinyNetwork {
+    const NAME: &'static str = "tiny";
+    const ARCH: u32 = u32::from_le_bytes(*b"TINY");
+    const LAYERS: [u32; 4] = [32, 0, 0, 0];
+    const QA: u32 = 255;
+    const QB: u32 = 64;
+}
+
+struct OtherNetwork;
+
+impl NetworkArch for OtherNetwork {
+    const NAME: &'static str = "other";
+    const ARCH: u32 = u32::from_le_bytes(*b"OTHR");
+    const LAYERS: [u32; 4] = [32, 0, 0, 0];
+    const QA: u32 = 255;
+    const QB: u32 = 64;
+}
+
+fn payload() -> Vec<u8> {
+    (0..32i16)
+        .flat_map(|x| (x * 3 - 40).to_le_bytes())
+        .collect()
+}
+
+/// A file in the temporary directory, removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, bytes: &[u8]) -> Self {
+        let path = std::env::temp_dir().join(format!("monty-{}-{name}", std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        Self(path)
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn header_round_trips() {
+    let header = NetworkHeader::new::<TinyNetwork>(&payload());
+    let bytes = header.to_bytes();
+
+    assert_eq!(bytes.len(), HEADER_SIZE);
+    assert_eq!(NetworkHeader::parse(&bytes), Some(header));
+    assert!(header.validate::<TinyNetwork>(&payload()).is_ok());
+}
+
+#[test]
+fn loads_with_and_without_header() {
+    let mut file = Vec::new();
+    write_network::<TinyNetwork>(&mut file, &payload()).unwrap();
+
+    let file = TempFile::new("headered", &file);
+    let net = NetworkFile::<TinyNetwork>::open(file.path()).unwrap();
+    assert!(net.header().is_some());
+    assert_eq!(net.get().weights[1], -37);
+
+    let file = TempFile::new("legacy", &payload());
+    let net = NetworkFile::<TinyNetwork>::open(file.path()).unwrap();
+    assert!(net.header().is_none());
+    assert_eq!(net.get().weights[31], 53);
+}
+
+#[test]
+fn rejects_mismatched_networks() {
+    let header = NetworkHeader::new::<TinyNetwork>(&payload());
+
+    let mut corrupt = payload();
+    corrupt[7] ^= 1;
+    assert!(matches!(
+        header.validate::<TinyNetwork>(&corrupt),
+        Err(NetworkError::BadChecksum)
+    ));
+
+    assert!(matches!(
+        header.validate::<OtherNetwork>(&payload()),
+        Err(NetworkError::WrongArch { .. })
+    ));
+
+    let file = TempFile::new("truncated", &payload()[..40]);
+    assert!(matches!(
+        NetworkFile::<TinyNetwork>::open(file.path()),
+        Err(NetworkError::WrongSize {
+            expected: 64,
+            found: 40
+        })
+    ));
+}
+
+#[test]
+fn reports_sha_prefix_of_file() {
+    let file = TempFile::new("sha", &payload());
+    let net = NetworkFile::<TinyNetwork>::open(file.path()).unwrap();
+
+    // `sha256sum` of the payload
+    assert_eq!(net.sha_prefix(), "4d60df260564");
+}
@@ -0,0 +1,78 @@
+use monty::chess::ChessState;
+use montyformat::chess::{
+    perft::{check_epd, EpdEntry, PerftMismatch, PerftTable},
+    STARTPOS,
+};
+
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+#[test]
+fn parallel_hashed_perft_matches_serial() {
+    let table = PerftTable::new(1);
+
+    for fen in [STARTPOS, KIWIPETE] {
+        let pos = ChessState::from_fen(fen);
+
+        for depth in 1..=4 {
+            let serial = pos.perft(depth);
+
+            for threads in [1, 4] {
+                for table in [None, Some(&table)] {
+                    let divide = pos.perft_divide(depth, threads, table);
+                    let total = divide.iter().map(|&(_, count)| count).sum::<u64>();
+                    assert_eq!(total, serial, "{fen} depth {depth} threads {threads}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn parses_epd_lines() {
+    let entry = EpdEntry::parse(&format!("{KIWIPETE} ;D1 48 ;D2 2039")).unwrap();
+    assert_eq!(entry.fen, KIWIPETE);
+    assert_eq!(entry.counts, vec![(1, 48), (2, 2039)]);
+
+    assert_eq!(EpdEntry::parse(""), None);
+    assert_eq!(EpdEntry::parse("# comment"), None);
+    assert_eq!(EpdEntry::parse(&format!("{KIWIPETE} ;D1 x")), None);
+}
+
+#[test]
+fn reports_epd_mismatches() {
+    let entry = EpdEntry::parse(&format!("{KIWIPETE} ;D1 48 ;D2 2040 ;D3 97862")).unwrap();
+
+    assert_eq!(
+        check_epd(&entry, 3, 2, None).unwrap(),
+        vec![PerftMismatch {
+            depth: 2,
+            expected: 2040,
+            found: 2039
+        }]
+    );
+
+    assert!(check_epd(&entry, 1, 2, None).unwrap().is_empty());
+}
+
+#[test]
+fn counts_the_position_at_depth_zero() {
+    let entry = EpdEntry::parse(&format!("{KIWIPETE} ;D0 1 ;D1 48")).unwrap();
+    assert!(check_epd(&entry, 1, 2, None).unwrap().is_empty());
+}
+
+#[test]
+fn rejects_invalid_epd_positions() {
+    for fen in [
+        "8/8/8/8/8/8/8/8 w - - 0 1",
+        "8/8/8/8/8/8/8/K6k w",
+        "9/8/8/8/8/8/8/K6k w - - 0 1",
+        "8/8/8/8/8/8/8/K5xk w - - 0 1",
+        "8/8/8/8/8/8/8/K6k x - - 0 1",
+        "8/8/8/8/8/8/8/K6k w - z9 0 1",
+        "8/8/8/8/8/8/8/K6k w K - 0 1",
+        "k6R/8/8/8/8/8/8/K7 w - - 0 1",
+    ] {
+        let entry = EpdEntry::parse(&format!("{fen} ;D1 1")).unwrap();
+        assert!(check_epd(&entry, 1, 1, None).is_err(), "{fen}");
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::{atomic::AtomicBool, Mutex};
+
+use monty::{
+    boxed_and_zeroed,
+    chess::{ChessState, GameState, Move},
+    mcts::{Limits, MctsParams, Score, Searcher},
+    networks::{PolicyNetwork, ValueNetwork},
+    syzygy::TB_DISTANCE,
+    tree::{Bounds, NodePtr, Outcome, Tree},
+};
+
+/// A root with `states.len()` children in the given states.
+fn root_with_children(tree: &Tree, states: &[GameState]) -> (NodePtr, NodePtr) {
+    let root = tree.push_new_node().unwrap();
+    tree[root].clear();
+
+    let first = tree.push_new_node().unwrap();
+    for (i, &state) in states.iter().enumerate() {
+        let ptr = if i == 0 {
+            first
+        } else {
+            tree.push_new_node().unwrap()
+        };
+
+        assert_eq!(ptr, first + i);
+        tree[ptr].set_new(Move::from(i as u16 + 1), 0.0);
+        tree[ptr].set_state(state);
+    }
+
+    tree[root].actions_mut().store(first);
+    tree[root].set_num_actions(states.len());
+
+    (root, first)
+}
+
+#[test]
+fn tablebase_distances_saturate() {
+    let tree = Tree::new_mb(1, 1);
+
+    let (root, first) = root_with_children(&tree, &[GameState::Lost(u8::MAX)]);
+    tree.propogate_proven_mates(root, first);
+    assert_eq!(tree[root].state(), GameState::Won(u8::MAX));
+
+    let (root, first) = root_with_children(
+        &tree,
+        &[GameState::Won(TB_DISTANCE), GameState::Won(u8::MAX)],
+    );
+    tree.propogate_proven_mates(root, first);
+    assert_eq!(tree[root].state(), GameState::Lost(u8::MAX));
+}
+
+#[test]
+fn children_no_better_than_draws_prove_a_draw() {
+    let tree = Tree::new_mb(1, 1);
+
+    // a draw, or a move into a position won for the opponent
+    let (root, first) = root_with_children(&tree, &[GameState::Won(3), GameState::Draw]);
+    tree.propogate_proven_mates(root, first + 1);
+    assert_eq!(tree[root].state(), GameState::Draw);
+
+    // an unknown child may still win
+    let (root, first) = root_with_children(&tree, &[GameState::Draw, GameState::Ongoing]);
+    tree.propogate_proven_mates(root, first);
+    assert_eq!(tree[root].state(), GameState::Ongoing);
+    assert_eq!(
+        tree[root].bounds(),
+        Bounds::new(Outcome::Draw, Outcome::Win)
+    );
+
+    // until it is shown to be no better than a draw either
+    tree[first + 1].set_bounds(Bounds::new(Outcome::Draw, Outcome::Win));
+    tree.propogate_proven_mates(root, first + 1);
+    assert_eq!(tree[root].state(), GameState::Draw);
+}
+
+#[test]
+fn children_that_cannot_improve_are_pruned() {
+    let tree = Tree::new_mb(1, 1);
+
+    let (root, first) = root_with_children(&tree, &[GameState::Draw, GameState::Ongoing]);
+    let (drawn, unknown) = (first, first + 1);
+
+    // nothing is pruned until the parent has a guaranteed result
+    assert!(!Tree::is_pruned(&tree[root], &tree[unknown]));
+    assert!(!Tree::is_pruned(&tree[root], &tree[drawn]));
+
+    tree.propogate_proven_mates(root, drawn);
+    assert!(Tree::is_pruned(&tree[root], &tree[drawn]));
+    assert!(!Tree::is_pruned(&tree[root], &tree[unknown]));
+
+    // the opponent can hold a draw, so no better than the draw already found
+    tree[unknown].set_bounds(Bounds::new(Outcome::Draw, Outcome::Win));
+    assert!(Tree::is_pruned(&tree[root], &tree[unknown]));
+}
+
+#[test]
+fn proven_draws_are_reported_as_zero() {
+    // the only move takes the queen, leaving bare kings
+    let pos = ChessState::from_fen("8/8/8/8/5k2/8/6q1/7K w - - 0 1");
+
+    // SAFETY: the networks are made only of integers and floats
+    let policy: Box<PolicyNetwork> = unsafe { boxed_and_zeroed() };
+    let value: Box<ValueNetwork> = unsafe { boxed_and_zeroed() };
+
+    let mut tree = Tree::new_mb(1, 1);
+    tree.set_root_position(&pos);
+
+    let params = MctsParams::default();
+    let abort = AtomicBool::new(false);
+    let reports = Mutex::new(Vec::new());
+    let progress = |info: &monty::mcts::SearchInfo| reports.lock().unwrap().push(info.clone());
+
+    let limits = Limits {
+        max_nodes: 100,
+        ..Limits::default()
+    };
+
+    Searcher::new(&tree, &params, &policy, &value, &abort)
+        .with_progress(&progress)
+        .search(
+            1,
+            limits,
+            false,
+            1,
+            false,
+            &mut 0,
+            #[cfg(feature = "datagen")]
+            false,
+            #[cfg(feature = "datagen")]
+            1.0,
+        );
+
+    assert_eq!(tree[tree.root_node()].state(), GameState::Draw);
+
+    let reports = reports.into_inner().unwrap();
+    let last = reports.last().unwrap();
+    assert_eq!(last.lines[0].score, Score::Cp(0));
+    assert_eq!(last.wdl, [0.0, 1.0, 0.0]);
+}
@@ -0,0 +1,149 @@
+use std::{env, fs, io};
+
+use monty::tree::Tree;
+
+const STARTPOS: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// A saved tree with the given root and history, but no nodes.
+fn header(fen: &[u8], stack_len: u32) -> Vec<u8> {
+    let mut bytes = b"MONTYTRE".to_vec();
+    bytes.extend(2u32.to_le_bytes());
+    bytes.extend((fen.len() as u32).to_le_bytes());
+    bytes.extend(fen);
+    bytes.extend(stack_len.to_le_bytes());
+    bytes.extend((0..u64::from(stack_len.min(100))).flat_map(u64::to_le_bytes));
+    bytes.extend(0u64.to_le_bytes());
+    bytes
+}
+
+/// A saved tree from the start position with the given nodes, as
+/// `(first child, number of children)`, but no hash table.
+fn with_nodes(nodes: &[(u64, u8)]) -> Vec<u8> {
+    let mut bytes = header(STARTPOS.as_bytes(), 0);
+    bytes.truncate(bytes.len() - 8);
+    bytes.extend((nodes.len() as u64).to_le_bytes());
+
+    for &(first_child, num_actions) in nodes {
+        bytes.extend(first_child.to_le_bytes());
+        bytes.push(num_actions);
+        bytes.extend([0; 39]);
+    }
+
+    bytes
+}
+
+fn load_into(tree: &mut Tree, name: &str, bytes: &[u8]) -> io::Result<usize> {
+    let path = env::temp_dir().join(format!("monty-{name}-{}.tree", std::process::id()));
+    fs::write(&path, bytes).unwrap();
+
+    let result = tree.load(path.to_str().unwrap(), 1);
+    fs::remove_file(&path).unwrap();
+
+    result
+}
+
+fn load(name: &str, bytes: &[u8]) -> io::Error {
+    load_into(&mut Tree::new_mb(1, 1), name, bytes).unwrap_err()
+}
+
+#[test]
+fn rejects_corrupt_files() {
+    // a well formed header only fails for having no nodes
+    let err = load("valid", &header(STARTPOS.as_bytes(), 100));
+    assert_eq!(err.to_string(), "saved tree has no root");
+
+    for (name, bytes, msg) in [
+        ("magic", b"MONTYTRA".to_vec(), "not a saved tree"),
+        ("long-fen", header(&[b'8'; 129], 0), "root fen is too long"),
+        ("utf8", header(&[0xFF; 8], 0), "root fen is not valid utf-8"),
+        (
+            "fields",
+            header(b"8/8/8/8/8/8/8/8 w", 0),
+            "root fen does not have 4 to 6 fields",
+        ),
+        (
+            "ranks",
+            header(b"8/8/8/8/8/8/8 w - - 0 1", 0),
+            "root fen has an invalid board",
+        ),
+        (
+            "files",
+            header(b"9/8/8/8/8/8/8/K6k w - - 0 1", 0),
+            "root fen has an invalid board",
+        ),
+        (
+            "pieces",
+            header(b"8/8/8/8/8/8/8/K5xk w - - 0 1", 0),
+            "root fen has an invalid board",
+        ),
+        (
+            "kings",
+            header(b"8/8/8/8/8/8/8/K6K w - - 0 1", 0),
+            "root fen does not have one king per side",
+        ),
+        (
+            "stm",
+            header(b"8/8/8/8/8/8/8/K6k x - - 0 1", 0),
+            "root fen has an invalid side to move",
+        ),
+        (
+            "castling",
+            header(b"8/8/8/8/8/8/8/K6k w KX - 0 1", 0),
+            "root fen has invalid castling rights",
+        ),
+        (
+            "enp",
+            header(b"8/8/8/8/8/8/8/K6k w - e9 0 1", 0),
+            "root fen has an invalid en passant square",
+        ),
+        (
+            "stack",
+            header(STARTPOS.as_bytes(), 101),
+            "root position history is too long",
+        ),
+    ] {
+        assert_eq!(load(name, &bytes).to_string(), msg, "{name}");
+    }
+
+    // truncated part way through the root
+    let bytes = header(STARTPOS.as_bytes(), 0);
+    let err = load("truncated", &bytes[..20]);
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn rejects_cycles_between_nodes() {
+    for (name, nodes, msg) in [
+        (
+            "self",
+            vec![(0, 1)],
+            "saved tree has a child before its parent",
+        ),
+        (
+            "parent",
+            vec![(1, 1), (0, 1)],
+            "saved tree has a child before its parent",
+        ),
+        (
+            "range",
+            vec![(1, 2), (u64::MAX, 0)],
+            "saved tree has a child out of range",
+        ),
+        (
+            "overflow",
+            vec![(u64::MAX - 1, 2)],
+            "saved tree has a child out of range",
+        ),
+    ] {
+        assert_eq!(load(name, &with_nodes(&nodes)).to_string(), msg, "{name}");
+    }
+}
+
+#[test]
+fn clears_the_tree_if_the_hash_table_is_missing() {
+    let mut tree = Tree::new_mb(1, 1);
+
+    let err = load_into(&mut tree, "no-hash", &with_nodes(&[(1, 1), (u64::MAX, 0)])).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(tree.is_empty());
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use common::Rand;
+use monty::networks::{
+    common::simd::{self, Level, Supported},
+    Accumulator,
+};
+
+const N: usize = 512;
+const ROWS: usize = 32;
+
+fn supported() -> impl Iterator<Item = Supported> {
+    Level::ALL
+        .into_iter()
+        .filter(|&level| level != Level::Scalar)
+        .filter_map(Level::supported)
+}
+
+#[test]
+fn add_sub_matches_scalar() {
+    let mut rand = Rand(0x2545_F491_4F6C_DD1D);
+
+    let weights_i8 = (0..ROWS)
+        .map(|_| Accumulator(std::array::from_fn(|_| rand.next() as i8)))
+        .collect::<Vec<Accumulator<i8, N>>>();
+    let weights_i16 = (0..ROWS)
+        .map(|_| Accumulator(std::array::from_fn(|_| rand.next() as i16)))
+        .collect::<Vec<Accumulator<i16, N>>>();
+    let start: [i16; N] = std::array::from_fn(|_| rand.next() as i16);
+
+    // large values, so that wrapping is exercised too
+    let adds = [0, 3, 3, 17, 31];
+    let subs = [1, 17, 30];
+
+    for level in supported() {
+        for range in [0..N, 128..384] {
+            let mut expected = start;
+            let mut actual = start;
+            simd::add_sub_i8(
+                Supported::SCALAR,
+                &mut expected,
+                range.clone(),
+                &adds,
+                &subs,
+                &weights_i8,
+            );
+            simd::add_sub_i8(level, &mut actual, range.clone(), &adds, &subs, &weights_i8);
+            assert_eq!(actual, expected, "add_sub_i8 {}", level.level().name());
+
+            let mut expected = start;
+            let mut actual = start;
+            simd::add_sub_i16(
+                Supported::SCALAR,
+                &mut expected,
+                range.clone(),
+                &adds,
+                &subs,
+                &weights_i16,
+            );
+            simd::add_sub_i16(level, &mut actual, range, &adds, &subs, &weights_i16);
+            assert_eq!(actual, expected, "add_sub_i16 {}", level.level().name());
+        }
+    }
+}
+
+fn check_dot_products<const LEN: usize>(rand: &mut Rand) {
+    let a_i8: [i8; LEN] = std::array::from_fn(|_| rand.next() as i8);
+    let a_i16: [i16; LEN] = std::array::from_fn(|_| rand.next() as i16);
+    let b: [i16; LEN] = std::array::from_fn(|_| rand.next() as i16);
+
+    for level in supported() {
+        assert_eq!(
+            simd::dot_i8_i16(level, &a_i8, &b),
+            simd::dot_i8_i16(Supported::SCALAR, &a_i8, &b),
+            "dot_i8_i16 {} len {LEN}",
+            level.level().name()
+        );
+
+        assert_eq!(
+            simd::dot_i16_i16(level, &a_i16, &b),
+            simd::dot_i16_i16(Supported::SCALAR, &a_i16, &b),
+            "dot_i16_i16 {} len {LEN}",
+            level.level().name()
+        );
+    }
+}
+
+#[test]
+fn dot_products_match_scalar() {
+    let mut rand = Rand(0x2545_F491_4F6C_DD1D);
+
+    // include lengths which leave a tail after the vector loop
+    check_dot_products::<0>(&mut rand);
+    check_dot_products::<7>(&mut rand);
+    check_dot_products::<64>(&mut rand);
+    check_dot_products::<100>(&mut rand);
+    check_dot_products::<4096>(&mut rand);
+}
+
+#[test]
+fn detected_level_is_supported() {
+    assert!(simd::level().level().is_supported());
+}
@@ -0,0 +1,242 @@
+#[path = "syzygy/generate.rs"]
+mod generate;
+
+use std::path::Path;
+
+use monty::{
+    chess::{ChessState, GameState},
+    syzygy::{Tablebases, Wdl},
+};
+use montyformat::chess::{Castling, Piece, Position, Side};
+
+const TABLES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/syzygy");
+
+fn tablebases() -> Tablebases {
+    let tablebases = Tablebases::new(TABLES);
+    assert_eq!(tablebases.num_tables(), 5);
+    assert_eq!(tablebases.max_pieces(), 3);
+    tablebases
+}
+
+fn parse(fen: &str) -> Position {
+    Position::parse_fen(fen, &mut Castling::default())
+}
+
+/// The same position with the colours swapped.
+fn mirror(pos: &Position) -> Position {
+    let fen = ["8/8/8/8/8/8/8/8 b - - 0 1", "8/8/8/8/8/8/8/8 w - - 0 1"][pos.stm()];
+    let mut mirrored = parse(fen);
+
+    for side in [Side::WHITE, Side::BLACK] {
+        for piece in Piece::PAWN..=Piece::KING {
+            let mut bb = pos.piece(side) & pos.piece(piece);
+
+            while bb > 0 {
+                let sq = bb.trailing_zeros() as u16;
+                mirrored.toggle(side ^ 1, piece, sq ^ 56);
+                bb &= bb - 1;
+            }
+        }
+    }
+
+    mirrored
+}
+
+fn children(pos: &Position) -> Vec<(Position, bool)> {
+    let castling = Castling::default();
+    let mut children = Vec::new();
+
+    pos.map_legal_moves(&castling, |mov| {
+        let zeroing = mov.is_capture() || pos.get_pc(1 << mov.src()) == Piece::PAWN;
+
+        let mut next = *pos;
+        next.make(mov, &castling);
+        children.push((next, zeroing));
+    });
+
+    children
+}
+
+/// Every legal position of `K<piece>vK` with the squares of
+/// the pieces stepping through the tables, in both colours.
+fn sample(piece: usize, step: usize) -> Vec<Position> {
+    let mut positions = Vec::new();
+
+    for slot in (0..2 * 64 * 64 * 64).step_by(step) {
+        let (stm, wk, extra, bk) = (slot >> 18, (slot >> 12) & 63, (slot >> 6) & 63, slot & 63);
+
+        if let Some(pos) = generate::position(piece, stm, wk, extra, bk) {
+            positions.push(pos);
+            positions.push(mirror(&pos));
+        }
+    }
+
+    positions
+}
+
+#[test]
+#[ignore = "rewrites the committed tables"]
+fn generate_tables() {
+    generate::write_tables(Path::new(TABLES)).unwrap();
+}
+
+#[test]
+fn probes_known_results() {
+    let tablebases = tablebases();
+
+    for (fen, wdl, dtz) in [
+        ("4k3/8/4K3/8/8/8/8/Q7 w - - 0 1", Wdl::Win, 1),
+        ("q7/8/8/8/8/4k3/8/4K3 b - - 0 1", Wdl::Win, 1),
+        ("Q3k3/8/4K3/8/8/8/8/8 b - - 0 1", Wdl::Loss, -1),
+        ("k7/8/1QK5/8/8/8/8/8 b - - 0 1", Wdl::Draw, 0),
+        ("8/8/8/8/8/8/1k6/Q6K b - - 0 1", Wdl::Draw, 0),
+        ("4k3/8/4K3/8/8/8/8/R7 w - - 0 1", Wdl::Win, 1),
+        ("R3k3/8/4K3/8/8/8/8/8 b - - 0 1", Wdl::Loss, -1),
+        ("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1", Wdl::Draw, 0),
+        ("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1", Wdl::Draw, 0),
+        ("8/8/8/8/8/8/3kP3/7K b - - 0 1", Wdl::Draw, 0),
+        ("8/8/8/8/8/8/4P3/4K2k w - - 0 1", Wdl::Win, 1),
+    ] {
+        let pos = parse(fen);
+        assert_eq!(tablebases.probe_wdl(&pos), Some(wdl), "{fen}");
+        assert_eq!(tablebases.probe_dtz(&pos), Some(dtz), "{fen}");
+    }
+
+    let pos = parse("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1");
+    assert_eq!(tablebases.probe_wdl(&pos), Some(Wdl::Loss));
+    assert!(tablebases.probe_dtz(&pos).unwrap() < -1);
+
+    // too many pieces, or castling rights
+    let pos = parse("4k3/8/4K3/8/8/8/8/QQ6 w - - 0 1");
+    assert_eq!(tablebases.probe_wdl(&pos), None);
+    let pos = parse("4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+    assert_eq!(tablebases.probe_wdl(&pos), None);
+}
+
+#[test]
+fn results_agree_with_every_move() {
+    let tablebases = tablebases();
+
+    for piece in [Piece::QUEEN, Piece::ROOK, Piece::PAWN] {
+        for pos in sample(piece, 97) {
+            let wdl = tablebases.probe_wdl(&pos).unwrap();
+            let children = children(&pos);
+
+            let best = children
+                .iter()
+                .map(|(next, _)| -tablebases.probe_wdl(next).unwrap())
+                .max();
+
+            let expected = match best {
+                Some(best) => best,
+                None if pos.in_check() => Wdl::Loss,
+                None => Wdl::Draw,
+            };
+
+            assert_eq!(wdl, expected, "{}", pos.as_fen());
+        }
+    }
+}
+
+#[test]
+fn distances_agree_with_every_move() {
+    let tablebases = tablebases();
+
+    for piece in [Piece::QUEEN, Piece::ROOK, Piece::PAWN] {
+        for pos in sample(piece, 89) {
+            let dtz = tablebases.probe_dtz(&pos).unwrap();
+            let wdl = tablebases.probe_wdl(&pos).unwrap();
+
+            // plies to the next zeroing move or mate, after each move
+            let mut options = Vec::new();
+
+            for (next, zeroing) in children(&pos) {
+                let result = -tablebases.probe_wdl(&next).unwrap();
+                let next_dtz = tablebases.probe_dtz(&next).unwrap();
+                let mated = next_dtz == -1 && children(&next).is_empty();
+
+                let plies = if zeroing || mated {
+                    1
+                } else {
+                    next_dtz.abs() + 1
+                };
+
+                options.push((result, plies));
+            }
+
+            let expected = match wdl {
+                Wdl::Win => options
+                    .iter()
+                    .filter(|&&(result, _)| result == Wdl::Win)
+                    .map(|&(_, plies)| plies)
+                    .min()
+                    .unwrap(),
+                Wdl::Loss => -options.iter().map(|&(_, plies)| plies).max().unwrap_or(1),
+                _ => 0,
+            };
+
+            assert_eq!(dtz, expected, "{}", pos.as_fen());
+        }
+    }
+}
+
+#[test]
+fn root_moves_keep_the_fastest_win() {
+    let tablebases = tablebases();
+    let pos = ChessState::from_fen("4k3/8/4K3/8/8/8/8/Q7 w - - 0 1");
+
+    let name = |moves: Vec<_>| {
+        let mut names = moves
+            .into_iter()
+            .map(|mov| pos.conv_mov_to_str(mov))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    };
+
+    let mut all = Vec::new();
+    pos.map_legal_moves(|mov| all.push(mov));
+
+    let best = tablebases.root_moves(&pos, &[]).unwrap();
+    assert_eq!(name(best.clone()), ["a1a8", "a1h8"]);
+
+    for &mov in &best {
+        let mut next = pos.clone();
+        next.make_move(mov);
+        assert!(matches!(next.game_state(), GameState::Lost(_)));
+    }
+
+    // only the given moves are considered
+    let slow = all
+        .iter()
+        .copied()
+        .filter(|mov| !best.contains(mov))
+        .collect::<Vec<_>>();
+    let restricted = tablebases.root_moves(&pos, &slow).unwrap();
+    assert!(!restricted.is_empty());
+    assert!(restricted.iter().all(|mov| slow.contains(mov)));
+
+    let restricted = tablebases.root_moves(&pos, &[best[0], slow[0]]).unwrap();
+    assert_eq!(restricted, [best[0]]);
+}
+
+#[test]
+fn root_moves_keep_the_draw() {
+    let tablebases = tablebases();
+
+    // only capturing the queen avoids losing
+    let pos = ChessState::from_fen("8/8/8/8/8/8/1k6/Q6K b - - 0 1");
+    let moves = tablebases.root_moves(&pos, &[]).unwrap();
+    assert_eq!(moves.len(), 1);
+    assert_eq!(pos.conv_mov_to_str(moves[0]), "b2a1");
+
+    // all moves lose, so those resisting longest are kept
+    let pos = ChessState::from_fen("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1");
+    let dtz = tablebases.probe_dtz(&pos.board()).unwrap();
+
+    for mov in tablebases.root_moves(&pos, &[]).unwrap() {
+        let mut next = pos.clone();
+        next.make_move(mov);
+        assert_eq!(tablebases.probe_dtz(&next.board()).unwrap(), -dtz - 1);
+    }
+}
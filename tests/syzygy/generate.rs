@@ -0,0 +1,902 @@
+//! Writes the tables in `tests/syzygy`, solving each three piece endgame by
+//! retrograde analysis and encoding the results in the Syzygy format, with
+//! the same indexing, pair compression and canonical Huffman codes as the
+//! tables from the generator.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fs, io,
+    path::Path,
+};
+
+use montyformat::chess::{Castling, Piece, Position, Side};
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const SINGLE_VALUE: u8 = 128;
+
+const BLOCK_BITS: u8 = 6;
+const SPAN_BITS: u8 = 8;
+const MAX_BLOCK_VALUES: usize = 65536 - (1 << SPAN_BITS);
+
+/// Values a symbol may expand to, as `symlen` is stored in a byte.
+const MAX_SYMBOL_VALUES: u32 = 256;
+const MAX_PAIRS: usize = 64;
+
+const SLOTS: usize = 2 * 64 * 64 * 64;
+
+pub fn slot(stm: usize, wk: usize, extra: usize, bk: usize) -> usize {
+    ((stm * 64 + wk) * 64 + extra) * 64 + bk
+}
+
+/// The position with a white king, a white `piece` and a black king, if
+/// it is legal and the piece isn't a pawn on the first or last rank.
+pub fn position(piece: usize, stm: usize, wk: usize, extra: usize, bk: usize) -> Option<Position> {
+    if wk == extra || wk == bk || extra == bk {
+        return None;
+    }
+
+    if piece == Piece::PAWN && !(8..56).contains(&extra) {
+        return None;
+    }
+
+    let fen = ["8/8/8/8/8/8/8/8 w - - 0 1", "8/8/8/8/8/8/8/8 b - - 0 1"][stm];
+    let mut pos = Position::parse_fen(fen, &mut Castling::default());
+
+    pos.toggle(Side::WHITE, Piece::KING, wk as u16);
+    pos.toggle(Side::WHITE, piece, extra as u16);
+    pos.toggle(Side::BLACK, Piece::KING, bk as u16);
+
+    let their_king = [bk, wk][stm];
+    (!pos.is_square_attacked(their_king, stm ^ 1, pos.occ())).then_some(pos)
+}
+
+fn squares(pos: &Position) -> (usize, usize, usize) {
+    let white = pos.piece(Side::WHITE);
+    let kings = pos.piece(Piece::KING);
+
+    (
+        (white & kings).trailing_zeros() as usize,
+        (white & !kings).trailing_zeros() as usize,
+        (pos.piece(Side::BLACK) & kings).trailing_zeros() as usize,
+    )
+}
+
+#[derive(Clone, Copy)]
+enum Child {
+    /// A zeroing move to another endgame, with its result for the side to
+    /// move after it.
+    Known(i8),
+    Internal {
+        slot: u32,
+        zeroing: bool,
+    },
+}
+
+/// `K<piece>vK` solved for both sides to move, indexed by `slot`.
+pub struct Endgame {
+    pub piece: usize,
+    /// The result for the side to move, -2, 0 or 2, if the position is legal.
+    pub wdl: Vec<Option<i8>>,
+    /// Plies to the next zeroing move or mate, for won and lost positions.
+    pub dtz: Vec<u8>,
+}
+
+impl Endgame {
+    /// Solve the endgame, with `promotion` giving the result of a position
+    /// just after a promotion for the side to move.
+    pub fn solve(piece: usize, promotion: impl Fn(&Position) -> i8) -> Self {
+        let castling = Castling::default();
+
+        let mut legal = vec![false; SLOTS];
+        let mut in_check = vec![false; SLOTS];
+        let mut start = vec![0u32; SLOTS + 1];
+        let mut children = Vec::new();
+
+        for (slot, legal) in legal.iter_mut().enumerate() {
+            start[slot] = children.len() as u32;
+
+            let (stm, wk, extra, bk) = (slot >> 18, (slot >> 12) & 63, (slot >> 6) & 63, slot & 63);
+            let Some(pos) = position(piece, stm, wk, extra, bk) else {
+                continue;
+            };
+
+            *legal = true;
+            in_check[slot] = pos.in_check();
+
+            pos.map_legal_moves(&castling, |mov| {
+                let mut next = pos;
+                next.make(mov, &castling);
+
+                let child = if next.occ().count_ones() == 2 {
+                    Child::Known(0)
+                } else if piece == Piece::PAWN && next.piece(Piece::PAWN) == 0 {
+                    Child::Known(promotion(&next))
+                } else {
+                    let (wk, extra, bk) = squares(&next);
+                    Child::Internal {
+                        slot: self::slot(next.stm(), wk, extra, bk) as u32,
+                        zeroing: pos.get_pc(1 << mov.src()) == Piece::PAWN,
+                    }
+                };
+
+                children.push(child);
+            });
+        }
+
+        start[SLOTS] = children.len() as u32;
+
+        let moves = |slot: usize| &children[start[slot] as usize..start[slot + 1] as usize];
+        let mated = |slot: usize| moves(slot).is_empty() && in_check[slot];
+
+        let mut wdl = vec![None; SLOTS];
+
+        for slot in 0..SLOTS {
+            if legal[slot] && moves(slot).is_empty() {
+                wdl[slot] = Some(if in_check[slot] { -2 } else { 0 });
+            }
+        }
+
+        loop {
+            let mut changed = false;
+
+            for slot in 0..SLOTS {
+                if !legal[slot] || wdl[slot].is_some() {
+                    continue;
+                }
+
+                let mut win = false;
+                let mut loss = true;
+
+                for &child in moves(slot) {
+                    match child_wdl(&wdl, child) {
+                        Some(-2) => win = true,
+                        Some(2) => {}
+                        _ => loss = false,
+                    }
+                }
+
+                if win || loss {
+                    wdl[slot] = Some(if win { 2 } else { -2 });
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        for slot in 0..SLOTS {
+            if legal[slot] && wdl[slot].is_none() {
+                wdl[slot] = Some(0);
+            }
+        }
+
+        // layer by layer, so that each pass only uses the distances
+        // found in earlier passes
+        let mut dtz = vec![0u8; SLOTS];
+
+        for pass in 1.. {
+            let mut found = Vec::new();
+
+            for slot in 0..SLOTS {
+                if dtz[slot] > 0 || wdl[slot].is_none_or(|wdl| wdl == 0) {
+                    continue;
+                }
+
+                let winning = wdl[slot] == Some(2);
+                let mut best = if winning { u8::MAX } else { 0 };
+                let mut known = true;
+
+                if mated(slot) {
+                    best = 1;
+                }
+
+                for &child in moves(slot) {
+                    let value = match child {
+                        Child::Known(_) => 1,
+                        Child::Internal { zeroing: true, .. } => 1,
+                        Child::Internal { slot, .. } if mated(slot as usize) => 1,
+                        Child::Internal { slot, .. } => match dtz[slot as usize] {
+                            0 => {
+                                known = false;
+                                continue;
+                            }
+                            dtz => dtz + 1,
+                        },
+                    };
+
+                    if winning && child_wdl(&wdl, child) == Some(-2) {
+                        best = best.min(value);
+                    } else if !winning {
+                        best = best.max(value);
+                    }
+                }
+
+                if (winning && best <= pass) || (!winning && known) {
+                    assert_eq!(best, pass);
+                    found.push(slot);
+                }
+            }
+
+            if found.is_empty() {
+                break;
+            }
+
+            for slot in found {
+                dtz[slot] = pass;
+            }
+        }
+
+        for slot in 0..SLOTS {
+            if wdl[slot].is_some_and(|wdl| wdl != 0) {
+                assert!(dtz[slot] > 0 && dtz[slot] <= 100);
+            }
+        }
+
+        Self { piece, wdl, dtz }
+    }
+
+    pub fn wdl_of(&self, pos: &Position) -> i8 {
+        let (wk, extra, bk) = squares(pos);
+        self.wdl[slot(pos.stm(), wk, extra, bk)].unwrap()
+    }
+}
+
+fn child_wdl(wdl: &[Option<i8>], child: Child) -> Option<i8> {
+    match child {
+        Child::Known(wdl) => Some(wdl),
+        Child::Internal { slot, .. } => wdl[slot as usize],
+    }
+}
+
+fn off_diagonal(sq: usize) -> i32 {
+    (sq >> 3) as i32 - (sq & 7) as i32
+}
+
+/// Index of three different pieces, the first being in the a1-d1-d4
+/// triangle, as `encode_unique` in the probing code.
+fn encode_unique(sq: [usize; 3]) -> usize {
+    let below = |sq: usize| (0..sq).filter(|&s| off_diagonal(s) < 0).count();
+    let triangle = |sq: usize| {
+        let mut order = (0..28)
+            .filter(|&s| s & 7 <= 3 && off_diagonal(s) < 0)
+            .collect::<Vec<_>>();
+        order.extend((0..28).filter(|&s| s & 7 <= 3 && off_diagonal(s) == 0));
+        order.iter().position(|&s| s == sq).unwrap()
+    };
+
+    let adjust1 = usize::from(sq[1] > sq[0]);
+    let adjust2 = usize::from(sq[2] > sq[0]) + usize::from(sq[2] > sq[1]);
+
+    if off_diagonal(sq[0]) != 0 {
+        (triangle(sq[0]) * 63 + sq[1] - adjust1) * 62 + sq[2] - adjust2
+    } else if off_diagonal(sq[1]) != 0 {
+        (6 * 63 + (sq[0] >> 3) * 28 + below(sq[1])) * 62 + sq[2] - adjust2
+    } else if off_diagonal(sq[2]) != 0 {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + (sq[0] >> 3) * 7 * 28
+            + ((sq[1] >> 3) - adjust1) * 28
+            + below(sq[2])
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + (sq[0] >> 3) * 6 * 7
+            + ((sq[1] >> 3) - adjust1) * 6
+            + (sq[2] >> 3)
+            - adjust2
+    }
+}
+
+/// The pieces of one sub-table, in the order they are encoded, and the
+/// position of the leading group among the groups.
+#[derive(Clone, Copy)]
+struct Layout {
+    pieces: [u8; 3],
+    order: u8,
+}
+
+impl Layout {
+    /// Multipliers of the three single piece groups of a pawn table.
+    fn pawn_multipliers(self) -> [usize; 3] {
+        let sizes = [6, 63, 62];
+        let mut multipliers = [0; 3];
+        let mut idx = 1;
+        let mut next = 1;
+
+        for k in 0..3 {
+            let group = if k == usize::from(self.order) {
+                0
+            } else {
+                next += 1;
+                next - 1
+            };
+
+            multipliers[group] = idx;
+            idx *= sizes[group];
+        }
+
+        multipliers
+    }
+
+    /// The sub-table file and index of `pos`.
+    fn index(self, pos: &Position) -> (usize, usize) {
+        let mut sq = self.pieces.map(|code| {
+            let side = usize::from(code >> 3);
+            let piece = usize::from(code & 7) + 1;
+            (pos.piece(side) & pos.piece(piece)).trailing_zeros() as usize
+        });
+
+        if sq[0] & 7 > 3 {
+            sq = sq.map(|sq| sq ^ 7);
+        }
+
+        if self.pieces[0] == 1 {
+            let [lead, first, second] = self.pawn_multipliers();
+            let n1 = sq[1] - usize::from(sq[1] > sq[0]);
+            let n2 = sq[2] - usize::from(sq[2] > sq[0]) - usize::from(sq[2] > sq[1]);
+
+            return (
+                sq[0] & 7,
+                ((sq[0] >> 3) - 1) * lead + n1 * first + n2 * second,
+            );
+        }
+
+        if sq[0] >> 3 > 3 {
+            sq = sq.map(|sq| sq ^ 56);
+        }
+
+        if let Some(i) = (0..3).find(|&i| off_diagonal(sq[i]) != 0) {
+            if off_diagonal(sq[i]) > 0 {
+                for sq in &mut sq[i..] {
+                    *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                }
+            }
+        }
+
+        (0, encode_unique(sq))
+    }
+}
+
+/// The values of one sub-table and how they are stored.
+struct Part {
+    layout: Layout,
+    flags: u8,
+    values: Vec<u16>,
+    map: Option<[Vec<u8>; 4]>,
+}
+
+impl Part {
+    /// Collect `value` for every legal position with `stm` to move, in the
+    /// pawn file `file`, leaving the rest to extend the preceding values.
+    fn new(
+        endgame: &Endgame,
+        layout: Layout,
+        stm: usize,
+        file: usize,
+        mut value: impl FnMut(i8, u8) -> Option<u16>,
+    ) -> Self {
+        let size = if endgame.piece == Piece::PAWN {
+            6 * 63 * 62
+        } else {
+            31332
+        };
+
+        let mut values = vec![None; size];
+
+        for slot in (stm << 18)..((stm + 1) << 18) {
+            let Some(wdl) = endgame.wdl[slot] else {
+                continue;
+            };
+
+            let (wk, extra, bk) = ((slot >> 12) & 63, (slot >> 6) & 63, slot & 63);
+            let pos = position(endgame.piece, stm, wk, extra, bk).unwrap();
+            let (pos_file, idx) = layout.index(&pos);
+
+            if pos_file != file {
+                continue;
+            }
+
+            if let Some(value) = value(wdl, endgame.dtz[slot]) {
+                assert!(values[idx].is_none_or(|v| v == value), "index clash");
+                values[idx] = Some(value);
+            }
+        }
+
+        let mut last = values.iter().flatten().next().copied().unwrap_or(0);
+        let values = values
+            .into_iter()
+            .map(|value| {
+                last = value.unwrap_or(last);
+                last
+            })
+            .collect();
+
+        Self {
+            layout,
+            flags: 0,
+            values,
+            map: None,
+        }
+    }
+
+    /// Store values through a map of the distinct values, for DTZ tables.
+    fn mapped(mut self, list: usize) -> Self {
+        let mut distinct = self.values.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+
+        let mut map: [Vec<u8>; 4] = Default::default();
+        map[list] = distinct.iter().map(|&value| value as u8).collect();
+
+        for value in &mut self.values {
+            *value = distinct.binary_search(value).unwrap() as u16;
+        }
+
+        self.flags |= MAPPED;
+        self.map = Some(map);
+        self
+    }
+}
+
+/// Compressed values, as in the file after the flags of a sub-table.
+#[derive(Default)]
+struct Compressed {
+    sizes: Vec<u8>,
+    sparse: Vec<u8>,
+    lengths: Vec<u8>,
+    data: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+enum Symbol {
+    Value(u16),
+    Pair(u16, u16),
+}
+
+/// Replace the most frequent pairs of adjacent symbols with new symbols.
+fn pair_up(values: &[u16]) -> (Vec<Symbol>, Vec<u32>, Vec<u16>) {
+    let mut distinct = values.to_vec();
+    distinct.sort_unstable();
+    distinct.dedup();
+
+    let mut symbols = distinct
+        .iter()
+        .map(|&v| Symbol::Value(v))
+        .collect::<Vec<_>>();
+    let mut lens = vec![1; symbols.len()];
+    let mut seq = values
+        .iter()
+        .map(|v| distinct.binary_search(v).unwrap() as u16)
+        .collect::<Vec<_>>();
+
+    for _ in 0..MAX_PAIRS {
+        let mut counts = HashMap::new();
+
+        for pair in seq.windows(2) {
+            if lens[usize::from(pair[0])] + lens[usize::from(pair[1])] <= MAX_SYMBOL_VALUES {
+                *counts.entry((pair[0], pair[1])).or_insert(0u32) += 1;
+            }
+        }
+
+        let Some((&(left, right), &count)) = counts
+            .iter()
+            .max_by_key(|&(&pair, &count)| (count, Reverse(pair)))
+        else {
+            break;
+        };
+
+        if count < 8 {
+            break;
+        }
+
+        let symbol = symbols.len() as u16;
+        symbols.push(Symbol::Pair(left, right));
+        lens.push(lens[usize::from(left)] + lens[usize::from(right)]);
+
+        let mut paired = Vec::with_capacity(seq.len());
+        let mut i = 0;
+
+        while i < seq.len() {
+            if i + 1 < seq.len() && (seq[i], seq[i + 1]) == (left, right) {
+                paired.push(symbol);
+                i += 2;
+            } else {
+                paired.push(seq[i]);
+                i += 1;
+            }
+        }
+
+        seq = paired;
+    }
+
+    (symbols, lens, seq)
+}
+
+/// Huffman code lengths, with every symbol given a code.
+fn code_lengths(freqs: &[u64]) -> Vec<u8> {
+    let mut parents = vec![usize::MAX; freqs.len()];
+    let mut heap = freqs
+        .iter()
+        .enumerate()
+        .map(|(i, &freq)| Reverse((freq.max(1), i)))
+        .collect::<BinaryHeap<_>>();
+
+    while heap.len() > 1 {
+        let Reverse((a, i)) = heap.pop().unwrap();
+        let Reverse((b, j)) = heap.pop().unwrap();
+
+        let node = parents.len();
+        parents.push(usize::MAX);
+        parents[i] = node;
+        parents[j] = node;
+        heap.push(Reverse((a + b, node)));
+    }
+
+    (0..freqs.len())
+        .map(|mut node| {
+            let mut len = 0;
+            while parents[node] != usize::MAX {
+                node = parents[node];
+                len += 1;
+            }
+            len
+        })
+        .collect()
+}
+
+fn compress(values: &[u16]) -> Compressed {
+    let (symbols, lens, seq) = pair_up(values);
+
+    let mut freqs = vec![0; symbols.len()];
+    for &sym in &seq {
+        freqs[usize::from(sym)] += 1;
+    }
+
+    let lengths = code_lengths(&freqs);
+    let min_len = *lengths.iter().min().unwrap();
+    let max_len = *lengths.iter().max().unwrap();
+    assert!(max_len <= 32);
+
+    // longer codes come first, and within each length codes are
+    // consecutive from `base`, the shortest codes being the largest
+    let mut by_length = (0..symbols.len()).collect::<Vec<_>>();
+    by_length.sort_by_key(|&sym| (Reverse(lengths[sym]), sym));
+
+    let mut renamed = vec![0u16; symbols.len()];
+    for (new, &old) in by_length.iter().enumerate() {
+        renamed[old] = new as u16;
+    }
+
+    let groups = usize::from(max_len - min_len) + 1;
+    let mut counts = vec![0u64; groups];
+    for &len in &lengths {
+        counts[usize::from(len - min_len)] += 1;
+    }
+
+    let mut lowest = vec![0u64; groups];
+    let mut base = vec![0u64; groups];
+    for i in (0..groups - 1).rev() {
+        lowest[i] = lowest[i + 1] + counts[i + 1];
+        assert_eq!((base[i + 1] + counts[i + 1]) % 2, 0);
+        base[i] = (base[i + 1] + counts[i + 1]) / 2;
+    }
+    assert_eq!(base[0] + counts[0], 1 << min_len);
+
+    let code = |sym: usize| {
+        let len = lengths[sym];
+        let group = usize::from(len - min_len);
+        (base[group] + u64::from(renamed[sym]) - lowest[group], len)
+    };
+
+    let mut out = Compressed::default();
+    out.sizes.extend([BLOCK_BITS, SPAN_BITS, 0]);
+
+    // pack whole symbols into blocks
+    let block_size = 1usize << BLOCK_BITS;
+    let mut blocks = Vec::new();
+    let mut starts = Vec::new();
+    let mut bits = Vec::new();
+    let mut count = 0;
+    let mut total = 0;
+
+    for &sym in &seq {
+        let sym = usize::from(sym);
+        let (value, len) = code(sym);
+        let values = lens[sym] as usize;
+
+        if bits.len() + usize::from(len) > block_size * 8 || count + values > MAX_BLOCK_VALUES {
+            blocks.push((std::mem::take(&mut bits), count));
+            starts.push(total - count);
+            count = 0;
+        }
+
+        bits.extend((0..len).rev().map(|bit| (value >> bit) & 1 == 1));
+        count += values;
+        total += values;
+    }
+
+    blocks.push((bits, count));
+    starts.push(total - count);
+
+    out.sizes.extend((blocks.len() as u32).to_le_bytes());
+    out.sizes.extend([max_len, min_len]);
+
+    for &lowest in &lowest {
+        out.sizes.extend((lowest as u16).to_le_bytes());
+    }
+
+    out.sizes.extend((symbols.len() as u16).to_le_bytes());
+
+    for &old in &by_length {
+        let (left, right) = match symbols[old] {
+            Symbol::Value(value) => (value, 0xFFF),
+            Symbol::Pair(left, right) => (renamed[usize::from(left)], renamed[usize::from(right)]),
+        };
+
+        out.sizes.extend([
+            left as u8,
+            ((left >> 8) | (right << 4)) as u8,
+            (right >> 4) as u8,
+        ]);
+    }
+
+    if symbols.len() % 2 == 1 {
+        out.sizes.push(0);
+    }
+
+    // the sparse index locates the middle value of each span
+    let span = 1usize << SPAN_BITS;
+    for k in 0..values.len().div_ceil(span) {
+        let mid = k * span + span / 2;
+        let block = starts.partition_point(|&start| start <= mid) - 1;
+        let offset = mid - starts[block];
+
+        out.sparse.extend((block as u32).to_le_bytes());
+        out.sparse
+            .extend(u16::try_from(offset).unwrap().to_le_bytes());
+    }
+
+    for (bits, count) in blocks {
+        out.lengths.extend(((count - 1) as u16).to_le_bytes());
+
+        let mut block = vec![0u8; block_size];
+        for (i, &bit) in bits.iter().enumerate() {
+            block[i / 8] |= u8::from(bit) << (7 - i % 8);
+        }
+
+        out.data.extend(block);
+    }
+
+    out
+}
+
+/// Write a table from its sub-tables, by file and then side to move.
+fn write(path: &Path, magic: [u8; 4], has_pawns: bool, parts: &[Vec<Part>]) -> io::Result<()> {
+    // none of the tables are for symmetric material, so all are split
+    let mut out = magic.to_vec();
+    out.push(1 | u8::from(has_pawns) << 1);
+
+    for file in parts {
+        let nibbles = |f: &dyn Fn(&Part) -> u8| f(&file[0]) | file.get(1).map_or(0, |p| f(p) << 4);
+
+        out.push(nibbles(&|part| part.layout.order));
+
+        for k in 0..3 {
+            out.push(nibbles(&|part| part.layout.pieces[k]));
+        }
+    }
+
+    out.resize(out.len() + out.len() % 2, 0);
+
+    let mut compressed = Vec::new();
+
+    for part in parts.iter().flatten() {
+        let first = part.values[0];
+
+        if part.values.iter().all(|&value| value == first) {
+            out.extend([part.flags | SINGLE_VALUE, first as u8]);
+            compressed.push(Compressed::default());
+        } else {
+            let part_compressed = compress(&part.values);
+            out.push(part.flags);
+            out.extend(&part_compressed.sizes);
+            compressed.push(part_compressed);
+        }
+    }
+
+    if magic == DTZ_MAGIC {
+        for part in parts.iter().flatten() {
+            for list in part.map.iter().flatten() {
+                out.push(list.len() as u8);
+                out.extend(list);
+            }
+        }
+
+        out.resize(out.len() + out.len() % 2, 0);
+    }
+
+    for part in &compressed {
+        out.extend(&part.sparse);
+    }
+
+    for part in &compressed {
+        out.extend(&part.lengths);
+    }
+
+    for part in &compressed {
+        out.resize(out.len().next_multiple_of(64), 0);
+        out.extend(&part.data);
+    }
+
+    fs::write(path, out)
+}
+
+fn code(side: usize, piece: usize) -> u8 {
+    (piece as u8 - 1) | (side as u8) << 3
+}
+
+/// Write `KQvK`, `KRvK`, `KPvK`, `KBvK` and `KNvK` tables to `dir`.
+pub fn write_tables(dir: &Path) -> io::Result<()> {
+    let draw = |_: &Position| 0;
+    let queen = Endgame::solve(Piece::QUEEN, draw);
+    let rook = Endgame::solve(Piece::ROOK, draw);
+    let pawn = Endgame::solve(Piece::PAWN, |pos| {
+        match pos.get_pc(pos.piece(Side::WHITE) & !pos.piece(Piece::KING)) {
+            Piece::QUEEN => queen.wdl_of(pos),
+            Piece::ROOK => rook.wdl_of(pos),
+            _ => 0,
+        }
+    });
+
+    let wdl = |wdl: i8, _| Some((wdl + 2) as u16);
+
+    for (endgame, name) in [(&queen, "KQvK"), (&rook, "KRvK")] {
+        let [king, piece, their_king] = [
+            code(Side::WHITE, Piece::KING),
+            code(Side::WHITE, endgame.piece),
+            code(Side::BLACK, Piece::KING),
+        ];
+
+        // the sides are encoded with the pieces in different orders
+        let layouts = [
+            Layout {
+                pieces: [piece, king, their_king],
+                order: 0,
+            },
+            Layout {
+                pieces: [their_king, king, piece],
+                order: 0,
+            },
+        ];
+
+        let parts = vec![vec![
+            Part::new(endgame, layouts[0], 0, 0, wdl),
+            Part::new(endgame, layouts[1], 1, 0, wdl),
+        ]];
+
+        write(&dir.join(format!("{name}.rtbw")), WDL_MAGIC, false, &parts)?;
+    }
+
+    // distances for white to move are all odd, so are stored in moves
+    let queen_dtz = Part::new(
+        &queen,
+        Layout {
+            pieces: [
+                code(Side::BLACK, Piece::KING),
+                code(Side::WHITE, Piece::QUEEN),
+                code(Side::WHITE, Piece::KING),
+            ],
+            order: 0,
+        },
+        0,
+        0,
+        |wdl, dtz| (wdl != 0).then(|| u16::from(dtz / 2)),
+    );
+
+    write(&dir.join("KQvK.rtbz"), DTZ_MAGIC, false, &[vec![queen_dtz]])?;
+
+    // stored for black to move, which is always losing or drawn
+    let mut rook_dtz = Part::new(
+        &rook,
+        Layout {
+            pieces: [
+                code(Side::WHITE, Piece::ROOK),
+                code(Side::BLACK, Piece::KING),
+                code(Side::WHITE, Piece::KING),
+            ],
+            order: 0,
+        },
+        1,
+        0,
+        |wdl, dtz| (wdl != 0).then(|| u16::from(dtz - 1)),
+    )
+    .mapped(1);
+    rook_dtz.flags |= STM | LOSS_PLIES;
+
+    write(&dir.join("KRvK.rtbz"), DTZ_MAGIC, false, &[vec![rook_dtz]])?;
+
+    let [pawn_code, king, their_king] = [
+        code(Side::WHITE, Piece::PAWN),
+        code(Side::WHITE, Piece::KING),
+        code(Side::BLACK, Piece::KING),
+    ];
+
+    // the leading pawn is encoded first, last or in between
+    let parts = (0..4)
+        .map(|file| {
+            let layouts = [
+                Layout {
+                    pieces: [pawn_code, king, their_king],
+                    order: [0, 1, 2, 0][file],
+                },
+                Layout {
+                    pieces: [pawn_code, their_king, king],
+                    order: [2, 0, 1, 1][file],
+                },
+            ];
+
+            vec![
+                Part::new(&pawn, layouts[0], 0, file, wdl),
+                Part::new(&pawn, layouts[1], 1, file, wdl),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    write(&dir.join("KPvK.rtbw"), WDL_MAGIC, true, &parts)?;
+
+    let parts = (0..4)
+        .map(|file| {
+            let mut part = Part::new(
+                &pawn,
+                Layout {
+                    pieces: [pawn_code, their_king, king],
+                    order: [0, 2, 1, 0][file],
+                },
+                0,
+                file,
+                |wdl, dtz| (wdl != 0).then(|| u16::from(dtz - 1)),
+            );
+
+            part.flags |= WIN_PLIES | LOSS_PLIES;
+            vec![part]
+        })
+        .collect::<Vec<_>>();
+
+    write(&dir.join("KPvK.rtbz"), DTZ_MAGIC, true, &parts)?;
+
+    // drawn whatever the position, so stored as a single value
+    for (piece, name) in [(Piece::BISHOP, "KBvK"), (Piece::KNIGHT, "KNvK")] {
+        let part = || Part {
+            layout: Layout {
+                pieces: [
+                    code(Side::WHITE, piece),
+                    code(Side::WHITE, Piece::KING),
+                    code(Side::BLACK, Piece::KING),
+                ],
+                order: 0,
+            },
+            flags: 0,
+            values: vec![2],
+            map: None,
+        };
+
+        write(
+            &dir.join(format!("{name}.rtbw")),
+            WDL_MAGIC,
+            false,
+            &[vec![part(), part()]],
+        )?;
+    }
+
+    Ok(())
+}
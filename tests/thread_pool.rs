@@ -0,0 +1,101 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use monty::mcts::ThreadPool;
+
+#[test]
+fn runs_jobs_borrowing_the_stack() {
+    let pool = ThreadPool::default();
+    let counts = (0..8).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+
+    pool.scope(|s| {
+        for count in &counts {
+            s.spawn(move || {
+                count.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+    });
+
+    assert!(counts
+        .iter()
+        .all(|count| count.load(Ordering::Relaxed) == 1));
+}
+
+#[test]
+fn reuses_parked_workers() {
+    let pool = ThreadPool::default();
+    let ids = Mutex::new(HashSet::new());
+
+    for _ in 0..50 {
+        pool.scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    ids.lock().unwrap().insert(thread::current().id());
+                });
+            }
+        });
+    }
+
+    assert!(ids.into_inner().unwrap().len() <= 4);
+}
+
+#[test]
+fn nested_scopes_use_other_workers() {
+    let pool = ThreadPool::default();
+    let total = AtomicUsize::new(0);
+
+    pool.scope(|s| {
+        s.spawn(|| {
+            pool.scope(|s| {
+                for _ in 0..3 {
+                    s.spawn(|| {
+                        total.fetch_add(1, Ordering::Relaxed);
+                    });
+                }
+            });
+        });
+    });
+
+    assert_eq!(total.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+#[should_panic(expected = "a thread in the pool panicked")]
+fn propagates_panics() {
+    let pool = ThreadPool::default();
+
+    pool.scope(|s| {
+        s.spawn(|| panic!("search thread failed"));
+    });
+}
+
+#[test]
+fn affinity_can_be_toggled() {
+    let pool = ThreadPool::default();
+    let supported = pool.set_affinity(true);
+    assert_eq!(supported, cfg!(target_os = "linux"));
+
+    let total = AtomicUsize::new(0);
+    pool.scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| {
+                total.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+    });
+
+    pool.set_affinity(false);
+    pool.scope(|s| {
+        s.spawn(|| {
+            total.fetch_add(1, Ordering::Relaxed);
+        });
+    });
+
+    assert_eq!(total.load(Ordering::Relaxed), 3);
+}